@@ -0,0 +1,49 @@
+//! Appends to the tail of a value one element at a time, once by reallocating it in place with
+//! `with_extended_tail`, and once by building a new value and dropping the old one.
+//!
+//! Run with `cargo bench --bench extend`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use dyn_struct::DynStruct;
+
+const APPENDS: [usize; 3] = [16, 256, 4096];
+
+fn extend(appends: usize) -> Box<DynStruct<u64, u32>> {
+    let mut value = DynStruct::new(0, []);
+    for i in 0..appends {
+        value = DynStruct::with_extended_tail(value, &[i as u32]);
+    }
+    value
+}
+
+fn rebuild(appends: usize) -> Box<DynStruct<u64, u32>> {
+    let mut value = DynStruct::new(0, []);
+    for i in 0..appends {
+        let tail = value.tail.iter().copied().chain(Some(i as u32));
+        value = DynStruct::new(value.header, tail.collect::<Vec<_>>());
+    }
+    value
+}
+
+fn appends(c: &mut Criterion) {
+    assert_eq!(&extend(100).tail, &rebuild(100).tail);
+
+    let mut group = c.benchmark_group("appends");
+    for appends in APPENDS {
+        group.throughput(Throughput::Elements(appends as u64));
+        group.bench_with_input(
+            BenchmarkId::new("with_extended_tail", appends),
+            &appends,
+            |b, &appends| b.iter(|| extend(appends)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("rebuild", appends),
+            &appends,
+            |b, &appends| b.iter(|| rebuild(appends)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, appends);
+criterion_main!(benches);
@@ -0,0 +1,211 @@
+//! Compares a `Box<DynStruct>` against the usual ways of keeping a header next to a list, with
+//! tails of 8 bytes, 1 KiB and 1 MiB:
+//!
+//! - `dyn_struct`: `Box<DynStruct<Header, u64>>`, with the header and the tail in one allocation.
+//! - `boxed_vec`: `Box<(Header, Vec<u64>)>`, which reaches the header through one pointer, like a
+//!   `Box<DynStruct>`, and the tail through a second allocation.
+//! - `boxed_slice`: `(Header, Box<[u64]>)`, with the header inline and the tail in its own
+//!   allocation.
+//!
+//! Run with `cargo bench --bench layout`. To compare a change against the current state, save a
+//! baseline first with `cargo bench --bench layout -- --save-baseline before`, and then pass
+//! `-- --baseline before` after making the change.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use dyn_struct::DynStruct;
+
+/// The length of the tail, and where to read from it.
+type Header = (u32, u32);
+
+const SIZES: [(&str, usize); 3] = [("8B", 1), ("1KiB", 128), ("1MiB", 128 * 1024)];
+
+/// Returns the number of values to scan, so that they take up about 32 MiB, which is more than
+/// the caches hold.
+fn count(len: usize) -> usize {
+    ((32 << 20) / (len * 8 + 32)).clamp(16, 1 << 16)
+}
+
+/// A cheap pseudo-random sequence, so that the values are visited in an unpredictable order.
+fn shuffle(state: &mut u64) -> u64 {
+    *state = state
+        .wrapping_mul(6364136223846793005)
+        .wrapping_add(1442695040888963407);
+    *state >> 33
+}
+
+fn tail(len: usize) -> Vec<u64> {
+    (0..len as u64).collect()
+}
+
+fn header(len: usize, seed: u64) -> Header {
+    (len as u32, (seed % len as u64) as u32)
+}
+
+fn construct(c: &mut Criterion) {
+    let mut group = c.benchmark_group("construct");
+    for (name, len) in SIZES {
+        group.throughput(Throughput::Bytes(len as u64 * 8));
+        group.bench_function(BenchmarkId::new("dyn_struct", name), |b| {
+            b.iter_batched(
+                || tail(len),
+                |tail| DynStruct::from_vec(header(len, 0), tail),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_function(BenchmarkId::new("boxed_vec", name), |b| {
+            b.iter_batched(
+                || tail(len),
+                |tail| Box::new((header(len, 0), tail)),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_function(BenchmarkId::new("boxed_slice", name), |b| {
+            b.iter_batched(
+                || tail(len),
+                |tail| (header(len, 0), tail.into_boxed_slice()),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn clone(c: &mut Criterion) {
+    let mut group = c.benchmark_group("clone");
+    for (name, len) in SIZES {
+        group.throughput(Throughput::Bytes(len as u64 * 8));
+        let value = DynStruct::from_vec(header(len, 0), tail(len));
+        group.bench_function(BenchmarkId::new("dyn_struct", name), |b| {
+            b.iter(|| DynStruct::from_slice(value.header, &value.tail))
+        });
+        let value = Box::new((header(len, 0), tail(len)));
+        group.bench_function(BenchmarkId::new("boxed_vec", name), |b| {
+            b.iter(|| value.clone())
+        });
+        let value = (header(len, 0), tail(len).into_boxed_slice());
+        group.bench_function(BenchmarkId::new("boxed_slice", name), |b| {
+            b.iter(|| value.clone())
+        });
+    }
+    group.finish();
+}
+
+/// Reads one element of the tail of each of many values, in a random order, at the position
+/// given by its header. This is where keeping the header and the tail together should pay off.
+fn random_access(c: &mut Criterion) {
+    const LOOKUPS: usize = 1024;
+
+    let mut group = c.benchmark_group("random_access");
+    group.throughput(Throughput::Elements(LOOKUPS as u64));
+    for (name, len) in SIZES {
+        let count = count(len);
+        let mut seed = 1;
+        let headers: Vec<Header> = (0..count)
+            .map(|_| header(len, shuffle(&mut seed)))
+            .collect();
+        let order: Vec<usize> = (0..LOOKUPS)
+            .map(|_| shuffle(&mut seed) as usize % count)
+            .collect();
+
+        let values: Vec<Box<DynStruct<Header, u64>>> = headers
+            .iter()
+            .map(|&header| DynStruct::from_vec(header, tail(len)))
+            .collect();
+        group.bench_function(BenchmarkId::new("dyn_struct", name), |b| {
+            b.iter(|| {
+                order
+                    .iter()
+                    .map(|&i| {
+                        let value = &values[i];
+                        value.tail[value.header.1 as usize]
+                    })
+                    .sum::<u64>()
+            })
+        });
+        drop(values);
+
+        let values: Vec<Box<(Header, Vec<u64>)>> = headers
+            .iter()
+            .map(|&header| Box::new((header, tail(len))))
+            .collect();
+        group.bench_function(BenchmarkId::new("boxed_vec", name), |b| {
+            b.iter(|| {
+                order
+                    .iter()
+                    .map(|&i| {
+                        let (header, tail) = &*values[i];
+                        tail[header.1 as usize]
+                    })
+                    .sum::<u64>()
+            })
+        });
+        drop(values);
+
+        let values: Vec<(Header, Box<[u64]>)> = headers
+            .iter()
+            .map(|&header| (header, tail(len).into_boxed_slice()))
+            .collect();
+        group.bench_function(BenchmarkId::new("boxed_slice", name), |b| {
+            b.iter(|| {
+                order
+                    .iter()
+                    .map(|&i| {
+                        let (header, tail) = &values[i];
+                        tail[header.1 as usize]
+                    })
+                    .sum::<u64>()
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Sums the tails of 16 values, one after the other.
+fn iterate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("iterate");
+    for (name, len) in SIZES {
+        group.throughput(Throughput::Bytes(16 * len as u64 * 8));
+
+        let values: Vec<Box<DynStruct<Header, u64>>> = (0..16)
+            .map(|_| DynStruct::from_vec(header(len, 0), tail(len)))
+            .collect();
+        group.bench_function(BenchmarkId::new("dyn_struct", name), |b| {
+            b.iter(|| {
+                black_box(&values)
+                    .iter()
+                    .map(|value| value.tail.iter().sum::<u64>())
+                    .sum::<u64>()
+            })
+        });
+
+        let values: Vec<Box<(Header, Vec<u64>)>> = (0..16)
+            .map(|_| Box::new((header(len, 0), tail(len))))
+            .collect();
+        group.bench_function(BenchmarkId::new("boxed_vec", name), |b| {
+            b.iter(|| {
+                black_box(&values)
+                    .iter()
+                    .map(|value| value.1.iter().sum::<u64>())
+                    .sum::<u64>()
+            })
+        });
+
+        let values: Vec<(Header, Box<[u64]>)> = (0..16)
+            .map(|_| (header(len, 0), tail(len).into_boxed_slice()))
+            .collect();
+        group.bench_function(BenchmarkId::new("boxed_slice", name), |b| {
+            b.iter(|| {
+                black_box(&values)
+                    .iter()
+                    .map(|value| value.1.iter().sum::<u64>())
+                    .sum::<u64>()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, construct, clone, random_access, iterate);
+criterion_main!(benches);
@@ -0,0 +1,70 @@
+//! Builds tails of 64 Ki and 8 Mi elements from a parallel iterator, once with `from_par_iter`,
+//! which writes the elements straight into the value, and once by collecting them into a `Vec` with
+//! `rayon` first and copying that into the value with `from_vec`. A sequential `DynStruct::new` is
+//! measured as well, for reference.
+//!
+//! Every element is computed once cheaply, where the copy dominates, and once with a more
+//! expensive function, where the parallel work does.
+//!
+//! Run with `cargo bench --bench par_iter --features rayon`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use dyn_struct::DynStruct;
+use rayon::prelude::*;
+
+fn cheap(i: u64) -> u64 {
+    i.wrapping_mul(i)
+}
+
+fn expensive(i: u64) -> u64 {
+    (0..64).fold(i, |state, _| {
+        state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407)
+            .rotate_left(17)
+    })
+}
+
+const LENGTHS: [(&str, usize); 2] = [("64Ki", 64 << 10), ("8Mi", 8 << 20)];
+
+fn par_iter(len: usize, f: fn(u64) -> u64) -> Box<DynStruct<u32, u64>> {
+    DynStruct::from_par_iter(0, (0..len).into_par_iter().map(|i| f(i as u64)))
+}
+
+fn collect_then_copy(len: usize, f: fn(u64) -> u64) -> Box<DynStruct<u32, u64>> {
+    let tail: Vec<u64> = (0..len).into_par_iter().map(|i| f(i as u64)).collect();
+    DynStruct::from_vec(0, tail)
+}
+
+fn sequential(len: usize, f: fn(u64) -> u64) -> Box<DynStruct<u32, u64>> {
+    DynStruct::new(0, (0..len).map(|i| f(i as u64)))
+}
+
+/// Measures building the value and dropping it again.
+fn build(c: &mut Criterion) {
+    for (group, f) in [("cheap", cheap as fn(u64) -> u64), ("expensive", expensive)] {
+        let mut group = c.benchmark_group(group);
+        // The expensive elements take more than half a second for the longer tail.
+        group.sample_size(10);
+        for (name, len) in LENGTHS {
+            assert_eq!(*par_iter(len, f), *sequential(len, f));
+
+            group.throughput(Throughput::Elements(len as u64));
+            group.bench_with_input(BenchmarkId::new("from_par_iter", name), &len, |b, &len| {
+                b.iter(|| par_iter(len, f))
+            });
+            group.bench_with_input(
+                BenchmarkId::new("collect_then_copy", name),
+                &len,
+                |b, &len| b.iter(|| collect_then_copy(len, f)),
+            );
+            group.bench_with_input(BenchmarkId::new("sequential", name), &len, |b, &len| {
+                b.iter(|| sequential(len, f))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, build);
+criterion_main!(benches);
@@ -0,0 +1,106 @@
+//! Builds many values with a mix of tail lengths, mostly short and some long, once as
+//! `Box<DynStruct>` and once as `SmallDynStruct`. Before timing them, it prints how many allocations
+//! each takes.
+//!
+//! Run with `cargo bench --bench small`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use dyn_struct::{DynStruct, SmallDynStruct};
+
+/// Counts the calls to `alloc`, so that the two representations can be compared.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Up to this many bytes of tail are stored inline.
+const INLINE: usize = 32;
+
+/// The numbers of values to build.
+const COUNTS: [usize; 2] = [1_000, 100_000];
+
+/// The tail lengths of the workload: nine in ten values are short, like names or small lists,
+/// and the rest are up to a few hundred bytes long.
+fn lengths(count: usize) -> Vec<usize> {
+    let mut state = 1u64;
+    (0..count)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let random = (state >> 33) as usize;
+            if random % 10 == 0 {
+                random % 512
+            } else {
+                random % INLINE
+            }
+        })
+        .collect()
+}
+
+fn boxed(lengths: &[usize]) -> Vec<Box<DynStruct<u32, u8>>> {
+    lengths
+        .iter()
+        .map(|&len| DynStruct::new(len as u32, (0..len).map(|i| i as u8)))
+        .collect()
+}
+
+fn small(lengths: &[usize]) -> Vec<SmallDynStruct<u32, u8, INLINE>> {
+    lengths
+        .iter()
+        .map(|&len| SmallDynStruct::new(len as u32, (0..len).map(|i| i as u8)))
+        .collect()
+}
+
+/// Returns the number of allocations made by building the values.
+fn allocations<T>(lengths: &[usize], f: fn(&[usize]) -> Vec<T>) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    drop(black_box(f(lengths)));
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build");
+    for count in COUNTS {
+        let lengths = lengths(count);
+        for (boxed, small) in boxed(&lengths).iter().zip(small(&lengths)) {
+            assert_eq!(**boxed, *small);
+        }
+        let boxed_allocations = allocations(&lengths, boxed);
+        let small_allocations = allocations(&lengths, small);
+        println!(
+            "{} values: Box<DynStruct> {} allocations, SmallDynStruct {} allocations",
+            count, boxed_allocations, small_allocations,
+        );
+        assert!(small_allocations < boxed_allocations);
+
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::new("boxed", count), &lengths, |b, lengths| {
+            b.iter(|| boxed(lengths))
+        });
+        group.bench_with_input(BenchmarkId::new("small", count), &lengths, |b, lengths| {
+            b.iter(|| small(lengths))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, build);
+criterion_main!(benches);
@@ -0,0 +1,41 @@
+//! Allocates values with tails of zeros of up to 64 MiB, once with `new_zeroed`, which asks the
+//! allocator for zeroed memory, and once by writing every element.
+//!
+//! Large zeroed allocations usually come straight from the operating system as fresh pages, which
+//! are already zero and are not touched until they are used, so `new_zeroed` should take next to no
+//! time.
+//!
+//! Run with `cargo bench --bench zeroed`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use dyn_struct::DynStruct;
+
+const SIZES: [(&str, usize); 3] = [("1KiB", 1 << 10), ("1MiB", 1 << 20), ("64MiB", 64 << 20)];
+
+fn zeroed(len: usize) -> Box<DynStruct<u64, u8>> {
+    DynStruct::new_zeroed(0, len)
+}
+
+fn written(len: usize) -> Box<DynStruct<u64, u8>> {
+    DynStruct::new(0, std::iter::repeat_n(0, len))
+}
+
+/// Measures allocating the value and dropping it again.
+fn allocate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("allocate");
+    for (name, len) in SIZES {
+        assert_eq!(*zeroed(len), *written(len));
+
+        group.throughput(Throughput::Bytes(len as u64));
+        group.bench_with_input(BenchmarkId::new("new_zeroed", name), &len, |b, &len| {
+            b.iter(|| zeroed(len))
+        });
+        group.bench_with_input(BenchmarkId::new("written", name), &len, |b, &len| {
+            b.iter(|| written(len))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, allocate);
+criterion_main!(benches);
@@ -1,7 +1,8 @@
-use proc_macro2::{TokenStream, TokenTree};
+use proc_macro2::TokenStream;
 use quote::quote;
+use syn::ext::IdentExt;
 
-#[proc_macro_derive(DynStruct)]
+#[proc_macro_derive(DynStruct, attributes(dyn_struct, serde))]
 pub fn derive_dyn_struct(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = syn::parse_macro_input!(input as syn::DeriveInput);
 
@@ -22,116 +23,2619 @@ macro_rules! err {
 fn expand(input: syn::DeriveInput) -> syn::Result<TokenStream> {
     match &input.data {
         syn::Data::Struct(struc) => {
-            check_repr(&input)?;
+            let align = check_repr(&input)?;
+            let options = parse_options(&input)?;
+            let constructor_name = &options.constructor;
+            let vis = &options.vis;
+            let krate = &options.krate;
+            // `std` is never named, so that the generated code also works in `no_std` crates.
+            let core = quote! { #krate::__private::core };
+            let alloc = quote! { #krate::__private::alloc };
+            // The header and the sized twin must be at least as aligned as the struct itself.
+            let repr = match &align {
+                Some(align) => quote! { #[repr(C, #align)] },
+                None => quote! { #[repr(C)] },
+            };
 
             let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
 
-            let (sized_fields, dynamic_field) = collect_fields(struc)?;
+            // `#[doc(hidden)]` and `#[doc(cfg(...))]` on the struct apply to generated items too.
+            let forwarded: Vec<&syn::Attribute> = input
+                .attrs
+                .iter()
+                .filter(|attr| match attr.parse_meta() {
+                    Ok(syn::Meta::List(list)) if list.path.is_ident("doc") => {
+                        list.nested.iter().any(|nested| match nested {
+                            syn::NestedMeta::Meta(meta) => {
+                                meta.path().is_ident("hidden") || meta.path().is_ident("cfg")
+                            }
+                            _ => false,
+                        })
+                    }
+                    _ => false,
+                })
+                .collect();
+            let forwarded = quote! { #(#forwarded)* };
+
+            let (sized_fields, dynamic_field) = collect_fields(struc)?;
+
+            let single = syn::Ident::new(
+                &format!("{}_DynStruct_Single", input.ident.unraw()),
+                input.ident.span(),
+            );
+
+            let phantom_field;
+            let phantom_init;
+            if input.generics.lt_token.is_some() {
+                let variables = input.generics.params.iter().map(|param| match param {
+                    syn::GenericParam::Type(ty) => {
+                        // behind a pointer, since the parameter may be `?Sized`
+                        let ident = &ty.ident;
+                        quote! { *const #ident }
+                    }
+                    syn::GenericParam::Lifetime(life) => {
+                        let lifetime = &life.lifetime;
+                        quote! { &#lifetime () }
+                    }
+                    syn::GenericParam::Const(constant) => {
+                        let ident = &constant.ident;
+                        quote! { [(); #ident] }
+                    }
+                });
+
+                phantom_field = quote! {
+                    __DynStruct_phantom: #core::marker::PhantomData<(#(#variables,)*)>,
+                };
+                phantom_init = quote! { __DynStruct_phantom: #core::marker::PhantomData, };
+            } else {
+                phantom_field = quote! {};
+                phantom_init = quote! {};
+            };
+
+            // The header always has named fields, even for tuple structs: only the order of the
+            // fields affects the layout of a `#[repr(C)]` struct.
+            let single_idents: Vec<syn::Ident> = sized_fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| match &field.ident {
+                    Some(ident) => ident.clone(),
+                    None => syn::Ident::new(&format!("field{}", i), span(field)),
+                })
+                .collect();
+            let sized_types: Vec<&syn::Type> = sized_fields.iter().map(|field| &field.ty).collect();
+
+            let single_definition = quote! {
+                #repr
+                struct #single #impl_generics #where_clause {
+                    #(#single_idents: #sized_types,)*
+                    #phantom_field
+                    // marks the offset just past the last field
+                    __DynStruct_end: (),
+                }
+            };
+            let single_init = quote! {
+                #single { #(#single_idents,)* #phantom_init __DynStruct_end: () }
+            };
+
+            let mut field_options = struc
+                .fields
+                .iter()
+                .map(parse_field_options)
+                .collect::<syn::Result<Vec<_>>>()?;
+            let tail_options = field_options.pop().expect("the struct has a dynamic field");
+            let tail_option = tail_options
+                .into
+                .as_ref()
+                .or(tail_options.copy.as_ref())
+                .or(tail_options.skip.as_ref());
+            if let Some(option) = tail_option {
+                return Err(err!(
+                    option,
+                    "`{}` can only be used on fields before the dynamically sized one",
+                    quote! { #option },
+                ));
+            }
+
+            // Fields marked with `#[dyn_struct(skip)]` are not passed to the constructors, which
+            // fill them in with their default value instead.
+            let mut param_idents = Vec::new();
+            let mut param_types = Vec::new();
+            let mut skipped_idents = Vec::new();
+            let mut skipped_types = Vec::new();
+            for (options, (name, ty)) in field_options
+                .iter()
+                .zip(single_idents.iter().zip(&sized_types))
+            {
+                if options.skip.is_some() {
+                    if let Some(into) = &options.into {
+                        return Err(err!(into, "`into` cannot be used together with `skip`"));
+                    }
+                    skipped_idents.push(name);
+                    skipped_types.push(*ty);
+                } else {
+                    param_idents.push(name);
+                    param_types.push(*ty);
+                }
+            }
+            let defaults = quote! {
+                #(let #skipped_idents: #skipped_types = #core::default::Default::default();)*
+            };
+
+            let sized_parameters = param_idents
+                .iter()
+                .zip(&param_types)
+                .map(|(name, ty)| quote! { #name: #ty })
+                .collect::<Vec<_>>();
+
+            let tail = tail_kind(&dynamic_field.ty, &input.generics)?;
+            let dynamic_name = dynamic_field
+                .ident
+                .clone()
+                .unwrap_or_else(|| syn::Ident::new("tail", span(&dynamic_field.ty)));
+
+            // How to access each field of the struct.
+            let member = |index: usize, field: &syn::Field| match &field.ident {
+                Some(ident) => quote! { #ident },
+                None => {
+                    let index = syn::Index::from(index);
+                    quote! { #index }
+                }
+            };
+            let sized_members: Vec<TokenStream> = sized_fields
+                .iter()
+                .enumerate()
+                .map(|(i, field)| member(i, field))
+                .collect();
+            let tail_field = member(sized_fields.len(), &dynamic_field);
+
+            // Each kind of tail gets its own signature. The body evaluates to a
+            // `Result<Box<Self>, TryNewError>`, which is shared by `new` and `try_new`.
+            let slice_element = match tail {
+                Tail::Slice(element) => Some(element),
+                _ => None,
+            };
+
+            // Parameters of fields marked with `#[dyn_struct(into)]` accept anything that converts
+            // into the type of the field, each through its own generic parameter.
+            let mut generics = Vec::new();
+            let mut bounds = Vec::new();
+            let mut conversions = Vec::new();
+            let mut parameters = Vec::new();
+            for (i, (options, (name, ty))) in field_options
+                .iter()
+                .zip(single_idents.iter().zip(&sized_types))
+                .enumerate()
+            {
+                if options.skip.is_some() {
+                    continue;
+                } else if options.into.is_some() {
+                    let param = syn::Ident::new(&format!("__DynStructInto{}", i), span(ty));
+                    parameters.push(quote! { #name: #param });
+                    bounds.push(quote! { #param: #core::convert::Into<#ty> });
+                    conversions.push(quote! { let #name = #core::convert::Into::into(#name); });
+                    generics.push(param);
+                } else {
+                    parameters.push(quote! { #name: #ty });
+                }
+            }
+
+            // The arena of the constructors allocating in a `bumpalo::Bump`, renamed if a field is
+            // called `bump`, and the construction of the value in it where that is supported.
+            let bump_name = if single_idents.iter().any(|ident| ident.unraw() == "bump") {
+                syn::Ident::new("arena", dynamic_name.span())
+            } else {
+                syn::Ident::new("bump", dynamic_name.span())
+            };
+            let mut bump_body = None;
+
+            let (tail_generics, tail_parameter, tail_bounds, body) = match tail {
+                Tail::Slice(dynamic_type) => {
+                    bump_body = Some(quote! {
+                        #single_definition
+
+                        let header: #single #type_generics = #single_init;
+                        let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+
+                        unsafe { #krate::__private::new_in_bump(header, header_end, #dynamic_name, #bump_name) }
+                            .map(|ptr| unsafe { &mut *(ptr as *mut Self) })
+                    });
+                    (
+                        quote! { __DynStructIter },
+                        quote! { #dynamic_name: __DynStructIter },
+                        quote! {
+                            __DynStructIter: #core::iter::IntoIterator<Item = #dynamic_type>,
+                            <__DynStructIter as #core::iter::IntoIterator>::IntoIter: #core::iter::ExactSizeIterator,
+                        },
+                        quote! {
+                            #single_definition
+
+                            let header: #single #type_generics = #single_init;
+                            let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+
+                            unsafe { #krate::__private::new(header, header_end, #dynamic_name) }
+                                .map(|ptr| unsafe { #alloc::boxed::Box::from_raw(ptr as *mut Self) })
+                        },
+                    )
+                }
+                Tail::Str => {
+                    bump_body = Some(quote! {
+                        #single_definition
+
+                        let header: #single #type_generics = #single_init;
+                        let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+
+                        unsafe {
+                            #krate::__private::from_slice_in_bump(
+                                header,
+                                header_end,
+                                #dynamic_name.as_bytes(),
+                                #bump_name,
+                            )
+                        }
+                        .map(|ptr| unsafe { &mut *(ptr as *mut Self) })
+                    });
+                    (
+                        quote! {},
+                        quote! { #dynamic_name: &str },
+                        quote! {},
+                        quote! {
+                            #single_definition
+
+                            let header: #single #type_generics = #single_init;
+                            let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+
+                            // `str` has the same layout and metadata as `[u8]`, and the bytes of a
+                            // `&str` are valid UTF-8.
+                            unsafe {
+                                #krate::__private::from_slice(
+                                    header,
+                                    header_end,
+                                    #dynamic_name.as_bytes(),
+                                )
+                            }
+                            .map(|ptr| unsafe { #alloc::boxed::Box::from_raw(ptr as *mut Self) })
+                        },
+                    )
+                }
+                Tail::Dyn(object) => {
+                    let dynamic_type = &dynamic_field.ty;
+
+                    // The concrete value is stored in a sized twin of the struct, which shares
+                    // its layout since both are `#[repr(C)]` with the same fields.
+                    let sized = syn::Ident::new(
+                        &format!("{}_DynStruct_Sized", input.ident.unraw()),
+                        input.ident.span(),
+                    );
+                    let param = syn::Ident::new("__DynStructTail", span(dynamic_type));
+
+                    let mut sized_generics = input.generics.clone();
+                    for param in sized_generics.type_params_mut() {
+                        param.eq_token = None;
+                        param.default = None;
+                    }
+                    for param in sized_generics.const_params_mut() {
+                        param.eq_token = None;
+                        param.default = None;
+                    }
+                    sized_generics.params.push(syn::parse_quote! { #param });
+                    let (sized_impl_generics, sized_type_generics, _) =
+                        sized_generics.split_for_impl();
+
+                    let bounds = &object.bounds;
+                    let lifetime = if bounds
+                        .iter()
+                        .any(|bound| matches!(bound, syn::TypeParamBound::Lifetime(_)))
+                    {
+                        quote! {}
+                    } else {
+                        // Spanned at the field, so that borrowing a local for the tail points at
+                        // the trait object which has no lifetime.
+                        quote::quote_spanned! {span(dynamic_type)=> + 'static }
+                    };
+
+                    let sized_value = quote! {
+                        #repr
+                        struct #sized #sized_impl_generics #where_clause {
+                            #(#single_idents: #sized_types,)*
+                            #phantom_field
+                            #dynamic_name: #param,
+                        }
+
+                        let sized: #sized #sized_type_generics = #sized {
+                            #(#single_idents,)*
+                            #phantom_init
+                            #dynamic_name,
+                        };
+                    };
+                    bump_body = Some(quote! {
+                        #sized_value
+
+                        #krate::__private::new_sized_in_bump(sized, #bump_name).map(|ptr| {
+                            let ptr = ptr.cast::<#param>() as *mut (#dynamic_type) as *mut Self;
+                            unsafe { &mut *ptr }
+                        })
+                    });
+
+                    (
+                        quote! { #param },
+                        quote! { #dynamic_name: #param },
+                        quote! { #param: #bounds #lifetime, },
+                        quote! {
+                            #sized_value
+
+                            // Reinterpreting the pointer to the start of the struct as a pointer
+                            // to the tail only serves to attach the vtable of the tail to it.
+                            #krate::__private::new_box(sized).map(|ptr| {
+                                let ptr = ptr.cast::<#param>() as *mut (#dynamic_type) as *mut Self;
+                                unsafe { #alloc::boxed::Box::from_raw(ptr) }
+                            })
+                        },
+                    )
+                }
+                Tail::Other(dynamic_type) => {
+                    // Only dynamically sized types with a length as metadata are supported, which
+                    // is checked by this cast. Errors point at the type of the field.
+                    let len = quote::quote_spanned! {span(dynamic_type)=>
+                        (&*#dynamic_name as *const #dynamic_type as *const [()]).len()
+                    };
+
+                    (
+                        quote! {},
+                        quote! { #dynamic_name: #alloc::boxed::Box<#dynamic_type> },
+                        quote! {},
+                        quote! {
+                            #single_definition
+
+                            let header: #single #type_generics = #single_init;
+                            let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+
+                            let len = #len;
+
+                            unsafe { #krate::__private::new_unsized(header, header_end, #dynamic_name) }
+                                .map(|ptr| {
+                                    let ptr = #core::ptr::slice_from_raw_parts_mut(ptr.cast::<()>(), len);
+                                    unsafe { #alloc::boxed::Box::from_raw(ptr as *mut Self) }
+                                })
+                        },
+                    )
+                }
+            };
+
+            // The generic parameters of the constructors taking an allocator, which comes last.
+            let mut in_generics: Vec<TokenStream> =
+                generics.iter().map(|param| quote! { #param }).collect();
+            if !tail_generics.is_empty() {
+                in_generics.push(tail_generics.clone());
+            }
+            let generics = if generics.is_empty() && tail_generics.is_empty() {
+                quote! {}
+            } else {
+                quote! { <#(#generics,)* #tail_generics> }
+            };
+            let parameters = quote! { #(#parameters,)* #tail_parameter };
+            let bounds = quote! { where #(#bounds,)* #tail_bounds };
+            let body = quote! { #(#conversions)* #defaults #body };
+
+            // The element type of tails which are laid out like a slice.
+            let layout_element = match tail {
+                Tail::Slice(element) => Some(quote! { #element }),
+                Tail::Str => Some(quote! { u8 }),
+                _ => None,
+            };
+
+            // Check that the layout constants agree with where the compiler put the tail.
+            let check_layout = |body: TokenStream, boxed: TokenStream| {
+                if layout_element.is_none() {
+                    return body;
+                }
+                quote! {
+                    { #body }.map(|value: #boxed| {
+                        if #core::cfg!(debug_assertions) {
+                            let start = #core::ptr::addr_of!(*value).cast::<u8>();
+                            let tail = #core::ptr::addr_of!(value.#tail_field).cast::<u8>();
+                            #core::debug_assert_eq!(
+                                unsafe { tail.offset_from(start) } as usize,
+                                Self::TAIL_OFFSET,
+                            );
+                            #core::debug_assert_eq!(
+                                #core::mem::size_of_val(&*value),
+                                Self::layout_for_len(value.#tail_field.len()).size(),
+                            );
+                        }
+                        value
+                    })
+                }
+            };
+            let body = check_layout(body, quote! { #alloc::boxed::Box<Self> });
+
+            // Describe the constructors with the documentation of the fields they take.
+            let mut doc = format!(
+                "Allocates a new [`{}`], with all fields in a single allocation.",
+                input.ident
+            );
+            let field_docs: Vec<(&syn::Ident, String)> = single_idents
+                .iter()
+                .chain(Some(&dynamic_name))
+                .zip(&struc.fields)
+                .filter_map(|(name, field)| Some((name, doc_summary(&field.attrs)?)))
+                .collect();
+            if !field_docs.is_empty() {
+                doc.push_str("\n\n# Parameters\n");
+                for (name, summary) in &field_docs {
+                    doc.push_str(&format!("\n- `{}`: {}", name, summary));
+                }
+            }
+            let panics =
+                "\n\n# Panics\n\nPanics if the size of the value would exceed `isize::MAX`, \
+                 and aborts if the allocation fails.";
+            let new_doc = format!("{}{}", doc, panics);
+            let try_doc = format!(
+                "{}\n\n# Errors\n\nReturns an error if the size of the value would exceed \
+                 `isize::MAX`, or if the allocation fails.",
+                doc
+            );
+
+            let mut items = Vec::new();
+
+            let header_doc = format!(
+                "The layout of the fields before `{}`, excluding any padding after them.",
+                dynamic_name
+            );
+            items.push(quote! {
+                #[doc = #header_doc]
+                #vis const HEADER_LAYOUT: #core::alloc::Layout = {
+                    #single_definition
+
+                    let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+                    match #krate::__private::header_layout::<#single #type_generics>(header_end) {
+                        #core::result::Result::Ok(layout) => layout,
+                        #core::result::Result::Err(_) => #core::panic!("the header is too large"),
+                    }
+                };
+            });
+
+            if let Some(element) = &layout_element {
+                let offset_doc = format!(
+                    "The offset in bytes of `{}` from the start of the struct.",
+                    dynamic_name
+                );
+                let layout_doc =
+                    format!(
+                    "Returns the layout of a value with `len` {} in `{}`, as it is allocated by \
+                     the constructors.\n\n# Panics\n\nPanics if the size of the value would \
+                     exceed `isize::MAX`.",
+                    if matches!(tail, Tail::Str) { "bytes" } else { "elements" },
+                    dynamic_name,
+                );
+                items.push(quote! {
+                    #[doc = #offset_doc]
+                    #vis const TAIL_OFFSET: usize = {
+                        #single_definition
+
+                        let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+                        #krate::__private::tail_offset::<#single #type_generics, #element>(header_end)
+                    };
+
+                    #[doc = #layout_doc]
+                    #vis const fn layout_for_len(len: usize) -> #core::alloc::Layout {
+                        #single_definition
+
+                        let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+                        #krate::__private::unwrap_layout(
+                            #krate::__private::layout_for_len::<#single #type_generics, #element>(header_end, len)
+                        )
+                    }
+                });
+            }
+
+            if !options.skip_new {
+                items.push(quote! {
+                    #[doc = #new_doc]
+                    #[inline]
+                    #[must_use]
+                    #vis fn #constructor_name #generics (#parameters) -> #alloc::boxed::Box<Self> #bounds {
+                        #krate::__private::unwrap({ #body })
+                    }
+                });
+            }
+            if options.try_new {
+                let try_name = syn::Ident::new(
+                    &format!("try_{}", constructor_name.unraw()),
+                    constructor_name.span(),
+                );
+                items.push(quote! {
+                    #[doc = #try_doc]
+                    #[inline]
+                    #vis fn #try_name #generics (#parameters)
+                        -> #core::result::Result<#alloc::boxed::Box<Self>, #krate::TryNewError> #bounds
+                    {
+                        #body
+                    }
+                });
+            }
+
+            for (enabled, suffix, pointer, link) in [
+                (options.rc, "rc", quote! { #alloc::rc::Rc }, "`Rc`"),
+                (options.arc, "arc", quote! { #alloc::sync::Arc }, "`Arc`"),
+            ] {
+                if !enabled {
+                    continue;
+                }
+                let name = syn::Ident::new(
+                    &format!("{}_{}", constructor_name.unraw(), suffix),
+                    constructor_name.span(),
+                );
+                let doc = format!(
+                    "{}\n\nThe value is built in a `Box` first, and then moved into a new {}.{}",
+                    doc, link, panics,
+                );
+                items.push(quote! {
+                    #[doc = #doc]
+                    #[inline]
+                    #[must_use]
+                    #vis fn #name #generics (#parameters) -> #pointer<Self> #bounds {
+                        #pointer::from(#krate::__private::unwrap({ #body }))
+                    }
+                });
+            }
+
+            if let Some(allocator_api) = &options.allocator_api {
+                // Like `len` below, the allocator is renamed if a field is called `alloc`.
+                let alloc_name = if single_idents.iter().any(|ident| ident.unraw() == "alloc") {
+                    syn::Ident::new("allocator", dynamic_name.span())
+                } else {
+                    syn::Ident::new("alloc", dynamic_name.span())
+                };
+                let construct = match tail {
+                    Tail::Slice(_) => quote! {
+                        #krate::__private::new_in(header, header_end, #dynamic_name, &#alloc_name)
+                    },
+                    Tail::Str => quote! {
+                        #krate::__private::from_slice_in(
+                            header,
+                            header_end,
+                            #dynamic_name.as_bytes(),
+                            &#alloc_name,
+                        )
+                    },
+                    _ => {
+                        return Err(err!(
+                        allocator_api,
+                        "`allocator_api` can only be used if the final field is a slice or `str`"
+                    ))
+                    }
+                };
+                let allocator = syn::Ident::new("__DynStructAlloc", span(allocator_api));
+                let body = quote! {
+                    #(#conversions)*
+                    #defaults
+                    #single_definition
+
+                    let header: #single #type_generics = #single_init;
+                    let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+
+                    unsafe { #construct }.map(|ptr| unsafe {
+                        #alloc::boxed::Box::from_raw_in(ptr as *mut Self, #alloc_name)
+                    })
+                };
+                let generics = quote! { <#(#in_generics,)* #allocator> };
+                let parameters = quote! { #parameters, #alloc_name: #allocator };
+                let bounds = quote! { #bounds #allocator: #core::alloc::Allocator };
+                let result = quote! { #alloc::boxed::Box<Self, #allocator> };
+                let body = check_layout(body, result.clone());
+
+                let name = syn::Ident::new(
+                    &format!("{}_in", constructor_name.unraw()),
+                    constructor_name.span(),
+                );
+                let in_doc = format!(
+                    "{}\n\nThe value is allocated in `{}` instead of the global allocator.",
+                    doc, alloc_name,
+                );
+                let new_doc = format!("{}{}", in_doc, panics);
+                items.push(quote! {
+                    #[doc = #new_doc]
+                    #[inline]
+                    #[must_use]
+                    #vis fn #name #generics (#parameters) -> #result #bounds {
+                        #krate::__private::unwrap({ #body })
+                    }
+                });
+                if options.try_new {
+                    let try_name = syn::Ident::new(
+                        &format!("try_{}_in", constructor_name.unraw()),
+                        constructor_name.span(),
+                    );
+                    let try_doc = format!(
+                        "{}\n\n# Errors\n\nReturns an error if the size of the value would exceed \
+                         `isize::MAX`, or if the allocation fails.",
+                        in_doc
+                    );
+                    items.push(quote! {
+                        #[doc = #try_doc]
+                        #[inline]
+                        #vis fn #try_name #generics (#parameters)
+                            -> #core::result::Result<#result, #krate::TryNewError> #bounds
+                        {
+                            #body
+                        }
+                    });
+                }
+            }
+
+            if let Some(bumpalo) = &options.bumpalo {
+                let bump_body = match bump_body {
+                    Some(bump_body) => bump_body,
+                    None => {
+                        return Err(err!(
+                            bumpalo,
+                            "`bumpalo` can only be used if the final field is a slice, `str` or a \
+                             trait object"
+                        ))
+                    }
+                };
+                let lifetime = syn::Lifetime::new("'__dyn_struct_bump", span(bumpalo));
+                let result = quote! { &#lifetime mut Self };
+                let body = check_layout(
+                    quote! { #(#conversions)* #defaults #bump_body },
+                    result.clone(),
+                );
+                let generics = quote! { <#lifetime, #(#in_generics,)*> };
+                let parameters =
+                    quote! { #parameters, #bump_name: &#lifetime #krate::__private::Bump };
+
+                let name = syn::Ident::new(
+                    &format!("{}_in_bump", constructor_name.unraw()),
+                    constructor_name.span(),
+                );
+                let bump_doc = format!(
+                    "{}\n\nThe value is allocated in the arena `{}`, and is never dropped: like \
+                     `Bump::alloc`, the arena frees its memory without running destructors.",
+                    doc, bump_name,
+                );
+                let new_doc = format!("{}{}", bump_doc, panics);
+                // The reference is to new memory in the arena, like the one from `Bump::alloc`.
+                items.push(quote! {
+                    #[doc = #new_doc]
+                    #[inline]
+                    #[must_use]
+                    #[allow(clippy::mut_from_ref)]
+                    #vis fn #name #generics (#parameters) -> #result #bounds {
+                        #krate::__private::unwrap({ #body })
+                    }
+                });
+                if options.try_new {
+                    let try_name = syn::Ident::new(
+                        &format!("try_{}_in_bump", constructor_name.unraw()),
+                        constructor_name.span(),
+                    );
+                    let try_doc = format!(
+                        "{}\n\n# Errors\n\nReturns an error if the size of the value would exceed \
+                         `isize::MAX`, or if the allocation fails.",
+                        bump_doc
+                    );
+                    items.push(quote! {
+                        #[doc = #try_doc]
+                        #[inline]
+                        #[allow(clippy::mut_from_ref)]
+                        #vis fn #try_name #generics (#parameters)
+                            -> #core::result::Result<#result, #krate::TryNewError> #bounds
+                        {
+                            #body
+                        }
+                    });
+                }
+            }
+
+            // The parameter for the length of the tail, for constructors which do not take its
+            // elements.
+            let len = if single_idents.iter().any(|ident| ident.unraw() == "len") {
+                syn::Ident::new(
+                    &format!("{}_len", dynamic_name.unraw()),
+                    dynamic_name.span(),
+                )
+            } else {
+                syn::Ident::new("len", dynamic_name.span())
+            };
+
+            // Items outside of the `impl` block.
+            let mut extra = Vec::new();
+
+            let owned_tail = match tail {
+                Tail::Slice(element) => Some((
+                    quote! { #alloc::vec::Vec<#element> },
+                    quote! { #krate::__private::read_vec(&this.#tail_field) },
+                    syn::Ident::new("into_vec", input.ident.span()),
+                )),
+                Tail::Str => Some((
+                    quote! { #alloc::string::String },
+                    quote! {
+                        #alloc::string::String::from_utf8_unchecked(
+                            #krate::__private::read_vec(this.#tail_field.as_bytes())
+                        )
+                    },
+                    syn::Ident::new("into_string", input.ident.span()),
+                )),
+                _ => None,
+            };
+
+            if matches!(tail, Tail::Slice(_) | Tail::Str) && !options.no_len {
+                let unit = if matches!(tail, Tail::Str) {
+                    "bytes"
+                } else {
+                    "elements"
+                };
+                let doc = format!("Returns the number of {} in `{}`.", unit, dynamic_name);
+                let empty_doc = format!("Returns `true` if `{}` is empty.", dynamic_name);
+                items.push(quote! {
+                    #[doc = #doc]
+                    #[inline]
+                    #vis fn len(&self) -> usize {
+                        self.#tail_field.len()
+                    }
+
+                    #[doc = #empty_doc]
+                    #[inline]
+                    #vis fn is_empty(&self) -> bool {
+                        self.#tail_field.is_empty()
+                    }
+                });
+            }
+
+            if let Some(pod) = &options.pod {
+                let element = match slice_element {
+                    Some(element) => element,
+                    None => {
+                        return Err(err!(
+                            pod,
+                            "`pod` can only be used if the final field is a slice"
+                        ))
+                    }
+                };
+
+                let bounds = sized_types.iter().copied().chain(Some(element)).map(|ty| {
+                    let krate = respan(krate, span(ty));
+                    quote::quote_spanned! {span(ty)=> #ty: #krate::Pod }
+                });
+                let bounds = quote! { where #(#bounds,)* };
+
+                let message = format!(
+                    "`{}` contains padding, so it cannot be viewed as bytes",
+                    input.ident
+                );
+                let assertion = quote! {
+                    #single_definition
+
+                    let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+                    let fields = 0 #(+ #core::mem::size_of::<#sized_types>())*;
+                    assert!(
+                        #krate::__private::padding_free::<#single #type_generics, #element>(
+                            header_end,
+                            fields,
+                        ),
+                        #message,
+                    );
+                };
+
+                // Fails to compile if there is any padding. This can only be checked once the
+                // generic parameters are known, that is when the functions are used.
+                let check = if input.generics.params.is_empty() {
+                    extra.push(quote! {
+                        const _: () = { #assertion };
+                    });
+                    quote! {}
+                } else {
+                    quote! { const { #assertion } }
+                };
+
+                items.push(quote! {
+                    /// Returns the bytes of the entire value, including the tail.
+                    #vis fn as_bytes(&self) -> &[u8] #bounds {
+                        #check
+                        let len = #core::mem::size_of_val(self);
+                        unsafe { #core::slice::from_raw_parts((self as *const Self).cast::<u8>(), len) }
+                    }
+
+                    /// Returns the bytes of the entire value, including the tail, which may be
+                    /// modified freely.
+                    #vis fn as_bytes_mut(&mut self) -> &mut [u8] #bounds {
+                        #check
+                        let len = #core::mem::size_of_val(self);
+                        unsafe { #core::slice::from_raw_parts_mut((self as *mut Self).cast::<u8>(), len) }
+                    }
+                });
+            }
+
+            let empty_tail = match tail {
+                Tail::Slice(element) => Some(quote! {
+                    #krate::__private::new(header, header_end, #core::iter::empty::<#element>())
+                }),
+                Tail::Str => Some(quote! {
+                    #krate::__private::from_slice::<_, u8>(header, header_end, &[])
+                }),
+                _ => None,
+            };
+            if let Some(empty_tail) = empty_tail {
+                let doc = format!("Allocates a value with an empty `{}`.", dynamic_name);
+                items.push(quote! {
+                    #[doc = #doc]
+                    #[inline]
+                    #[must_use]
+                    #vis fn empty(#(#sized_parameters),*) -> #alloc::boxed::Box<Self> {
+                        #single_definition
+
+                        #defaults
+                        let header: #single #type_generics = #single_init;
+                        let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+
+                        let ptr = #krate::__private::unwrap(unsafe { #empty_tail });
+                        unsafe { #alloc::boxed::Box::from_raw(ptr as *mut Self) }
+                    }
+                });
+            }
+
+            if let Some(default) = &options.default {
+                if !matches!(tail, Tail::Slice(_) | Tail::Str) {
+                    return Err(err!(
+                        default,
+                        "`default` can only be used if the final field is a slice or `str`"
+                    ));
+                }
+
+                let mut generics = input.generics.clone();
+                for ty in &sized_types {
+                    let core = respan(&core, span(ty));
+                    let bound = quote::quote_spanned! {span(ty)=> #ty: #core::default::Default };
+                    generics
+                        .make_where_clause()
+                        .predicates
+                        .push(syn::parse_quote! { #bound });
+                }
+                let (_, _, default_where) = generics.split_for_impl();
+                let ident = &input.ident;
+                let arguments = param_types
+                    .iter()
+                    .map(|_| quote! { #core::default::Default::default() });
+                extra.push(quote! {
+                    #forwarded
+                    impl #impl_generics #core::default::Default for #alloc::boxed::Box<#ident #type_generics> #default_where {
+                        fn default() -> Self {
+                            #ident::empty(#(#arguments),*)
+                        }
+                    }
+                });
+            }
+
+            if let Some(clone) = &options.clone {
+                let (element, clone_tail) = match tail {
+                    Tail::Slice(element) => (
+                        Some(element),
+                        quote! {
+                            #krate::__private::new(
+                                header,
+                                header_end,
+                                self.#tail_field.iter().cloned(),
+                            )
+                        },
+                    ),
+                    Tail::Str => (
+                        None,
+                        quote! {
+                            #krate::__private::from_slice(
+                                header,
+                                header_end,
+                                self.#tail_field.as_bytes(),
+                            )
+                        },
+                    ),
+                    _ => {
+                        return Err(err!(
+                            clone,
+                            "`clone` can only be used if the final field is a slice or `str`"
+                        ))
+                    }
+                };
+
+                let bounds: Vec<TokenStream> =
+                    unique_types(sized_types.iter().copied().chain(element))
+                        .into_iter()
+                        .map(|ty| {
+                            let core = respan(&core, span(ty));
+                            quote::quote_spanned! {span(ty)=> #ty: #core::clone::Clone }
+                        })
+                        .collect();
+
+                items.push(quote! {
+                    /// Allocates a copy of the value, cloning every field.
+                    #vis fn clone_box(&self) -> #alloc::boxed::Box<Self> where #(#bounds,)* {
+                        #single_definition
+
+                        #(let #single_idents = #core::clone::Clone::clone(&self.#sized_members);)*
+                        let header: #single #type_generics = #single_init;
+                        let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+
+                        let ptr = #krate::__private::unwrap(unsafe { #clone_tail });
+                        unsafe { #alloc::boxed::Box::from_raw(ptr as *mut Self) }
+                    }
+                });
+
+                let mut generics = input.generics.clone();
+                generics
+                    .make_where_clause()
+                    .predicates
+                    .extend(bounds.iter().map(|bound| -> syn::WherePredicate {
+                        syn::parse_quote! { #bound }
+                    }));
+                let (_, _, clone_where) = generics.split_for_impl();
+                let ident = &input.ident;
+                extra.push(quote! {
+                    #forwarded
+                    impl #impl_generics #core::clone::Clone for #alloc::boxed::Box<#ident #type_generics> #clone_where {
+                        fn clone(&self) -> Self {
+                            #ident::clone_box(self)
+                        }
+                    }
+                });
+            }
+
+            // Unlike `#[derive(PartialEq, Eq, Hash)]`, which bound every generic parameter, these impls
+            // only bound the types of the fields. Every field is compared and hashed in order, and
+            // the tail last: slices hash their length and `str` a terminator, so `Hash` stays
+            // consistent with `Eq` even though the tail has no fixed length.
+            let ident = &input.ident;
+            let members: Vec<&TokenStream> =
+                sized_members.iter().chain(Some(&tail_field)).collect();
+            let mut comparisons = Vec::new();
+            if let Some(option) = options.partial_eq.as_ref().or(options.eq.as_ref()) {
+                comparisons.push((
+                    option,
+                    quote! { cmp::PartialEq },
+                    quote! {
+                        fn eq(&self, other: &Self) -> bool {
+                            true #(&& self.#members == other.#members)*
+                        }
+                    },
+                ));
+            }
+            if let Some(option) = &options.eq {
+                comparisons.push((option, quote! { cmp::Eq }, quote! {}));
+            }
+            if let Some(option) = &options.hash {
+                comparisons.push((
+                    option,
+                    quote! { hash::Hash },
+                    quote! {
+                        fn hash<__H: #core::hash::Hasher>(&self, state: &mut __H) {
+                            #(#core::hash::Hash::hash(&self.#members, state);)*
+                        }
+                    },
+                ));
+            }
+            let tail_bound: Option<&syn::Type> = match tail {
+                Tail::Slice(element) => Some(element),
+                Tail::Str => None,
+                Tail::Other(dynamic_type) => Some(dynamic_type),
+                Tail::Dyn(_) => {
+                    if let Some(option) = comparisons.first().map(|(option, _, _)| *option) {
+                        return Err(err!(
+                            option,
+                            "`{}` cannot be used if the final field is a trait object",
+                            quote! { #option }
+                        ));
+                    }
+                    None
+                }
+            };
+            for (_, trait_path, body) in comparisons {
+                let mut generics = input.generics.clone();
+                generics.make_where_clause().predicates.extend(
+                    unique_types(sized_types.iter().copied().chain(tail_bound))
+                        .into_iter()
+                        .map(|ty| -> syn::WherePredicate {
+                            let trait_path = respan(&quote! { #core::#trait_path }, span(ty));
+                            let bound = quote::quote_spanned! {span(ty)=> #ty: #trait_path };
+                            syn::parse_quote! { #bound }
+                        }),
+                );
+                let (_, _, where_clause) = generics.split_for_impl();
+                extra.push(quote! {
+                    #forwarded
+                    impl #impl_generics #core::#trait_path for #ident #type_generics #where_clause {
+                        #body
+                    }
+                });
+            }
+
+            if let Some(serde) = &options.serde {
+                let (element, owned_tail, construct) = match tail {
+                    Tail::Slice(element) => (
+                        Some(element),
+                        quote! { #alloc::vec::Vec<#element> },
+                        quote! { #krate::__private::from_vec(header, header_end, __dyn_struct_tail) },
+                    ),
+                    Tail::Str => (
+                        None,
+                        quote! { #alloc::string::String },
+                        quote! {
+                            #krate::__private::from_slice(
+                                header,
+                                header_end,
+                                __dyn_struct_tail.as_bytes(),
+                            )
+                        },
+                    ),
+                    _ => {
+                        return Err(err!(
+                            serde,
+                            "`serde` can only be used if the final field is a slice or `str`"
+                        ))
+                    }
+                };
+
+                let serde_attrs = |attrs: &[syn::Attribute]| -> Vec<syn::Attribute> {
+                    attrs
+                        .iter()
+                        .filter(|attr| attr.path.is_ident("serde"))
+                        .cloned()
+                        .collect()
+                };
+                let struct_attrs = serde_attrs(&input.attrs);
+                // Fields which are not passed to the constructors are not serialized either.
+                let field_attrs: Vec<_> = struc
+                    .fields
+                    .iter()
+                    .zip(field_options.iter().chain(Some(&tail_options)))
+                    .map(|(field, options)| {
+                        let mut attrs = serde_attrs(&field.attrs);
+                        if options.skip.is_some() {
+                            attrs.push(syn::parse_quote! { #[serde(skip)] });
+                        }
+                        attrs
+                    })
+                    .collect();
+                let members: Vec<&TokenStream> =
+                    sized_members.iter().chain(Some(&tail_field)).collect();
+                let dynamic_type = &dynamic_field.ty;
+
+                // The fields are (de)serialized through a sized struct of the same name, which
+                // serde can derive for. Tuple structs stay tuple structs.
+                let mirror = |types: &[TokenStream], generics: &syn::Generics| {
+                    let (impl_generics, _, where_clause) = generics.split_for_impl();
+                    let fields = field_attrs.iter().zip(types);
+                    let ident = &input.ident;
+                    match &struc.fields {
+                        syn::Fields::Unnamed(_) => {
+                            let fields = fields.map(|(attrs, ty)| quote! { #(#attrs)* #ty });
+                            quote! {
+                                struct #ident #impl_generics (#(#fields,)*) #where_clause;
+                            }
+                        }
+                        _ => {
+                            let fields = fields
+                                .zip(single_idents.iter().chain(Some(&dynamic_name)))
+                                .map(|((attrs, ty), name)| quote! { #(#attrs)* #name: #ty });
+                            quote! {
+                                struct #ident #impl_generics #where_clause { #(#fields,)* }
+                            }
+                        }
+                    }
+                };
+
+                let lifetime = syn::Lifetime::new("'__dyn_struct", proc_macro2::Span::call_site());
+                let mut borrowed_generics = input.generics.clone();
+                borrowed_generics.params.insert(
+                    0,
+                    syn::GenericParam::Lifetime(syn::LifetimeDef::new(lifetime.clone())),
+                );
+                let borrowed: Vec<TokenStream> = sized_types
+                    .iter()
+                    .map(|ty| quote! { &#lifetime #ty })
+                    .chain(Some(quote! { &#lifetime #dynamic_type }))
+                    .collect();
+                let borrowed = mirror(&borrowed, &borrowed_generics);
+                let arguments = input.generics.params.iter().map(|param| match param {
+                    syn::GenericParam::Type(ty) => {
+                        let ident = &ty.ident;
+                        quote! { #ident }
+                    }
+                    syn::GenericParam::Lifetime(life) => {
+                        let lifetime = &life.lifetime;
+                        quote! { #lifetime }
+                    }
+                    syn::GenericParam::Const(constant) => {
+                        let ident = &constant.ident;
+                        quote! { #ident }
+                    }
+                });
+                let borrowed_arguments = quote! { <'_, #(#arguments,)*> };
+
+                let mut owned: Vec<TokenStream> =
+                    sized_types.iter().map(|ty| quote! { #ty }).collect();
+                owned.push(owned_tail);
+                let owned = mirror(&owned, &input.generics);
+
+                let de = syn::Lifetime::new("'__dyn_struct_de", proc_macro2::Span::call_site());
+                let mut serialize_generics = input.generics.clone();
+                let mut deserialize_generics = input.generics.clone();
+                deserialize_generics.params.insert(
+                    0,
+                    syn::GenericParam::Lifetime(syn::LifetimeDef::new(de.clone())),
+                );
+                for ty in sized_types.iter().copied().chain(element) {
+                    let krate = respan(krate, span(ty));
+                    let serialize = quote::quote_spanned! {span(ty)=>
+                        #ty: #krate::__private::serde::Serialize
+                    };
+                    let deserialize = quote::quote_spanned! {span(ty)=>
+                        #ty: #krate::__private::serde::Deserialize<#de>
+                    };
+                    serialize_generics
+                        .make_where_clause()
+                        .predicates
+                        .push(syn::parse_quote! { #serialize });
+                    deserialize_generics
+                        .make_where_clause()
+                        .predicates
+                        .push(syn::parse_quote! { #deserialize });
+                }
+                let (_, _, serialize_where) = serialize_generics.split_for_impl();
+                let (deserialize_impl_generics, _, deserialize_where) =
+                    deserialize_generics.split_for_impl();
+
+                let ident = &input.ident;
+                let serde_crate =
+                    format!("{}::__private::serde", quote! { #krate }).replace(' ', "");
+                // The bounds serde would infer for the mirrors do not cover every type, such as
+                // qualified paths, so they are the same as on the impls instead.
+                let bound_tokens: TokenStream = struct_attrs
+                    .iter()
+                    .map(|attr| attr.tokens.clone())
+                    .collect();
+                let user_bound = mentions(&bound_tokens, &syn::Ident::new("bound", span(ident)));
+                let mirror_bound = |bound: TokenStream| {
+                    let predicates = sized_types.iter().copied().chain(element).map(|ty| {
+                        quote! { #ty: #krate::__private::serde::#bound }
+                    });
+                    let predicates = quote! { #(#predicates,)* }.to_string();
+                    if user_bound {
+                        quote! {}
+                    } else {
+                        quote! { #[serde(bound = #predicates)] }
+                    }
+                };
+                let derive_attrs = |derive: TokenStream, bound: TokenStream| {
+                    let bound = mirror_bound(bound);
+                    quote! {
+                        #[derive(#krate::__private::serde::#derive)]
+                        #[serde(crate = #serde_crate)]
+                        #bound
+                        #(#struct_attrs)*
+                    }
+                };
+                let serialize_attrs = derive_attrs(quote! { Serialize }, quote! { Serialize });
+                let deserialize_attrs =
+                    derive_attrs(quote! { Deserialize }, quote! { Deserialize<'de> });
+
+                extra.push(quote! {
+                    #forwarded
+                    impl #impl_generics #krate::__private::serde::Serialize for #ident #type_generics #serialize_where {
+                        fn serialize<__S>(&self, serializer: __S) -> #core::result::Result<__S::Ok, __S::Error>
+                        where
+                            __S: #krate::__private::serde::Serializer,
+                        {
+                            #serialize_attrs
+                            #borrowed
+
+                            // The generic parameters cannot always be inferred from the fields,
+                            // such as when they are associated types.
+                            let mirror: #ident #borrowed_arguments = #ident { #(#members: &self.#members,)* };
+                            #krate::__private::serde::Serialize::serialize(&mirror, serializer)
+                        }
+                    }
+
+                    #forwarded
+                    impl #deserialize_impl_generics #krate::__private::serde::Deserialize<#de>
+                        for #alloc::boxed::Box<#ident #type_generics> #deserialize_where
+                    {
+                        fn deserialize<__D>(deserializer: __D) -> #core::result::Result<Self, __D::Error>
+                        where
+                            __D: #krate::__private::serde::Deserializer<#de>,
+                        {
+                            #single_definition
+
+                            let (#(#single_idents,)* __dyn_struct_tail) = {
+                                #deserialize_attrs
+                                #owned
+
+                                #[allow(non_shorthand_field_patterns)]
+                                let #ident { #(#sized_members: #single_idents,)* #tail_field: __dyn_struct_tail }: #ident #type_generics =
+                                    #krate::__private::serde::Deserialize::deserialize(deserializer)?;
+                                (#(#single_idents,)* __dyn_struct_tail)
+                            };
+                            let header: #single #type_generics = #single_init;
+                            let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+
+                            let ptr = unsafe { #construct }
+                                .map_err(#krate::__private::serde::de::Error::custom)?;
+                            #core::result::Result::Ok(unsafe { #alloc::boxed::Box::from_raw(ptr as *mut #ident #type_generics) })
+                        }
+                    }
+                });
+            }
+
+            if let Some((owned_type, read_tail, into_tail)) = owned_tail {
+                let doc = format!(
+                    "Moves the fields out of the allocation, with `{}` as a `{}`.",
+                    dynamic_name,
+                    if into_tail == "into_vec" {
+                        "Vec"
+                    } else {
+                        "String"
+                    },
+                );
+                items.push(quote! {
+                    #[doc = #doc]
+                    #vis fn into_parts(self: #alloc::boxed::Box<Self>) -> (#(#sized_types,)* #owned_type,) {
+                        let this = #core::mem::ManuallyDrop::new(self);
+                        unsafe {
+                            // Every field is read exactly once, after which only the memory is
+                            // freed.
+                            let parts = (#(#core::ptr::read(&this.#sized_members),)* #read_tail,);
+                            #krate::__private::free(#core::mem::ManuallyDrop::into_inner(this));
+                            parts
+                        }
+                    }
+                });
+
+                let doc = format!(
+                    "Moves `{}` out of the allocation, dropping the other fields.",
+                    dynamic_name,
+                );
+                items.push(quote! {
+                    #[doc = #doc]
+                    #vis fn #into_tail(self: #alloc::boxed::Box<Self>) -> #owned_type {
+                        let (.., tail) = Self::into_parts(self);
+                        tail
+                    }
+                });
+
+                let unit = if matches!(tail, Tail::Str) {
+                    "bytes"
+                } else {
+                    "elements"
+                };
+                let into_raw_doc = format!(
+                    "Consumes the `Box`, returning a thin pointer to the start of the allocation \
+                     and the number of {} in `{}`.\n\nThe value is not dropped. Use \
+                     [`{}::from_raw_parts`] to turn the parts back into a `Box`.",
+                    unit, dynamic_name, input.ident,
+                );
+                let from_raw_doc = format!(
+                    "Reassembles a `Box` from the parts returned by [`{0}::into_raw_parts`].\n\n\
+                     # Safety\n\n`ptr` and `len` must have been returned by a call to \
+                     [`{0}::into_raw_parts`] for the same type, and the parts may only be turned \
+                     back into a `Box` once.",
+                    input.ident,
+                );
+                items.push(quote! {
+                    #[doc = #into_raw_doc]
+                    #vis fn into_raw_parts(self: #alloc::boxed::Box<Self>) -> (*mut (), usize) {
+                        let len = self.#tail_field.len();
+                        let raw = #alloc::boxed::Box::into_raw(self);
+                        (raw.cast::<()>(), len)
+                    }
+
+                    #[doc = #from_raw_doc]
+                    #vis unsafe fn from_raw_parts(ptr: *mut (), len: usize) -> #alloc::boxed::Box<Self> {
+                        #krate::__private::assert_layout_on_free(ptr, || Self::layout_for_len(len));
+                        // The metadata of the struct is the length of its tail, both for slices
+                        // and `str`.
+                        let slice = #core::ptr::slice_from_raw_parts_mut(ptr, len);
+                        unsafe { #alloc::boxed::Box::from_raw(slice as *mut Self) }
+                    }
+                });
+
+                // Lets `DynStruct::into_repr` and its variants rebuild a `Box` of this type. Both
+                // slices and `str` have an element type for the layout.
+                if let Some(element) = &layout_element {
+                    extra.push(quote! {
+                        #forwarded
+                        unsafe impl #impl_generics #krate::__private::RawParts for #ident #type_generics #where_clause {
+                            const TAIL_OFFSET: usize = {
+                                #single_definition
+
+                                let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+                                #krate::__private::tail_offset::<#single #type_generics, #element>(header_end)
+                            };
+                            const ELEMENT_LAYOUT: #core::alloc::Layout = #core::alloc::Layout::new::<#element>();
+
+                            #[inline]
+                            fn layout_for_len(len: usize) -> #core::result::Result<#core::alloc::Layout, #krate::TryNewError> {
+                                #single_definition
+
+                                let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+                                #krate::__private::layout_for_len::<#single #type_generics, #element>(header_end, len)
+                            }
+
+                            #[inline]
+                            fn into_raw(ptr: *mut Self) -> (*mut (), usize) {
+                                (ptr.cast::<()>(), (ptr as *mut [()]).len())
+                            }
+
+                            #[inline]
+                            fn from_raw(ptr: *mut (), len: usize) -> *mut Self {
+                                #core::ptr::slice_from_raw_parts_mut(ptr, len) as *mut Self
+                            }
+                        }
+                    });
+                }
+            }
+
+            if let Some((owned, derives)) = &options.owned {
+                let (owned_type, into_bytes) = match tail {
+                    Tail::Slice(element) => (quote! { #alloc::vec::Vec<#element> }, quote! {}),
+                    Tail::Str => (quote! { #alloc::string::String }, quote! { .into_bytes() }),
+                    _ => {
+                        return Err(err!(
+                            owned,
+                            "`owned` can only be used if the final field is a slice or `str`"
+                        ))
+                    }
+                };
+
+                let struct_ident = &input.ident;
+                let owned_ident =
+                    syn::Ident::new(&format!("{}Owned", input.ident.unraw()), input.ident.span());
+
+                // `serde` attributes are only understood if the mirror derives one of its traits.
+                let keep_serde = derives.iter().any(|path| {
+                    path.segments.last().is_some_and(|segment| {
+                        segment.ident == "Serialize" || segment.ident == "Deserialize"
+                    })
+                });
+                let keep = |attr: &syn::Attribute| {
+                    !attr.path.is_ident("dyn_struct")
+                        && (keep_serde || !attr.path.is_ident("serde"))
+                };
+                let struct_serde = input
+                    .attrs
+                    .iter()
+                    .filter(|attr| keep_serde && attr.path.is_ident("serde"));
+
+                let mut fields = struc.fields.clone();
+                for field in fields.iter_mut() {
+                    field.attrs.retain(keep);
+                }
+                if let Some(last) = fields.iter_mut().last() {
+                    last.ty = syn::parse_quote! { #owned_type };
+                }
+                let body = match &fields {
+                    syn::Fields::Named(_) => quote! { #fields },
+                    _ => quote! { #fields #where_clause; },
+                };
+                let where_named = match &fields {
+                    syn::Fields::Named(_) => quote! { #where_clause },
+                    _ => quote! {},
+                };
+                let generics = &input.generics;
+
+                let doc = format!(
+                    "[`{0}`] with `{1}` as a `{2}`, which can grow. It is converted from a \
+                     `Box<{0}>` with `From`, and back with [`{3}::into_dyn`].",
+                    struct_ident,
+                    dynamic_name,
+                    if matches!(tail, Tail::Str) {
+                        "String"
+                    } else {
+                        "Vec"
+                    },
+                    owned_ident,
+                );
+                let into_doc = format!(
+                    "Moves the fields into a single allocation, as a [`{}`].",
+                    struct_ident
+                );
+                extra.push(quote! {
+                    #[doc = #doc]
+                    #forwarded
+                    #[derive(#(#derives),*)]
+                    #(#struct_serde)*
+                    #vis struct #owned_ident #generics #where_named #body
+
+                    #forwarded
+                    impl #impl_generics #core::convert::From<#alloc::boxed::Box<#struct_ident #type_generics>>
+                        for #owned_ident #type_generics #where_clause
+                    {
+                        fn from(value: #alloc::boxed::Box<#struct_ident #type_generics>) -> Self {
+                            let (#(#single_idents,)* #dynamic_name,) = #struct_ident::into_parts(value);
+                            Self { #(#sized_members: #single_idents,)* #tail_field: #dynamic_name }
+                        }
+                    }
+
+                    #forwarded
+                    impl #impl_generics #owned_ident #type_generics #where_clause {
+                        #[doc = #into_doc]
+                        #[inline]
+                        #[must_use]
+                        #vis fn into_dyn(self) -> #alloc::boxed::Box<#struct_ident #type_generics> {
+                            #single_definition
+
+                            #[allow(non_shorthand_field_patterns)]
+                            let Self { #(#sized_members: #single_idents,)* #tail_field: #dynamic_name } = self;
+                            let header: #single #type_generics = #single_init;
+                            let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+
+                            let ptr = #krate::__private::unwrap(unsafe {
+                                #krate::__private::from_vec(header, header_end, #dynamic_name #into_bytes)
+                            });
+                            unsafe {
+                                #alloc::boxed::Box::from_raw(ptr as *mut #struct_ident #type_generics)
+                            }
+                        }
+                    }
+                });
+            }
+
+            if options.view.is_some() {
+                let struct_ident = &input.ident;
+                let view_ident =
+                    syn::Ident::new(&format!("{}Ref", input.ident.unraw()), input.ident.span());
+                // Like `len`, the lifetime is renamed if the struct already has one called `'view`.
+                let taken = input
+                    .generics
+                    .lifetimes()
+                    .any(|def| def.lifetime.ident == "view");
+                let lifetime = syn::Lifetime::new(
+                    if taken { "'__dyn_struct_view" } else { "'view" },
+                    input.ident.span(),
+                );
 
-            let single = syn::Ident::new(
-                &format!("{}_DynStruct_Single", input.ident),
-                input.ident.span(),
-            );
+                // The same fields as the struct, but borrowed.
+                let mut fields = struc.fields.clone();
+                for field in fields.iter_mut() {
+                    field.attrs.retain(|attr| attr.path.is_ident("doc"));
+                    let ty = &field.ty;
+                    field.ty = match ty {
+                        // `&dyn A + B` would be ambiguous.
+                        syn::Type::TraitObject(_) => syn::parse_quote! { &#lifetime (#ty) },
+                        _ => syn::parse_quote! { &#lifetime #ty },
+                    };
+                }
+                let body = match &fields {
+                    syn::Fields::Named(_) => quote! { #fields },
+                    _ => quote! { #fields #where_clause; },
+                };
+                let where_named = match &fields {
+                    syn::Fields::Named(_) => quote! { #where_clause },
+                    _ => quote! {},
+                };
+                let mut generics = input.generics.clone();
+                generics.params.insert(
+                    0,
+                    syn::GenericParam::Lifetime(syn::LifetimeDef::new(lifetime.clone())),
+                );
+                let (view_impl_generics, view_type_generics, _) = generics.split_for_impl();
 
-            let phantom_field;
-            let phantom_init;
-            if input.generics.lt_token.is_some() {
-                let variables = input.generics.params.iter().map(|param| match param {
+                // The arguments of the struct, with an elided lifetime for the view.
+                let arguments = input.generics.params.iter().map(|param| match param {
                     syn::GenericParam::Type(ty) => {
                         let ident = &ty.ident;
                         quote! { #ident }
                     }
-                    syn::GenericParam::Lifetime(life) => {
-                        let lifetime = &life.lifetime;
-                        quote! { &#lifetime () }
+                    syn::GenericParam::Lifetime(def) => {
+                        let lifetime = &def.lifetime;
+                        quote! { #lifetime }
                     }
                     syn::GenericParam::Const(constant) => {
                         let ident = &constant.ident;
-                        quote! { [(); #ident] }
+                        quote! { #ident }
                     }
                 });
+                let members: Vec<&TokenStream> =
+                    sized_members.iter().chain(Some(&tail_field)).collect();
 
-                phantom_field = quote! {
-                    __DynStruct_phantom: std::marker::PhantomData<(#(#variables,)*)>
-                };
-                phantom_init = quote! { __DynStruct_phantom: std::marker::PhantomData };
-            } else {
-                phantom_field = quote! {};
-                phantom_init = quote! {};
-            };
+                let doc = format!(
+                    "A reference to every field of a [`{0}`], created by [`{0}::as_view`].",
+                    struct_ident,
+                );
+                extra.push(quote! {
+                    #[doc = #doc]
+                    #forwarded
+                    #vis struct #view_ident #generics #where_named #body
 
-            let single_definition;
-            let single_init;
-            let single_idents: Vec<syn::Ident>;
-            if matches!(struc.fields, syn::Fields::Named(_)) {
-                single_definition = quote! {
-                    #[repr(C)]
-                    struct #single #impl_generics #where_clause {
-                        #(#sized_fields,)*
-                        #phantom_field
+                    // Only references are copied, so the fields do not need to be `Clone`.
+                    #forwarded
+                    impl #view_impl_generics #core::clone::Clone for #view_ident #view_type_generics #where_clause {
+                        fn clone(&self) -> Self {
+                            *self
+                        }
+                    }
+
+                    #forwarded
+                    impl #view_impl_generics #core::marker::Copy for #view_ident #view_type_generics #where_clause {}
+                });
+
+                let doc = format!("Borrows every field, including `{}`.", dynamic_name);
+                items.push(quote! {
+                    #[doc = #doc]
+                    #[inline]
+                    #vis fn as_view(&self) -> #view_ident<'_, #(#arguments),*> {
+                        #view_ident { #(#members: &self.#members,)* }
+                    }
+                });
+            }
+
+            if let Some(header) = &options.header {
+                let element = match slice_element {
+                    Some(element) => element,
+                    None => {
+                        return Err(err!(
+                            header,
+                            "`header` can only be used if the final field is a slice"
+                        ))
                     }
                 };
-                single_idents = sized_fields
+
+                // Generic parameters which only appear in the tail are kept in a marker field, as
+                // in the private header.
+                let header_tokens: TokenStream =
+                    sized_types.iter().map(|ty| quote! { #ty }).collect();
+                let unused: Vec<TokenStream> = input
+                    .generics
+                    .params
                     .iter()
-                    .map(|field| field.ident.clone().unwrap())
+                    .filter_map(|param| match param {
+                        syn::GenericParam::Type(ty) if !mentions(&header_tokens, &ty.ident) => {
+                            let ident = &ty.ident;
+                            Some(quote! { *const #ident })
+                        }
+                        syn::GenericParam::Lifetime(life)
+                            if !mentions(&header_tokens, &life.lifetime.ident) =>
+                        {
+                            let lifetime = &life.lifetime;
+                            Some(quote! { &#lifetime () })
+                        }
+                        syn::GenericParam::Const(constant)
+                            if !mentions(&header_tokens, &constant.ident) =>
+                        {
+                            let ident = &constant.ident;
+                            Some(quote! { [(); #ident] })
+                        }
+                        _ => None,
+                    })
                     .collect();
-                single_init = quote! { #single { #(#single_idents,)* #phantom_init } };
-            } else {
-                single_definition = quote! {
+                let marker = if unused.is_empty() {
+                    None
+                } else {
+                    Some(quote! { #core::marker::PhantomData<fn() -> (#(#unused,)*)> })
+                };
+
+                let header_fields = struc.fields.iter().take(sized_fields.len()).map(|field| {
+                    let docs = field.attrs.iter().filter(|attr| attr.path.is_ident("doc"));
+                    let field_vis = &field.vis;
+                    let ty = &field.ty;
+                    match &field.ident {
+                        Some(ident) => quote! { #(#docs)* #field_vis #ident: #ty },
+                        None => quote! { #(#docs)* #field_vis #ty },
+                    }
+                });
+                let dyn_struct_link =
+                    format!("struct@{}::DynStruct", quote! { #krate }).replace(' ', "");
+                let header_doc = format!(
+                    "The fields of [`{}`] before `{}`, which become the header of a \
+                     [`DynStruct`]({}) after a conversion with [`{}::as_dyn_struct`].",
+                    input.ident, dynamic_name, dyn_struct_link, input.ident,
+                );
+                let header_definition = match &struc.fields {
+                    syn::Fields::Named(_) => {
+                        let marker = marker.map(|marker| quote! { __DynStruct_phantom: #marker, });
+                        quote! {
+                            #vis struct #header #impl_generics #where_clause {
+                                #(#header_fields,)*
+                                #marker
+                            }
+                        }
+                    }
+                    _ => quote! {
+                        #vis struct #header #impl_generics (#(#header_fields,)* #marker) #where_clause;
+                    },
+                };
+                extra.push(quote! {
+                    #forwarded
+                    #[doc = #header_doc]
                     #[repr(C)]
-                    struct #single #impl_generics ( #(#sized_fields,)* #phantom_init ) #where_clause;
+                    #header_definition
+                });
+
+                let dyn_struct = quote! { #krate::DynStruct<#header #type_generics, #element> };
+                let message = format!(
+                    "`{}` is not laid out like `DynStruct<{}, _>`, because of padding after the \
+                     last field of the header or a larger alignment",
+                    input.ident, header,
+                );
+                let as_doc = format!(
+                    "Views the value as a [`DynStruct`]({}), with [`{}`] as its header.",
+                    dyn_struct_link, header,
+                );
+                let as_mut_doc = format!(
+                    "Views the value mutably as a [`DynStruct`]({}), with [`{}`] as its header.",
+                    dyn_struct_link, header,
+                );
+                let into_doc = format!(
+                    "Converts the value into a [`DynStruct`]({}) without reallocating it.",
+                    dyn_struct_link,
+                );
+                let from_doc = format!(
+                    "Converts a [`DynStruct`]({}) into a [`{}`] without reallocating it.",
+                    dyn_struct_link, input.ident,
+                );
+                // The fields of both types are at the same offsets, since the header is
+                // `#[repr(C)]` with the same fields. The tail is at the same offset if the header
+                // has no trailing padding which the struct would use for its tail.
+                let struct_ident = &input.ident;
+                let assertion = quote! {
+                    let size = #core::mem::size_of::<#header #type_generics>();
+                    let align = #core::mem::align_of::<#header #type_generics>();
+                    let tail_offset =
+                        #krate::__private::tail_offset::<#header #type_generics, #element>(size);
+                    assert!(
+                        tail_offset == <#struct_ident #type_generics>::TAIL_OFFSET
+                            && align == <#struct_ident #type_generics>::HEADER_LAYOUT.align(),
+                        #message,
+                    );
+                };
+                let check = if input.generics.params.is_empty() {
+                    extra.push(quote! {
+                        const _: () = { #assertion };
+                    });
+                    quote! {}
+                } else {
+                    quote! { const { #assertion } }
+                };
+
+                items.push(quote! {
+                    #[doc = #as_doc]
+                    #[inline]
+                    #vis fn as_dyn_struct(&self) -> &#dyn_struct {
+                        #check
+                        let len = self.#tail_field.len();
+                        let ptr = #core::ptr::slice_from_raw_parts(self as *const Self as *const (), len);
+                        unsafe { &*(ptr as *const #dyn_struct) }
+                    }
+
+                    #[doc = #as_mut_doc]
+                    #[inline]
+                    #vis fn as_dyn_struct_mut(&mut self) -> &mut #dyn_struct {
+                        #check
+                        let len = self.#tail_field.len();
+                        let ptr = #core::ptr::slice_from_raw_parts_mut(self as *mut Self as *mut (), len);
+                        unsafe { &mut *(ptr as *mut #dyn_struct) }
+                    }
+
+                    #[doc = #into_doc]
+                    #[inline]
+                    #vis fn into_dyn_struct(self: #alloc::boxed::Box<Self>) -> #alloc::boxed::Box<#dyn_struct> {
+                        #check
+                        let (ptr, len) = Self::into_raw_parts(self);
+                        unsafe { #krate::DynStruct::from_raw_parts(ptr, len) }
+                    }
+
+                    #[doc = #from_doc]
+                    #[inline]
+                    #vis fn from_dyn_struct(value: #alloc::boxed::Box<#dyn_struct>) -> #alloc::boxed::Box<Self> {
+                        #check
+                        let (ptr, len) = #krate::DynStruct::into_raw_parts(value);
+                        unsafe { Self::from_raw_parts(ptr, len) }
+                    }
+                });
+            }
+
+            if let Some(zeroable) = &options.zeroable {
+                let element = match slice_element {
+                    Some(element) => element,
+                    None => {
+                        return Err(err!(
+                            zeroable,
+                            "`zeroable` can only be used if the final field is a slice"
+                        ))
+                    }
+                };
+
+                let zeroed_name = syn::Ident::new(
+                    &format!("{}_zeroed", constructor_name.unraw()),
+                    constructor_name.span(),
+                );
+                let doc = format!(
+                    "Allocates a new value with `{}` elements in `{}` that are all zero.",
+                    len, dynamic_name,
+                );
+
+                // The bound is checked where the struct is defined if the element type is not
+                // generic, which gives an error if it cannot be zeroed.
+                let krate = respan(krate, span(element));
+                let bound = quote::quote_spanned! {span(element)=>
+                    #element: #krate::Zeroable
+                };
+
+                items.push(quote! {
+                    #[doc = #doc]
+                    #[inline]
+                    #[must_use]
+                    #vis fn #zeroed_name(#(#sized_parameters,)* #len: usize) -> #alloc::boxed::Box<Self>
+                        where #bound
+                    {
+                        #single_definition
+
+                        #defaults
+                        let header: #single #type_generics = #single_init;
+                        let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+
+                        let ptr = #krate::__private::unwrap(unsafe {
+                            #krate::__private::new_zeroed::<_, #element>(header, header_end, #len)
+                        });
+                        unsafe { #alloc::boxed::Box::from_raw(ptr as *mut Self) }
+                    }
+                });
+            }
+
+            if let Some(element) = slice_element {
+                let from_vec_name = syn::Ident::new(
+                    &format!("{}_from_vec", constructor_name.unraw()),
+                    constructor_name.span(),
+                );
+                let doc = format!(
+                    "Allocates a new value, moving the elements of `{}` into the allocation.",
+                    dynamic_name,
+                );
+                items.push(quote! {
+                    #[doc = #doc]
+                    #[inline]
+                    #[must_use]
+                    #vis fn #from_vec_name(#(#sized_parameters,)* #dynamic_name: #alloc::vec::Vec<#element>) -> #alloc::boxed::Box<Self> {
+                        #single_definition
+
+                        #defaults
+                        let header: #single #type_generics = #single_init;
+                        let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+
+                        let ptr = #krate::__private::unwrap(unsafe {
+                            #krate::__private::from_vec(header, header_end, #dynamic_name)
+                        });
+                        unsafe { #alloc::boxed::Box::from_raw(ptr as *mut Self) }
+                    }
+                });
+
+                let from_slice_name = syn::Ident::new(
+                    &format!("{}_from_slice", constructor_name.unraw()),
+                    constructor_name.span(),
+                );
+                let doc = format!(
+                    "Allocates a new value, cloning every element of `{}` into the allocation. \
+                     This costs one call to `Clone::clone` per element.",
+                    dynamic_name,
+                );
+                items.push(quote! {
+                    #[doc = #doc]
+                    // The bound is higher-ranked so that it is not rejected as trivially false if
+                    // the elements are not `Clone`: in that case the function cannot be called.
+                    #[inline]
+                    #[must_use]
+                    #vis fn #from_slice_name(#(#sized_parameters,)* #dynamic_name: &[#element]) -> #alloc::boxed::Box<Self>
+                        where for<'__dyn_struct> #element: #core::clone::Clone
+                    {
+                        let #dynamic_name = #dynamic_name.iter().cloned();
+                        #krate::__private::unwrap({ #body })
+                    }
+                });
+
+                let extend_doc = format!(
+                    "Appends clones of the elements of `extra` to `{}`, reallocating the value in \
+                     place if possible.",
+                    dynamic_name,
+                );
+                let extend_iter_doc = format!(
+                    "Appends the items of `extra` to `{}`, reallocating the value in place if \
+                     possible.",
+                    dynamic_name,
+                );
+                let truncate_doc = format!(
+                    "Shortens `{0}` to `len` elements, dropping the rest and shrinking the \
+                     allocation. Like `Vec::truncate`, this does nothing if `{0}` is not longer \
+                     than `len`.",
+                    dynamic_name,
+                );
+                items.push(quote! {
+                    #[doc = #extend_doc]
+                    #vis fn with_extended_tail(self: #alloc::boxed::Box<Self>, extra: &[#element]) -> #alloc::boxed::Box<Self>
+                        where for<'__dyn_struct> #element: #core::clone::Clone
+                    {
+                        #single_definition
+
+                        let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+                        let len = self.#tail_field.len();
+                        let ptr = unsafe {
+                            #krate::__private::with_extended_tail::<_, #single #type_generics, #element>(
+                                self, header_end, len, extra,
+                            )
+                        };
+                        unsafe { #alloc::boxed::Box::from_raw(ptr as *mut Self) }
+                    }
+
+                    #[doc = #extend_iter_doc]
+                    #vis fn with_extended_tail_iter<__DynStructIter>(
+                        self: #alloc::boxed::Box<Self>,
+                        extra: __DynStructIter,
+                    ) -> #alloc::boxed::Box<Self>
+                    where
+                        __DynStructIter: #core::iter::IntoIterator<Item = #element>,
+                        __DynStructIter::IntoIter: #core::iter::ExactSizeIterator,
+                    {
+                        #single_definition
+
+                        let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+                        let len = self.#tail_field.len();
+                        let ptr = unsafe {
+                            #krate::__private::with_extended_tail_iter::<_, #single #type_generics, #element, _>(
+                                self, header_end, len, extra,
+                            )
+                        };
+                        unsafe { #alloc::boxed::Box::from_raw(ptr as *mut Self) }
+                    }
+
+                    #[doc = #truncate_doc]
+                    #vis fn with_truncated_tail(self: #alloc::boxed::Box<Self>, len: usize) -> #alloc::boxed::Box<Self> {
+                        #single_definition
+
+                        let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+                        let old_len = self.#tail_field.len();
+                        let ptr = unsafe {
+                            #krate::__private::with_truncated_tail::<_, #single #type_generics, #element>(
+                                self, header_end, old_len, len,
+                            )
+                        };
+                        unsafe { #alloc::boxed::Box::from_raw(ptr as *mut Self) }
+                    }
+                });
+            }
+
+            if let Some(uninit) = &options.uninit {
+                let element = match slice_element {
+                    Some(element) => element,
+                    None => {
+                        return Err(err!(
+                            uninit,
+                            "`uninit` can only be used if the final field is a slice"
+                        ))
+                    }
+                };
+
+                let struct_ident = &input.ident;
+                let uninit_ident = syn::Ident::new(
+                    &format!("{}Uninit", input.ident.unraw()),
+                    input.ident.span(),
+                );
+                let uninit_name = syn::Ident::new(
+                    &format!("{}_uninit", constructor_name.unraw()),
+                    constructor_name.span(),
+                );
+
+                // The same fields as the struct, but the elements of the tail may be
+                // uninitialized.
+                let mut fields = struc.fields.clone();
+                for field in fields.iter_mut() {
+                    field.attrs.retain(|attr| attr.path.is_ident("doc"));
+                }
+                if let Some(last) = fields.iter_mut().last() {
+                    last.ty = syn::parse_quote! { [#core::mem::MaybeUninit<#element>] };
+                }
+                let body = match &fields {
+                    syn::Fields::Named(_) => quote! { #fields },
+                    _ => quote! { #fields #where_clause; },
+                };
+                let where_named = match &fields {
+                    syn::Fields::Named(_) => quote! { #where_clause },
+                    _ => quote! {},
+                };
+                let generics = &input.generics;
+
+                let doc = format!(
+                    "[`{0}`] with a tail that may not be initialized yet, created by \
+                     [`{0}::{1}`].",
+                    struct_ident, uninit_name,
+                );
+                extra.push(quote! {
+                    #[doc = #doc]
+                    #forwarded
+                    #repr
+                    #vis struct #uninit_ident #generics #where_named #body
+
+                    #forwarded
+                    impl #impl_generics #uninit_ident #type_generics #where_clause {
+                        /// Returns the elements of the tail, which may not be initialized.
+                        #vis fn tail_uninit_mut(&mut self) -> &mut [#core::mem::MaybeUninit<#element>] {
+                            &mut self.#tail_field
+                        }
+
+                        /// Converts the value into one with an initialized tail.
+                        ///
+                        /// # Safety
+                        ///
+                        /// Every element of the tail must have been initialized.
+                        #vis unsafe fn assume_init(self: #alloc::boxed::Box<Self>) -> #alloc::boxed::Box<#struct_ident #type_generics> {
+                            // `MaybeUninit<T>` has the same layout as `T`.
+                            let ptr = #alloc::boxed::Box::into_raw(self);
+                            #alloc::boxed::Box::from_raw(ptr as *mut #struct_ident #type_generics)
+                        }
+                    }
+                });
+
+                let doc = format!(
+                    "Allocates a new value with `{}` elements in `{}` that are not initialized.",
+                    len, dynamic_name,
+                );
+                items.push(quote! {
+                    #[doc = #doc]
+                    #[inline]
+                    #[must_use]
+                    #vis fn #uninit_name(#(#sized_parameters,)* #len: usize) -> #alloc::boxed::Box<#uninit_ident #type_generics> {
+                        #single_definition
+
+                        #defaults
+                        let header: #single #type_generics = #single_init;
+                        let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+
+                        let ptr = #krate::__private::unwrap(unsafe {
+                            #krate::__private::new_uninit::<_, #element>(header, header_end, #len)
+                        });
+                        unsafe { #alloc::boxed::Box::from_raw(ptr as *mut #uninit_ident #type_generics) }
+                    }
+                });
+            }
+
+            if let Some(builder) = &options.builder {
+                let extend_name = syn::Ident::new(
+                    &format!("extend_{}", dynamic_name.unraw()),
+                    dynamic_name.span(),
+                );
+                let (owned_tail, extend, construct) = match tail {
+                    Tail::Slice(element) => (
+                        quote! { #alloc::vec::Vec<#element> },
+                        quote! {
+                            #vis fn #extend_name<__I>(mut self, #dynamic_name: __I) -> Self
+                            where
+                                __I: #core::iter::IntoIterator<Item = #element>,
+                            {
+                                self.#dynamic_name.extend(#dynamic_name);
+                                self
+                            }
+                        },
+                        quote! { #krate::__private::from_vec(header, header_end, self.#dynamic_name) },
+                    ),
+                    Tail::Str => (
+                        quote! { #alloc::string::String },
+                        quote! {
+                            #vis fn #extend_name(mut self, #dynamic_name: &str) -> Self {
+                                self.#dynamic_name.push_str(#dynamic_name);
+                                self
+                            }
+                        },
+                        quote! {
+                            #krate::__private::from_slice(
+                                header,
+                                header_end,
+                                self.#dynamic_name.as_bytes(),
+                            )
+                        },
+                    ),
+                    _ => {
+                        return Err(err!(
+                            builder,
+                            "`builder` can only be used if the final field is a slice or `str`"
+                        ))
+                    }
                 };
-                single_idents = sized_fields
+                if !matches!(struc.fields, syn::Fields::Named(_)) {
+                    return Err(err!(
+                        builder,
+                        "`builder` can only be used with named fields"
+                    ));
+                }
+
+                let struct_ident = &input.ident;
+                let builder_ident = syn::Ident::new(
+                    &format!("{}Builder", input.ident.unraw()),
+                    input.ident.span(),
+                );
+                let field_names: Vec<String> = single_idents
                     .iter()
-                    .enumerate()
-                    .map(|(i, field)| syn::Ident::new(&format!("_{}", i), span(field)))
+                    .map(|ident| ident.unraw().to_string())
                     .collect();
-                single_init = quote! { #single ( #(#single_idents,)*, #phantom_init ) };
-            };
+                let generics = &input.generics;
 
-            let sized_parameters = sized_fields.iter().enumerate().map(|(i, field)| {
-                let name = &single_idents[i];
-                let ty = &field.ty;
-                quote! { #name: #ty }
-            });
+                let doc = format!(
+                    "A builder for [`{0}`], created by [`{0}::builder`].\n\n\
+                     The elements of `{1}` are collected separately, and moved into place once the \
+                     value is built.",
+                    struct_ident, dynamic_name,
+                );
+                let build_doc = format!(
+                    "Allocates the [`{}`], or returns an error if a field was not set.",
+                    struct_ident,
+                );
+                let tail_doc = format!("Replaces the elements of `{}`.", dynamic_name);
+                let extend_doc = format!("Appends elements to `{}`.", dynamic_name);
+                let setter_docs = param_idents
+                    .iter()
+                    .map(|name| format!("Sets `{}`.", name.unraw()));
+                extra.push(quote! {
+                    #[doc = #doc]
+                    #forwarded
+                    #vis struct #builder_ident #generics #where_clause {
+                        #(#single_idents: #core::option::Option<#sized_types>,)*
+                        #dynamic_name: #owned_tail,
+                    }
+
+                    #forwarded
+                    impl #impl_generics #core::default::Default for #builder_ident #type_generics #where_clause {
+                        fn default() -> Self {
+                            #builder_ident {
+                                #(#param_idents: #core::option::Option::None,)*
+                                #(#skipped_idents: #core::option::Option::Some(#core::default::Default::default()),)*
+                                #dynamic_name: #core::default::Default::default(),
+                            }
+                        }
+                    }
+
+                    #forwarded
+                    impl #impl_generics #builder_ident #type_generics #where_clause {
+                        #(
+                            #[doc = #setter_docs]
+                            #vis fn #param_idents(mut self, #sized_parameters) -> Self {
+                                self.#param_idents = #core::option::Option::Some(#param_idents);
+                                self
+                            }
+                        )*
+
+                        #[doc = #tail_doc]
+                        #vis fn #dynamic_name(mut self, #dynamic_name: impl #core::convert::Into<#owned_tail>) -> Self {
+                            self.#dynamic_name = #dynamic_name.into();
+                            self
+                        }
+
+                        #[doc = #extend_doc]
+                        #extend
 
-            let dynamic_type = match &dynamic_field.ty {
-                syn::Type::Slice(inner) => inner.elem.as_ref(),
-                _ => {
+                        #[doc = #build_doc]
+                        #vis fn build(self) -> #core::result::Result<#alloc::boxed::Box<#struct_ident #type_generics>, #krate::MissingField> {
+                            #single_definition
+
+                            #(
+                                let #single_idents = match self.#single_idents {
+                                    #core::option::Option::Some(value) => value,
+                                    #core::option::Option::None => {
+                                        return #core::result::Result::Err(#krate::MissingField {
+                                            field: #field_names,
+                                        })
+                                    }
+                                };
+                            )*
+                            let header: #single #type_generics = #single_init;
+                            let header_end = #core::mem::offset_of!(#single #type_generics, __DynStruct_end);
+
+                            let ptr = #krate::__private::unwrap(unsafe { #construct });
+                            #core::result::Result::Ok(unsafe { #alloc::boxed::Box::from_raw(ptr as *mut #struct_ident #type_generics) })
+                        }
+                    }
+                });
+
+                items.push(quote! {
+                    /// Returns a builder which sets the fields one at a time.
+                    #[inline]
+                    #[must_use]
+                    #vis fn builder() -> #builder_ident #type_generics {
+                        #core::default::Default::default()
+                    }
+                });
+            }
+
+            if let Some(getters) = &options.getters {
+                if !matches!(struc.fields, syn::Fields::Named(_)) {
                     return Err(err!(
-                        dynamic_field.ty,
-                        "the last field needs to be a slice `[T]`"
-                    ))
+                        getters,
+                        "`getters` can only be used with named fields"
+                    ));
                 }
-            };
-            let dynamic_name = dynamic_field
-                .ident
-                .clone()
-                .unwrap_or_else(|| syn::Ident::new("tail", span(dynamic_type)));
 
+                let options = field_options.iter().chain(Some(&tail_options));
+                let names = single_idents.iter().chain(Some(&dynamic_name));
+                let types = sized_types.iter().copied().chain(Some(&dynamic_field.ty));
+                for ((field_options, name), ty) in options.zip(names).zip(types) {
+                    if field_options.skip.is_some() {
+                        continue;
+                    }
+                    let getter_vis = field_options.vis.as_ref().unwrap_or(vis);
+                    let doc = format!("Returns `{}`.", name);
+                    // Only types which are known to be `Copy` are returned by value.
+                    // Spanned at the field, so that a duplicate definition is reported there.
+                    let getter = if field_options.copy.is_some() || is_copy(ty) {
+                        quote::quote_spanned! {span(name)=>
+                            #[doc = #doc]
+                            #[inline]
+                            #getter_vis fn #name(&self) -> #ty {
+                                self.#name
+                            }
+                        }
+                    } else {
+                        quote::quote_spanned! {span(name)=>
+                            #[doc = #doc]
+                            #[inline]
+                            #getter_vis fn #name(&self) -> &#ty {
+                                &self.#name
+                            }
+                        }
+                    };
+                    items.push(getter);
+                }
+            }
+
+            // An empty `impl` would be harmless, but there is no reason to emit one.
             let struct_ident = &input.ident;
+            let implementation = if items.is_empty() {
+                quote! {}
+            } else {
+                // The constructors take every field, however many there are.
+                quote! {
+                    #forwarded
+                    #[allow(clippy::too_many_arguments)]
+                    impl #impl_generics #struct_ident #type_generics #where_clause {
+                        #(#items)*
+                    }
+                }
+            };
+
             Ok(quote! {
-                impl #impl_generics #struct_ident #type_generics #where_clause {
-                    pub fn new<I>(#(#sized_parameters,)* #dynamic_name: I) -> Box<Self>
-                        where I: std::iter::IntoIterator<Item = #dynamic_type>,
-                              <I as std::iter::IntoIterator>::IntoIter: std::iter::ExactSizeIterator
-                    {
-                        #single_definition
+                #implementation
+                #(#extra)*
+            })
+        }
+        syn::Data::Enum(data) => Err(err!(
+            data.enum_token,
+            "cannot derive `DynStruct` for an enum: {}",
+            ONLY_STRUCTS
+        )),
+        syn::Data::Union(data) => Err(err!(
+            data.union_token,
+            "cannot derive `DynStruct` for a union: {}",
+            ONLY_STRUCTS
+        )),
+    }
+}
 
-                        let header: #single #type_generics = #single_init;
+/// Options given through `#[dyn_struct(...)]` attributes on the struct.
+struct Options {
+    /// The name of the generated constructor.
+    constructor: syn::Ident,
+    /// The visibility of the generated constructor.
+    vis: syn::Visibility,
+    /// Do not generate a constructor.
+    skip_new: bool,
+    /// Also generate a constructor returning an error if the allocation fails.
+    try_new: bool,
+    /// Also generate a constructor returning an `Rc`.
+    rc: bool,
+    /// Also generate a constructor returning an `Arc`.
+    arc: bool,
+    /// Also generate a constructor with a tail of zeroes, given the path of the option.
+    zeroable: Option<syn::Path>,
+    /// Also generate a constructor with an uninitialized tail, given the path of the option.
+    uninit: Option<syn::Path>,
+    /// Do not generate `len` and `is_empty`.
+    no_len: bool,
+    /// Also generate `as_bytes` and `as_bytes_mut`, given the path of the option.
+    pod: Option<syn::Path>,
+    /// Also generate `clone_box` and `Clone` for `Box<Self>`, given the path of the option.
+    clone: Option<syn::Path>,
+    /// Also implement `Serialize` and `Deserialize` for `Box<Self>`, given the path of the option.
+    serde: Option<syn::Path>,
+    /// Also implement `Default` for `Box<Self>`, given the path of the option.
+    default: Option<syn::Path>,
+    /// Also generate a builder, given the path of the option.
+    builder: Option<syn::Path>,
+    /// Also generate a getter for every field, given the path of the option.
+    getters: Option<syn::Path>,
+    /// Also generate a header struct with this name, and conversions to and from `DynStruct`.
+    header: Option<syn::Ident>,
+    /// Also generate constructors taking an allocator, given the path of the option.
+    allocator_api: Option<syn::Path>,
+    /// Also generate constructors allocating in a `bumpalo::Bump`, given the path of the option.
+    bumpalo: Option<syn::Path>,
+    /// Also generate a copy of the struct with an owned tail, given the path of the option and the
+    /// traits to derive for it.
+    owned: Option<(syn::Path, Vec<syn::Path>)>,
+    /// Also generate a struct borrowing every field, given the path of the option.
+    view: Option<syn::Path>,
+    /// Also implement `PartialEq`, given the path of the option.
+    partial_eq: Option<syn::Path>,
+    /// Also implement `PartialEq` and `Eq`, given the path of the option.
+    eq: Option<syn::Path>,
+    /// Also implement `Hash`, given the path of the option.
+    hash: Option<syn::Path>,
+    /// The path of the `dyn_struct` crate in the generated code.
+    krate: syn::Path,
+}
+
+/// Options given through `#[dyn_struct(...)]` attributes on a field.
+struct FieldOptions {
+    /// Accept anything that converts into the type of the field in the constructor, given the
+    /// path of the option.
+    into: Option<syn::Path>,
+    /// Return the field by value from its getter, given the path of the option.
+    copy: Option<syn::Path>,
+    /// The visibility of the getter of the field.
+    vis: Option<syn::Visibility>,
+    /// Fill in the field with its default value instead of taking it as a parameter, given the
+    /// path of the option.
+    skip: Option<syn::Path>,
+}
+
+fn parse_field_options(field: &syn::Field) -> syn::Result<FieldOptions> {
+    let mut into = None;
+    let mut copy = None;
+    let mut vis = None;
+    let mut skip = None;
 
-                        let dyn_struct = dyn_struct::DynStruct::new(header, #dynamic_name);
-                        let ptr = std::boxed::Box::into_raw(dyn_struct);
-                        unsafe { std::boxed::Box::from_raw(ptr as *mut Self) }
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("dyn_struct"))
+    {
+        let options = attr.parse_args_with(
+            syn::punctuated::Punctuated::<syn::Meta, syn::Token![,]>::parse_terminated,
+        )?;
+
+        for option in &options {
+            match option {
+                syn::Meta::Path(path) if path.is_ident("into") => into = Some(path.clone()),
+                syn::Meta::Path(path) if path.is_ident("copy") => copy = Some(path.clone()),
+                syn::Meta::Path(path) if path.is_ident("skip") => skip = Some(path.clone()),
+                syn::Meta::NameValue(pair) if pair.path.is_ident("vis") => {
+                    let visibility = match &pair.lit {
+                        syn::Lit::Str(visibility) => visibility.parse::<syn::Visibility>()?,
+                        lit => return Err(err!(lit, "expected a visibility as a string")),
+                    };
+                    if vis.is_some() {
+                        return Err(err!(option, "the visibility can only be set once"));
                     }
+                    vis = Some(visibility);
                 }
-            })
+                _ => return Err(err!(option, "unknown `dyn_struct` option for a field")),
+            }
+        }
+    }
+
+    Ok(FieldOptions {
+        into,
+        copy,
+        vis,
+        skip,
+    })
+}
+
+fn parse_options(input: &syn::DeriveInput) -> syn::Result<Options> {
+    let mut constructor = None;
+    let mut vis = None;
+    let mut skip_new = false;
+    let mut try_new = false;
+    let mut rc = false;
+    let mut arc = false;
+    let mut zeroable = None;
+    let mut uninit = None;
+    let mut no_len = false;
+    let mut pod = None;
+    let mut clone = None;
+    let mut serde = None;
+    let mut default = None;
+    let mut builder = None;
+    let mut getters = None;
+    let mut header = None;
+    let mut allocator_api = None;
+    let mut bumpalo = None;
+    let mut owned = None;
+    let mut view = None;
+    let mut partial_eq = None;
+    let mut eq = None;
+    let mut hash = None;
+    let mut krate = None;
+
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("dyn_struct"))
+    {
+        let options = attr.parse_args_with(
+            syn::punctuated::Punctuated::<syn::Meta, syn::Token![,]>::parse_terminated,
+        )?;
+
+        for option in &options {
+            match option {
+                syn::Meta::Path(path) if path.is_ident("skip_new") => skip_new = true,
+                syn::Meta::Path(path) if path.is_ident("try_new") => try_new = true,
+                syn::Meta::Path(path) if path.is_ident("rc") => rc = true,
+                syn::Meta::Path(path) if path.is_ident("arc") => arc = true,
+                syn::Meta::Path(path) if path.is_ident("zeroable") => zeroable = Some(path.clone()),
+                syn::Meta::Path(path) if path.is_ident("uninit") => uninit = Some(path.clone()),
+                syn::Meta::Path(path) if path.is_ident("no_len") => no_len = true,
+                syn::Meta::Path(path) if path.is_ident("pod") => pod = Some(path.clone()),
+                syn::Meta::Path(path) if path.is_ident("clone") => clone = Some(path.clone()),
+                syn::Meta::Path(path) if path.is_ident("serde") => serde = Some(path.clone()),
+                syn::Meta::Path(path) if path.is_ident("default") => default = Some(path.clone()),
+                syn::Meta::Path(path) if path.is_ident("builder") => builder = Some(path.clone()),
+                syn::Meta::Path(path) if path.is_ident("getters") => getters = Some(path.clone()),
+                syn::Meta::Path(path) if path.is_ident("owned") => {
+                    owned = Some((path.clone(), Vec::new()))
+                }
+                syn::Meta::List(list) if list.path.is_ident("owned") => {
+                    let derives = list
+                        .nested
+                        .iter()
+                        .map(|nested| match nested {
+                            syn::NestedMeta::Meta(syn::Meta::Path(path)) => Ok(path.clone()),
+                            _ => Err(err!(nested, "expected the path of a trait to derive")),
+                        })
+                        .collect::<syn::Result<_>>()?;
+                    owned = Some((list.path.clone(), derives));
+                }
+                syn::Meta::Path(path) if path.is_ident("view") => view = Some(path.clone()),
+                syn::Meta::Path(path) if path.is_ident("partial_eq") => {
+                    partial_eq = Some(path.clone())
+                }
+                syn::Meta::Path(path) if path.is_ident("eq") => eq = Some(path.clone()),
+                syn::Meta::Path(path) if path.is_ident("hash") => hash = Some(path.clone()),
+                syn::Meta::Path(path) if path.is_ident("allocator_api") => {
+                    allocator_api = Some(path.clone())
+                }
+                syn::Meta::Path(path) if path.is_ident("bumpalo") => bumpalo = Some(path.clone()),
+                syn::Meta::NameValue(pair) if pair.path.is_ident("constructor") => {
+                    let name = match &pair.lit {
+                        syn::Lit::Str(name) => name.parse::<syn::Ident>()?,
+                        lit => {
+                            return Err(err!(
+                                lit,
+                                "expected the name of the constructor as a string"
+                            ))
+                        }
+                    };
+                    if constructor.is_some() {
+                        return Err(err!(option, "the constructor can only be renamed once"));
+                    }
+                    constructor = Some(name);
+                }
+                syn::Meta::NameValue(pair) if pair.path.is_ident("vis") => {
+                    let visibility = match &pair.lit {
+                        syn::Lit::Str(visibility) => visibility.parse::<syn::Visibility>()?,
+                        lit => return Err(err!(lit, "expected a visibility as a string")),
+                    };
+                    if vis.is_some() {
+                        return Err(err!(option, "the visibility can only be set once"));
+                    }
+                    vis = Some(visibility);
+                }
+                syn::Meta::NameValue(pair) if pair.path.is_ident("header") => {
+                    let name = match &pair.lit {
+                        syn::Lit::Str(name) => name.parse::<syn::Ident>()?,
+                        lit => {
+                            return Err(err!(lit, "expected the name of the header as a string"))
+                        }
+                    };
+                    if header.is_some() {
+                        return Err(err!(option, "the header can only be named once"));
+                    }
+                    header = Some(name);
+                }
+                syn::Meta::NameValue(pair) if pair.path.is_ident("crate") => {
+                    let path = match &pair.lit {
+                        syn::Lit::Str(path) => path.parse::<syn::Path>()?,
+                        lit => return Err(err!(lit, "expected the path of the crate as a string")),
+                    };
+                    if krate.is_some() {
+                        return Err(err!(option, "the path of the crate can only be set once"));
+                    }
+                    krate = Some(path);
+                }
+                _ => return Err(err!(option, "unknown `dyn_struct` option")),
+            }
+        }
+    }
+
+    Ok(Options {
+        // Errors about a duplicate definition point at the struct by default.
+        constructor: constructor.unwrap_or_else(|| syn::Ident::new("new", input.ident.span())),
+        vis: vis.unwrap_or_else(|| input.vis.clone()),
+        skip_new,
+        try_new,
+        rc,
+        arc,
+        zeroable,
+        uninit,
+        no_len,
+        pod,
+        clone,
+        serde,
+        default,
+        builder,
+        getters,
+        header,
+        allocator_api,
+        bumpalo,
+        owned,
+        view,
+        partial_eq,
+        eq,
+        hash,
+        krate: krate.unwrap_or_else(|| syn::parse_quote! { dyn_struct }),
+    })
+}
+
+/// The kinds of dynamically sized types supported as the last field.
+enum Tail<'a> {
+    /// `[T]`, with the type of the elements.
+    Slice(&'a syn::Type),
+    /// `str`
+    Str,
+    /// `dyn Trait`
+    Dyn(&'a syn::TypeTraitObject),
+    /// Any other dynamically sized type, such as another struct with `#[derive(DynStruct)]`.
+    Other(&'a syn::Type),
+}
+
+const ONLY_STRUCTS: &str =
+    "`DynStruct` can only be derived for structs with a trailing dynamically sized field";
+
+const NOT_DYNAMIC: &str =
+    "the final field must be a dynamically sized type such as `[T]`, `str`, or `dyn Trait`";
+
+fn tail_kind<'a>(ty: &'a syn::Type, generics: &syn::Generics) -> syn::Result<Tail<'a>> {
+    match ty {
+        syn::Type::Slice(inner) => Ok(Tail::Slice(inner.elem.as_ref())),
+        syn::Type::Path(path) if path.qself.is_none() && path.path.is_ident("str") => Ok(Tail::Str),
+        syn::Type::TraitObject(object) => Ok(Tail::Dyn(object)),
+        syn::Type::Paren(inner) => tail_kind(&inner.elem, generics),
+        syn::Type::Group(inner) => tail_kind(&inner.elem, generics),
+        syn::Type::Path(path) if is_sized_path(path, generics) => Err(err!(ty, "{}", NOT_DYNAMIC)),
+        syn::Type::Path(_) | syn::Type::Macro(_) | syn::Type::Verbatim(_) => Ok(Tail::Other(ty)),
+        _ => Err(err!(ty, "{}", NOT_DYNAMIC)),
+    }
+}
+
+/// Returns `true` if the path is known to refer to a sized type: a primitive or a type parameter
+/// without a `?Sized` bound.
+fn is_sized_path(path: &syn::TypePath, generics: &syn::Generics) -> bool {
+    const PRIMITIVES: &[&str] = &[
+        "bool", "char", "f32", "f64", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16",
+        "u32", "u64", "u128", "usize",
+    ];
+
+    let ident = match path.path.get_ident() {
+        Some(ident) if path.qself.is_none() => ident,
+        _ => return false,
+    };
+
+    if PRIMITIVES.iter().any(|primitive| ident == primitive) {
+        return true;
+    }
+
+    let param = match generics.type_params().find(|param| param.ident == *ident) {
+        Some(param) => param,
+        None => return false,
+    };
+
+    let is_maybe = |bound: &syn::TypeParamBound| {
+        matches!(bound, syn::TypeParamBound::Trait(trait_bound)
+            if matches!(trait_bound.modifier, syn::TraitBoundModifier::Maybe(_)))
+    };
+
+    let inline = param.bounds.iter().any(is_maybe);
+    let in_where = generics.where_clause.iter().any(|clause| {
+        clause.predicates.iter().any(|predicate| match predicate {
+            syn::WherePredicate::Type(predicate) => {
+                matches!(&predicate.bounded_ty, syn::Type::Path(bounded) if bounded.path.is_ident(ident))
+                    && predicate.bounds.iter().any(is_maybe)
+            }
+            _ => false,
+        })
+    });
+
+    !(inline || in_where)
+}
+
+/// Returns `true` if the type is syntactically known to be dynamically sized.
+fn is_unsized(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Slice(_) | syn::Type::TraitObject(_) => true,
+        syn::Type::Path(path) => path.qself.is_none() && path.path.is_ident("str"),
+        syn::Type::Paren(inner) => is_unsized(&inner.elem),
+        syn::Type::Group(inner) => is_unsized(&inner.elem),
+        _ => false,
+    }
+}
+
+/// Returns the first paragraph of the doc comments, joined into a single line.
+fn doc_summary(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                path,
+                lit: syn::Lit::Str(doc),
+                ..
+            })) if path.is_ident("doc") => Some(doc.value().trim().to_owned()),
+            _ => None,
+        })
+        .skip_while(|line| line.is_empty())
+        .take_while(|line| !line.is_empty())
+        .collect();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join(" "))
+    }
+}
+
+/// Returns `true` if the type is certainly `Copy`: primitives, shared references, pointers, and
+/// arrays and tuples of those.
+fn is_copy(ty: &syn::Type) -> bool {
+    const PRIMITIVES: &[&str] = &[
+        "bool", "char", "f32", "f64", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16",
+        "u32", "u64", "u128", "usize",
+    ];
+    match ty {
+        syn::Type::Path(path) => {
+            path.qself.is_none()
+                && PRIMITIVES
+                    .iter()
+                    .any(|primitive| path.path.is_ident(primitive))
+        }
+        syn::Type::Reference(reference) => reference.mutability.is_none(),
+        syn::Type::Ptr(_) | syn::Type::BareFn(_) | syn::Type::Never(_) => true,
+        syn::Type::Array(array) => is_copy(&array.elem),
+        syn::Type::Tuple(tuple) => tuple.elems.iter().all(is_copy),
+        syn::Type::Paren(inner) => is_copy(&inner.elem),
+        syn::Type::Group(inner) => is_copy(&inner.elem),
+        _ => false,
+    }
+}
+
+/// Removes repeated types, keeping the first occurrence, so that a bound is not repeated for every
+/// field of the same type.
+fn unique_types<'a>(types: impl IntoIterator<Item = &'a syn::Type>) -> Vec<&'a syn::Type> {
+    let mut unique: Vec<&syn::Type> = Vec::new();
+    for ty in types {
+        if !unique.contains(&ty) {
+            unique.push(ty);
         }
-        _ => Err(err!(
-            &input.ident,
-            "`DynStruct` can only be derived for structs"
-        )),
     }
+    unique
+}
+
+/// Returns `true` if the identifier occurs anywhere in the tokens.
+fn mentions(tokens: &TokenStream, ident: &syn::Ident) -> bool {
+    tokens.clone().into_iter().any(|token| match token {
+        proc_macro2::TokenTree::Ident(other) => other == *ident,
+        proc_macro2::TokenTree::Group(group) => mentions(&group.stream(), ident),
+        _ => false,
+    })
+}
+
+/// Moves all tokens of a path to the given span, such that errors about a bound on a trait in the
+/// path point at the same place as the rest of the bound.
+fn respan(path: &impl quote::ToTokens, span: proc_macro2::Span) -> TokenStream {
+    quote! { #path }
+        .into_iter()
+        .map(|mut token| {
+            token.set_span(span);
+            token
+        })
+        .collect()
 }
 
 fn span<T: syn::spanned::Spanned>(value: &T) -> proc_macro2::Span {
@@ -141,6 +2645,9 @@ fn span<T: syn::spanned::Spanned>(value: &T) -> proc_macro2::Span {
 fn collect_fields(struc: &syn::DataStruct) -> syn::Result<(Vec<syn::Field>, syn::Field)> {
     let mut fields = struc.fields.clone();
 
+    // The attributes of other macros have no meaning in the generated code. The compiler has
+    // already evaluated `#[cfg(...)]` and `#[cfg_attr(...)]` on the fields, removing any that are
+    // disabled, so there is nothing left for the derive to reproduce.
     for field in fields.iter_mut() {
         field.attrs.clear();
         field.vis = syn::Visibility::Inherited;
@@ -152,15 +2659,99 @@ fn collect_fields(struc: &syn::DataStruct) -> syn::Result<(Vec<syn::Field>, syn:
         syn::Fields::Unit => None,
     };
 
-    let dynamic =
-        dynamic.ok_or_else(|| err!(&struc.fields, "cannot derive `DynStruct` for empty struct"))?;
+    let dynamic = match dynamic {
+        Some(dynamic) => dynamic.into_value(),
+        None if matches!(struc.fields, syn::Fields::Unit) => {
+            return Err(err!(
+                struc.struct_token,
+                "cannot derive `DynStruct` for a unit struct: {}",
+                NOT_DYNAMIC
+            ))
+        }
+        None => {
+            return Err(err!(
+                &struc.fields,
+                "cannot derive `DynStruct` for an empty struct: {}",
+                NOT_DYNAMIC
+            ))
+        }
+    };
+
+    if let Some(field) = fields.iter().find(|field| is_unsized(&field.ty)) {
+        return Err(err!(
+            &field.ty,
+            "only the final field of the struct can be dynamically sized"
+        ));
+    }
 
-    Ok((fields.into_iter().collect(), dynamic.into_value()))
+    Ok((fields.into_iter().collect(), dynamic))
 }
 
-fn check_repr(input: &syn::DeriveInput) -> syn::Result<()> {
-    if input.attrs.iter().any(|attr| is_repr_c(attr)) {
-        Ok(())
+/// Checks that the struct is `#[repr(C)]`, returning its `align(N)` hint, if any.
+fn check_repr(input: &syn::DeriveInput) -> syn::Result<Option<syn::Meta>> {
+    const INTEGERS: &[&str] = &[
+        "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+    ];
+
+    let mut repr_c = false;
+    let mut align: Option<syn::Meta> = None;
+
+    for attr in input.attrs.iter().filter(|attr| attr.path.is_ident("repr")) {
+        let hints = attr.parse_args_with(
+            syn::punctuated::Punctuated::<syn::Meta, syn::Token![,]>::parse_terminated,
+        )?;
+
+        for hint in &hints {
+            let path = hint.path();
+            if path.is_ident("C") {
+                repr_c = true;
+            } else if path.is_ident("Rust") {
+                return Err(err!(
+                    hint,
+                    "`DynStruct` cannot be derived for structs with `#[repr(Rust)]`, since the \
+                     order of their fields is unspecified: use `#[repr(C)]` instead"
+                ));
+            } else if path.is_ident("transparent") {
+                return Err(err!(
+                    hint,
+                    "`DynStruct` cannot be derived for structs with `#[repr(transparent)]`: use \
+                     `#[repr(C)]` instead"
+                ));
+            } else if path.is_ident("packed") {
+                return Err(err!(
+                    hint,
+                    "`DynStruct` cannot be derived for structs with `#[repr(packed)]`, since \
+                     packing moves fields to offsets the layout of the tail does not account for"
+                ));
+            } else if path.is_ident("simd") {
+                return Err(err!(
+                    hint,
+                    "`DynStruct` cannot be derived for structs with `#[repr(simd)]`, since SIMD \
+                     vectors cannot have a dynamically sized field"
+                ));
+            } else if INTEGERS.iter().any(|int| path.is_ident(int)) {
+                return Err(err!(
+                    hint,
+                    "`DynStruct` cannot be derived for structs with an integer representation, \
+                     which only applies to enums"
+                ));
+            } else if path.is_ident("align") {
+                if let Some(previous) = &align {
+                    if previous != hint {
+                        return Err(err!(
+                            hint,
+                            "conflicting `align` representation hints in a struct deriving \
+                             `DynStruct`"
+                        ));
+                    }
+                }
+                align = Some(hint.clone());
+            }
+        }
+    }
+
+    if repr_c {
+        Ok(align)
     } else {
         Err(err!(
             &input.ident,
@@ -168,24 +2759,3 @@ fn check_repr(input: &syn::DeriveInput) -> syn::Result<()> {
         ))
     }
 }
-
-fn is_repr_c(attr: &syn::Attribute) -> bool {
-    match attr.path.get_ident() {
-        Some(ident) if ident == "repr" => {}
-        _ => return false,
-    }
-
-    match find_ident(attr.tokens.clone()) {
-        Some(ident) if ident == "C" => true,
-        _ => false,
-    }
-}
-
-fn find_ident(tokens: TokenStream) -> Option<syn::Ident> {
-    tokens.into_iter().find_map(|tree| match tree {
-        TokenTree::Group(group) => find_ident(group.stream()),
-        TokenTree::Ident(ident) => Some(ident.clone()),
-        _ => None,
-    })
-}
-
@@ -0,0 +1,153 @@
+//! Builds derived structs from arbitrary fields, converts them to and from `DynStruct` with their
+//! generated header, and checks the fields, the tail and the layout after each step.
+
+#![no_main]
+
+use std::alloc::Layout;
+
+use arbitrary::Arbitrary;
+use dyn_struct::DynStruct;
+use libfuzzer_sys::fuzz_target;
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(header = "RecordHeader")]
+struct Record {
+    pub id: u32,
+    pub kind: u16,
+    pub flags: u16,
+    pub bytes: [u8],
+}
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(header = "SamplesHeader")]
+struct Samples {
+    pub rate: u32,
+    pub channels: u32,
+    pub samples: [u64],
+}
+
+#[derive(Arbitrary, Debug)]
+enum Input {
+    Record {
+        id: u32,
+        kind: u16,
+        flags: u16,
+        bytes: Vec<u8>,
+        extra: Vec<u8>,
+        truncate: usize,
+    },
+    Samples {
+        rate: u32,
+        channels: u32,
+        samples: Vec<u64>,
+        extra: Vec<u64>,
+        truncate: usize,
+    },
+}
+
+fn record(id: u32, kind: u16, flags: u16, bytes: Vec<u8>, extra: Vec<u8>, truncate: usize) {
+    let check = |value: &Record, bytes: &[u8]| {
+        assert_eq!((value.id, value.kind, value.flags), (id, kind, flags));
+        assert_eq!(&value.bytes, bytes);
+        assert_eq!(value.len(), bytes.len());
+        assert_eq!(
+            Layout::for_value(value),
+            Record::layout_for_len(bytes.len())
+        );
+        let base = value as *const Record as *const u8;
+        assert_eq!(
+            value.bytes.as_ptr() as usize - base as usize,
+            Record::TAIL_OFFSET
+        );
+    };
+
+    let value = Record::new(id, kind, flags, bytes.clone());
+    check(&value, &bytes);
+
+    let converted = value.into_dyn_struct();
+    assert_eq!(
+        (
+            converted.header.id,
+            converted.header.kind,
+            converted.header.flags
+        ),
+        (id, kind, flags),
+    );
+    assert_eq!(&converted.tail, &bytes[..]);
+    let value = Record::from_dyn_struct(converted);
+    check(&value, &bytes);
+
+    let (ptr, len) = Record::into_raw_parts(value);
+    let value = unsafe { Record::from_raw_parts(ptr, len) };
+    check(&value, &bytes);
+
+    let mut expected = bytes;
+    let value = value.with_extended_tail(&extra);
+    expected.extend_from_slice(&extra);
+    check(&value, &expected);
+
+    let len = truncate % (expected.len() + 1);
+    let value = value.with_truncated_tail(len);
+    expected.truncate(len);
+    check(&value, &expected);
+
+    let (header, tail) = DynStruct::into_parts(value.into_dyn_struct());
+    assert_eq!((header.id, header.kind, header.flags), (id, kind, flags));
+    assert_eq!(tail, expected);
+}
+
+fn samples(rate: u32, channels: u32, samples: Vec<u64>, extra: Vec<u64>, truncate: usize) {
+    let check = |value: &Samples, samples: &[u64]| {
+        assert_eq!((value.rate, value.channels), (rate, channels));
+        assert_eq!(&value.samples, samples);
+        assert_eq!(
+            Layout::for_value(value),
+            Samples::layout_for_len(samples.len())
+        );
+        let base = value as *const Samples as *const u8;
+        assert_eq!(
+            value.samples.as_ptr() as usize - base as usize,
+            Samples::TAIL_OFFSET
+        );
+    };
+
+    // Build the value from a `DynStruct` first, the other way around from `record`.
+    let header = SamplesHeader { rate, channels };
+    let value = Samples::from_dyn_struct(DynStruct::from_slice(header, &samples));
+    check(&value, &samples);
+
+    let mut expected = samples;
+    let value = value.with_extended_tail(&extra);
+    expected.extend_from_slice(&extra);
+    check(&value, &expected);
+
+    let len = truncate % (expected.len() + 1);
+    let converted = value.with_truncated_tail(len).into_dyn_struct();
+    expected.truncate(len);
+    assert_eq!(
+        (converted.header.rate, converted.header.channels),
+        (rate, channels)
+    );
+    assert_eq!(&converted.tail, &expected[..]);
+    check(&Samples::from_dyn_struct(converted), &expected);
+}
+
+fuzz_target!(|input: Input| match input {
+    Input::Record {
+        id,
+        kind,
+        flags,
+        bytes,
+        extra,
+        truncate,
+    } => record(id, kind, flags, bytes, extra, truncate),
+    Input::Samples {
+        rate,
+        channels,
+        samples: values,
+        extra,
+        truncate,
+    } => samples(rate, channels, values, extra, truncate),
+});
@@ -0,0 +1,102 @@
+//! Builds `DynStruct`s with arbitrary headers and tails through every constructor, and passes them
+//! through the functions which move or reallocate them, checking the value after each step.
+
+#![no_main]
+
+use std::alloc::Layout;
+use std::fmt::Debug;
+
+use arbitrary::Arbitrary;
+use dyn_struct::DynStruct;
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+enum Input {
+    Bytes(Case<u8, u8>),
+    Words(Case<[u8; 3], u64>),
+    Padded(Case<u64, (u16, u8)>),
+    Empty(Case<(), u16>),
+    Strings(Case<(u16, u8), String>),
+}
+
+#[derive(Arbitrary, Debug)]
+struct Case<Header, Tail> {
+    header: Header,
+    tail: Vec<Tail>,
+    extra: Vec<Tail>,
+    truncate: usize,
+}
+
+/// Checks that `value` holds `header` and `tail`, and that `Box` will free it with the layout it
+/// was allocated with.
+fn check<Header, Tail>(value: &DynStruct<Header, Tail>, header: &Header, tail: &[Tail])
+where
+    Header: PartialEq + Debug,
+    Tail: PartialEq + Debug,
+{
+    assert_eq!(&value.header, header);
+    assert_eq!(&value.tail, tail);
+    assert_eq!(value.len(), tail.len());
+
+    let (layout, offset) = Layout::new::<Header>()
+        .extend(Layout::array::<Tail>(tail.len()).unwrap())
+        .unwrap();
+    assert_eq!(Layout::for_value(value), layout.pad_to_align());
+    let base = value as *const DynStruct<Header, Tail> as *const u8;
+    assert_eq!(value.tail.as_ptr() as usize - base as usize, offset);
+}
+
+/// `from_slice` is only passed for `Copy` tails, since it does not clone them.
+type FromSlice<Header, Tail> = fn(Header, &[Tail]) -> Box<DynStruct<Header, Tail>>;
+
+fn run<Header, Tail>(case: Case<Header, Tail>, from_slice: Option<FromSlice<Header, Tail>>)
+where
+    Header: Clone + PartialEq + Debug,
+    Tail: Clone + PartialEq + Debug,
+{
+    let Case {
+        header,
+        tail,
+        extra,
+        truncate,
+    } = case;
+
+    let value = DynStruct::new(header.clone(), tail.iter().cloned());
+    check(&value, &header, &tail);
+    if let Some(from_slice) = from_slice {
+        let value = from_slice(header.clone(), &tail);
+        check(&value, &header, &tail);
+    }
+    let value = DynStruct::from_vec(header.clone(), tail.clone());
+    check(&value, &header, &tail);
+
+    let (ptr, len) = DynStruct::into_raw_parts(value);
+    assert_eq!(len, tail.len());
+    let value = unsafe { DynStruct::<Header, Tail>::from_raw_parts(ptr, len) };
+    check(&value, &header, &tail);
+
+    let mut expected = tail;
+    let value = DynStruct::with_extended_tail(value, &extra);
+    expected.extend_from_slice(&extra);
+    check(&value, &header, &expected);
+    let value = DynStruct::with_extended_tail_iter(value, extra.iter().rev().cloned());
+    expected.extend(extra.iter().rev().cloned());
+    check(&value, &header, &expected);
+
+    let len = truncate % (expected.len() + 1);
+    let value = DynStruct::with_truncated_tail(value, len);
+    expected.truncate(len);
+    check(&value, &header, &expected);
+
+    let (parts_header, parts_tail) = DynStruct::into_parts(value);
+    assert_eq!(parts_header, header);
+    assert_eq!(parts_tail, expected);
+}
+
+fuzz_target!(|input: Input| match input {
+    Input::Bytes(case) => run(case, Some(DynStruct::from_slice)),
+    Input::Words(case) => run(case, Some(DynStruct::from_slice)),
+    Input::Padded(case) => run(case, Some(DynStruct::from_slice)),
+    Input::Empty(case) => run(case, Some(DynStruct::from_slice)),
+    Input::Strings(case) => run(case, None),
+});
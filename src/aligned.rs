@@ -0,0 +1,234 @@
+//! [`AlignedDynStruct`]: a `DynStruct` whose tail is aligned beyond what its types require.
+
+use alloc::boxed::Box;
+use core::alloc::Layout;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+use crate::{tracking, BoxWriter, DynStruct, RawAlloc, TryNewError};
+
+/// An owned `DynStruct` whose tail starts at a multiple of a chosen alignment, created by
+/// [`DynStruct::new_aligned`].
+///
+/// A `Box` frees its value with the layout of its type, which knows nothing about the extra
+/// alignment, so this owner keeps the alignment next to the pointer and frees the value itself.
+/// To move the tail onto the requested boundary, the allocation may start with padding before the
+/// header.
+pub struct AlignedDynStruct<Header, Tail> {
+    ptr: NonNull<DynStruct<Header, Tail>>,
+    /// The alignment of the allocation and of the tail, at least the one of the value.
+    align: usize,
+    _marker: PhantomData<Box<DynStruct<Header, Tail>>>,
+}
+
+// SAFETY: the value is owned, just like in a `Box`.
+unsafe impl<Header: Send, Tail: Send> Send for AlignedDynStruct<Header, Tail> {}
+unsafe impl<Header: Sync, Tail: Sync> Sync for AlignedDynStruct<Header, Tail> {}
+
+impl<Header, Tail> AlignedDynStruct<Header, Tail> {
+    /// See [`DynStruct::try_new_aligned`].
+    pub(crate) fn try_new<I>(header: Header, tail: I, min_align: usize) -> Result<Self, TryNewError>
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        assert!(
+            min_align.is_power_of_two(),
+            "the alignment of a `DynStruct` must be a power of two, not {}",
+            min_align
+        );
+        let align = min_align.max(DynStruct::<Header, Tail>::ALIGN);
+        let padded = Padded {
+            align,
+            offset: Self::offset(align),
+        };
+        unsafe {
+            let ptr = BoxWriter::<Header, Tail, Padded>::from_iter(
+                header,
+                Layout::new::<Header>(),
+                tail,
+                padded,
+            )?;
+            // The value is freed by `Drop` below, not through a `Box`.
+            tracking::forget(ptr.cast::<u8>());
+            Ok(AlignedDynStruct {
+                ptr: NonNull::new_unchecked(ptr as *mut DynStruct<Header, Tail>),
+                align,
+                _marker: PhantomData,
+            })
+        }
+    }
+
+    /// Returns the alignment of the tail, which is the requested one unless the types already
+    /// require more.
+    #[inline]
+    pub fn align(this: &Self) -> usize {
+        this.align
+    }
+
+    /// Returns the offset of the value in its allocation, such that the tail is aligned to
+    /// `align`.
+    ///
+    /// The offset of the tail is always a multiple of the alignment of the value, and so is `align`,
+    /// which keeps the header aligned as well.
+    fn offset(align: usize) -> usize {
+        DynStruct::<Header, Tail>::TAIL_OFFSET.wrapping_neg() & (align - 1)
+    }
+}
+
+impl<Header, Tail> Drop for AlignedDynStruct<Header, Tail> {
+    fn drop(&mut self) {
+        /// Frees the allocation, even if dropping the value panics.
+        struct Free {
+            value: *mut u8,
+            layout: Layout,
+            padded: Padded,
+        }
+
+        impl Drop for Free {
+            fn drop(&mut self) {
+                unsafe { self.padded.deallocate(self.value, self.layout) };
+            }
+        }
+
+        let value = self.ptr.as_ptr();
+        unsafe {
+            let _free = Free {
+                value: value.cast::<u8>(),
+                layout: Layout::for_value(&*value),
+                padded: Padded {
+                    align: self.align,
+                    offset: Self::offset(self.align),
+                },
+            };
+            core::ptr::drop_in_place(value);
+        }
+    }
+}
+
+impl<Header, Tail> Deref for AlignedDynStruct<Header, Tail> {
+    type Target = DynStruct<Header, Tail>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<Header, Tail> DerefMut for AlignedDynStruct<Header, Tail> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<Header: fmt::Debug, Tail: fmt::Debug> fmt::Debug for AlignedDynStruct<Header, Tail> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// The global allocator, with a larger alignment and `offset` bytes of padding before every value.
+struct Padded {
+    align: usize,
+    offset: usize,
+}
+
+impl Padded {
+    /// Returns the layout of the allocation for a value with the given layout.
+    fn layout(&self, value: Layout) -> Result<Layout, TryNewError> {
+        self.offset
+            .checked_add(value.size())
+            .and_then(|size| Layout::from_size_align(size, self.align).ok())
+            .map(|layout| layout.pad_to_align())
+            .ok_or(TryNewError::CapacityOverflow)
+    }
+}
+
+unsafe impl RawAlloc for Padded {
+    fn allocate(&self, layout: Layout, zeroed: bool) -> Result<*mut u8, TryNewError> {
+        let raw = crate::allocate(self.layout(layout)?, zeroed)?;
+        Ok(raw.wrapping_add(self.offset))
+    }
+
+    unsafe fn deallocate(&self, raw: *mut u8, layout: Layout) {
+        // This cannot fail, since the same layout was allocated before.
+        let full = crate::__private::unwrap(self.layout(layout));
+        if full.size() != 0 {
+            alloc::alloc::dealloc(raw.sub(self.offset), full);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use alloc::vec;
+    use std::rc::Rc;
+
+    fn tail_align<Header, Tail>(value: &DynStruct<Header, Tail>) -> usize {
+        let addr = value.tail.as_ptr() as usize;
+        1 << addr.trailing_zeros().min(12)
+    }
+
+    #[test]
+    fn aligned_tail() {
+        fn check<Header: Clone + PartialEq + fmt::Debug, Tail: Clone + PartialEq + fmt::Debug>(
+            header: Header,
+            tail: &[Tail],
+        ) {
+            for align in [1, 2, 8, 64, 256] {
+                let value = DynStruct::new_aligned(header.clone(), tail.iter().cloned(), align);
+                assert!(tail_align(&value) >= align, "{:?}", value);
+                assert!(AlignedDynStruct::align(&value) >= align);
+                assert_eq!(value.header, header);
+                assert_eq!(&value.tail, tail);
+                let start = &*value as *const DynStruct<Header, Tail> as *const u8 as usize;
+                assert_eq!(start % DynStruct::<Header, Tail>::ALIGN, 0);
+            }
+        }
+
+        check(1u8, &[2u8, 3, 4]);
+        check(1u32, &[2u8; 100]);
+        check((1u64, 2u8), &[3u16; 7]);
+        check(String::from("header"), &[String::from("tail")]);
+        check((), &[1u128]);
+        check(0u8, &[(); 3]);
+        check((), &[(); 0]);
+    }
+
+    #[test]
+    fn drops() {
+        let counter = Rc::new(());
+        let mut value = DynStruct::new_aligned(counter.clone(), vec![counter.clone(); 3], 64);
+        value.tail[0] = counter.clone();
+        assert_eq!(Rc::strong_count(&counter), 5);
+        drop(value);
+        assert_eq!(Rc::strong_count(&counter), 1);
+
+        // The iterator panicking frees the allocation, including the padding in front.
+        let tail = (0..3).map(|i| {
+            assert!(i < 2, "boom");
+            counter.clone()
+        });
+        let result =
+            std::panic::catch_unwind(|| DynStruct::new_aligned(counter.clone(), tail, 128));
+        assert!(result.is_err());
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "the alignment of a `DynStruct` must be a power of two, not 48")]
+    fn not_a_power_of_two() {
+        DynStruct::new_aligned(0u8, [1u8], 48);
+    }
+
+    #[test]
+    fn overflow() {
+        let result = DynStruct::try_new_aligned(0u8, [0u8; 0], 1 << (usize::BITS - 1));
+        assert_eq!(result.unwrap_err(), TryNewError::CapacityOverflow);
+    }
+}
@@ -0,0 +1,593 @@
+//! [`ArcDynStruct`]: a shared, reference counted `DynStruct` behind a single word.
+
+use alloc::boxed::Box;
+use core::alloc::Layout;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::ptr::NonNull;
+use core::sync::atomic::{self, AtomicUsize, Ordering};
+
+use crate::thin::Prefixed;
+use crate::{tracking, BoxWriter, DynStruct, RawAlloc, TryNewError};
+
+/// Like an `Arc<DynStruct<Header, Tail>>`, but only one pointer wide.
+///
+/// The strong and weak reference counts and the length of the tail are stored at the start of the
+/// allocation, followed by the value itself. The counts are updated just like the ones of an
+/// `Arc`: the value is dropped when the last `ArcDynStruct` is, and the memory is freed once no
+/// [`WeakDynStruct`] is left either.
+pub struct ArcDynStruct<Header, Tail> {
+    /// The start of the allocation, where the counts are stored.
+    raw: NonNull<Counts>,
+    _marker: PhantomData<DynStruct<Header, Tail>>,
+}
+
+/// A pointer to the value of an [`ArcDynStruct`] which does not keep it alive, like the `Weak` of
+/// an `Arc`.
+pub struct WeakDynStruct<Header, Tail> {
+    raw: NonNull<Counts>,
+    _marker: PhantomData<DynStruct<Header, Tail>>,
+}
+
+/// The start of every allocation.
+#[repr(C)]
+struct Counts {
+    strong: AtomicUsize,
+    /// The number of `WeakDynStruct`s, plus one for all `ArcDynStruct`s together.
+    weak: AtomicUsize,
+    len: usize,
+}
+
+// Both owners are single pointers, and so are `Option`s of them.
+const _: () = assert!(size_of::<ArcDynStruct<u8, u64>>() == size_of::<usize>());
+const _: () = assert!(size_of::<Option<ArcDynStruct<(), ()>>>() == size_of::<usize>());
+const _: () = assert!(size_of::<Option<WeakDynStruct<(), ()>>>() == size_of::<usize>());
+
+// SAFETY: like an `Arc`, the value may be dropped by and is shared with any thread holding a
+// pointer to it.
+unsafe impl<Header: Send + Sync, Tail: Send + Sync> Send for ArcDynStruct<Header, Tail> {}
+unsafe impl<Header: Send + Sync, Tail: Send + Sync> Sync for ArcDynStruct<Header, Tail> {}
+unsafe impl<Header: Send + Sync, Tail: Send + Sync> Send for WeakDynStruct<Header, Tail> {}
+unsafe impl<Header: Send + Sync, Tail: Send + Sync> Sync for WeakDynStruct<Header, Tail> {}
+
+/// A soft limit on the reference counts. Like `Arc`, cloning aborts once a count exceeds it, long
+/// before it could overflow, since every thread would have to clone about `isize::MAX` times.
+const MAX_REFCOUNT: usize = isize::MAX as usize;
+
+/// Aborts the process. There is no `process::abort` in `core`, but panicking while already
+/// panicking aborts as well.
+#[cold]
+fn abort() -> ! {
+    struct Abort;
+
+    impl Drop for Abort {
+        fn drop(&mut self) {
+            panic!("reference count overflow");
+        }
+    }
+
+    let _abort = Abort;
+    panic!("reference count overflow");
+}
+
+impl<Header, Tail> ArcDynStruct<Header, Tail> {
+    /// The offset of the value from the start of the allocation, just past the counts.
+    const OFFSET: usize = Prefixed::<Counts>::offset::<Header, Tail>();
+
+    /// Allocates a new value, like [`DynStruct::new`].
+    #[inline]
+    pub fn new<I>(header: Header, tail: I) -> Self
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        crate::__private::unwrap(Self::try_new(header, tail))
+    }
+
+    /// Like [`ArcDynStruct::new`], but returns an error instead of panicking or aborting if the
+    /// value cannot be allocated.
+    pub fn try_new<I>(header: Header, tail: I) -> Result<Self, TryNewError>
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        unsafe {
+            let ptr = BoxWriter::<Header, Tail, Prefixed<Counts>>::from_iter(
+                header,
+                Layout::new::<Header>(),
+                tail,
+                Prefixed::new(),
+            )?;
+            // The value is freed by `WeakDynStruct::drop`, not through a `Box`.
+            tracking::forget(ptr.cast::<u8>());
+            Ok(Self::from_value(ptr.cast::<u8>(), ptr.len()))
+        }
+    }
+
+    /// Returns the number of `ArcDynStruct`s pointing to the value.
+    ///
+    /// Other threads may change the count at any time, so it should only be relied upon when they
+    /// cannot, for example because they do not have a pointer to the value.
+    #[inline]
+    pub fn strong_count(this: &Self) -> usize {
+        this.counts().strong.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of `WeakDynStruct`s pointing to the value.
+    #[inline]
+    pub fn weak_count(this: &Self) -> usize {
+        // One weak reference is held by the strong ones together.
+        this.counts().weak.load(Ordering::Relaxed) - 1
+    }
+
+    /// Returns `true` if both point to the same value, like [`core::ptr::eq`].
+    #[inline]
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        this.raw == other.raw
+    }
+
+    /// Creates a [`WeakDynStruct`] pointing to the same value.
+    pub fn downgrade(this: &Self) -> WeakDynStruct<Header, Tail> {
+        let old = this.counts().weak.fetch_add(1, Ordering::Relaxed);
+        if old > MAX_REFCOUNT {
+            abort();
+        }
+        WeakDynStruct {
+            raw: this.raw,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Moves the value into a `Box` if this is the only `ArcDynStruct` pointing to it, and returns
+    /// it back otherwise.
+    ///
+    /// Any `WeakDynStruct`s pointing to the value can no longer be upgraded afterwards.
+    pub fn try_unwrap(this: Self) -> Result<Box<DynStruct<Header, Tail>>, Self> {
+        let counts = this.counts();
+        if counts
+            .strong
+            .compare_exchange(1, 0, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+        {
+            return Err(this);
+        }
+        // Synchronize with the `Release` decrements of the other owners, which may have used the
+        // value until then, exactly like `drop` does.
+        atomic::fence(Ordering::Acquire);
+
+        let this = core::mem::ManuallyDrop::new(this);
+        let value = this.as_ptr();
+        unsafe {
+            let boxed = crate::copy_into_box(value);
+            crate::poison(value.cast::<u8>(), size_of_val(&*boxed));
+            // Release the weak reference which was held by the strong ones.
+            drop(WeakDynStruct::<Header, Tail> {
+                raw: this.raw,
+                _marker: PhantomData,
+            });
+            Ok(boxed)
+        }
+    }
+
+    /// Returns a mutable reference to the value, cloning it into a new allocation first if other
+    /// `ArcDynStruct`s point to it, like `Arc::make_mut`.
+    ///
+    /// If only `WeakDynStruct`s point to it, the value is moved into a new allocation instead, and
+    /// they can no longer be upgraded. Either way, this is the only pointer to the value afterwards.
+    ///
+    /// ```
+    /// use dyn_struct::ArcDynStruct;
+    ///
+    /// let mut value = ArcDynStruct::new(1u8, [2u32, 3]);
+    /// let other = value.clone();
+    /// ArcDynStruct::make_mut(&mut value).tail[0] = 7;
+    ///
+    /// assert_eq!(&value.tail, &[7, 3]);
+    /// assert_eq!(&other.tail, &[2, 3]);
+    /// ```
+    pub fn make_mut(this: &mut Self) -> &mut DynStruct<Header, Tail>
+    where
+        Header: Clone,
+        Tail: Clone,
+    {
+        // Setting the strong count to zero, just like `Arc::make_mut`, keeps `WeakDynStruct`s from
+        // being upgraded while the weak count is checked. `Acquire` synchronizes with the
+        // decrements of other owners, which may have used the value until then.
+        let counts = this.counts();
+        if counts
+            .strong
+            .compare_exchange(1, 0, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            *this = Self::new(this.header.clone(), this.tail.iter().cloned());
+        } else if counts.weak.load(Ordering::Relaxed) != 1 {
+            // Only weak references are left, which see the value as dropped from now on.
+            unsafe {
+                let moved = Self::copy_from(this.as_ptr());
+                crate::poison(this.as_ptr().cast::<u8>(), size_of_val(&*moved));
+                let old = core::mem::replace(this, moved);
+                // Release the weak reference which was held by the strong ones.
+                drop(WeakDynStruct::<Header, Tail> {
+                    raw: core::mem::ManuallyDrop::new(old).raw,
+                    _marker: PhantomData,
+                });
+            }
+        } else {
+            // This is the only pointer to the value after all.
+            counts.strong.store(1, Ordering::Release);
+        }
+        // SAFETY: no other pointer can reach the value.
+        unsafe { &mut *this.as_ptr() }
+    }
+
+    /// Copies the value at `value` into a new allocation, with the counts in front of it.
+    ///
+    /// # Safety
+    ///
+    /// `value` must point to a valid value, which belongs to the new `ArcDynStruct` afterwards.
+    unsafe fn copy_from(value: *const DynStruct<Header, Tail>) -> Self {
+        let len = (*value).len();
+        let layout = Layout::for_value(&*value);
+        let raw = crate::__private::unwrap(Prefixed::<Counts>::new().allocate(layout, false));
+        raw.copy_from_nonoverlapping(value.cast::<u8>(), layout.size());
+        Self::from_value(raw, len)
+    }
+
+    /// Writes the counts in front of the value at `value`, with `len` elements in its tail, which
+    /// was allocated by [`Prefixed`], and takes it over.
+    #[inline]
+    unsafe fn from_value(value: *mut u8, len: usize) -> Self {
+        let raw = value.sub(Self::OFFSET).cast::<Counts>();
+        raw.write(Counts {
+            strong: AtomicUsize::new(1),
+            weak: AtomicUsize::new(1),
+            len,
+        });
+        ArcDynStruct {
+            raw: NonNull::new_unchecked(raw),
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    fn counts(&self) -> &Counts {
+        // SAFETY: the counts are never freed while there is an `ArcDynStruct`.
+        unsafe { self.raw.as_ref() }
+    }
+
+    /// Returns a pointer to the value, with the length stored in front of it as metadata.
+    #[inline]
+    fn as_ptr(&self) -> *mut DynStruct<Header, Tail> {
+        value_ptr(self.raw, Self::OFFSET)
+    }
+}
+
+/// Returns a pointer to the value at `offset` from `raw`, with the length from the counts.
+#[inline]
+fn value_ptr<Header, Tail>(raw: NonNull<Counts>, offset: usize) -> *mut DynStruct<Header, Tail> {
+    // The length never changes after the allocation has been created, so reading it does not race
+    // with anything.
+    let len = unsafe { raw.as_ref().len };
+    let value = raw.as_ptr().cast::<u8>().wrapping_add(offset);
+    core::ptr::slice_from_raw_parts_mut(value.cast::<()>(), len) as *mut DynStruct<Header, Tail>
+}
+
+impl<Header, Tail> Clone for ArcDynStruct<Header, Tail> {
+    #[inline]
+    fn clone(&self) -> Self {
+        // A new reference can only be created from an existing one, which already keeps the value
+        // alive, so no synchronization is needed.
+        let old = self.counts().strong.fetch_add(1, Ordering::Relaxed);
+        if old > MAX_REFCOUNT {
+            abort();
+        }
+        ArcDynStruct {
+            raw: self.raw,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<Header, Tail> Drop for ArcDynStruct<Header, Tail> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.counts().strong.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        // Every use of the value by other owners happens before their decrement, which this fence
+        // synchronizes with, so they all happen before the value is dropped.
+        atomic::fence(Ordering::Acquire);
+
+        // The weak reference held by the strong ones frees the memory, even if dropping the value
+        // panics.
+        let _weak = WeakDynStruct::<Header, Tail> {
+            raw: self.raw,
+            _marker: PhantomData,
+        };
+        unsafe { core::ptr::drop_in_place(self.as_ptr()) };
+    }
+}
+
+impl<Header, Tail> Deref for ArcDynStruct<Header, Tail> {
+    type Target = DynStruct<Header, Tail>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.as_ptr() }
+    }
+}
+
+impl<Header, Tail> From<Box<DynStruct<Header, Tail>>> for ArcDynStruct<Header, Tail> {
+    /// Moves the value into a new allocation, with the counts in front of it.
+    fn from(value: Box<DynStruct<Header, Tail>>) -> Self {
+        unsafe {
+            let this = Self::copy_from(&*value);
+            crate::__private::free(value);
+            this
+        }
+    }
+}
+
+impl<Header: fmt::Debug, Tail: fmt::Debug> fmt::Debug for ArcDynStruct<Header, Tail> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<Header, Tail> WeakDynStruct<Header, Tail> {
+    /// Returns an [`ArcDynStruct`] pointing to the value, or `None` if it has been dropped.
+    pub fn upgrade(&self) -> Option<ArcDynStruct<Header, Tail>> {
+        let strong = &self.counts().strong;
+        let mut count = strong.load(Ordering::Relaxed);
+        loop {
+            if count == 0 {
+                return None;
+            }
+            if count > MAX_REFCOUNT {
+                abort();
+            }
+            // `Acquire` on success and `Relaxed` on failure, just like `Arc`'s `Weak::upgrade`.
+            match strong.compare_exchange_weak(
+                count,
+                count + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    return Some(ArcDynStruct {
+                        raw: self.raw,
+                        _marker: PhantomData,
+                    })
+                }
+                Err(current) => count = current,
+            }
+        }
+    }
+
+    /// Returns the number of `ArcDynStruct`s pointing to the value.
+    #[inline]
+    pub fn strong_count(&self) -> usize {
+        self.counts().strong.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    fn counts(&self) -> &Counts {
+        // SAFETY: the counts are never freed while there is a `WeakDynStruct`.
+        unsafe { self.raw.as_ref() }
+    }
+}
+
+impl<Header, Tail> Clone for WeakDynStruct<Header, Tail> {
+    #[inline]
+    fn clone(&self) -> Self {
+        let old = self.counts().weak.fetch_add(1, Ordering::Relaxed);
+        if old > MAX_REFCOUNT {
+            abort();
+        }
+        WeakDynStruct {
+            raw: self.raw,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<Header, Tail> Drop for WeakDynStruct<Header, Tail> {
+    fn drop(&mut self) {
+        if self.counts().weak.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        atomic::fence(Ordering::Acquire);
+
+        // The value itself has been dropped or moved out already, but its length and thus its
+        // layout are still known.
+        let value = value_ptr::<Header, Tail>(self.raw, ArcDynStruct::<Header, Tail>::OFFSET);
+        let len = self.counts().len;
+        let (layout, _) =
+            crate::__private::unwrap(crate::layout::for_len::<Tail>(Layout::new::<Header>(), len));
+        unsafe { Prefixed::<Counts>::new().deallocate(value.cast::<u8>(), layout) };
+    }
+}
+
+impl<Header, Tail> fmt::Debug for WeakDynStruct<Header, Tail> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("(WeakDynStruct)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::sync::atomic::AtomicUsize;
+    use std::format;
+    use std::sync::Arc;
+
+    /// Counts how many times it was dropped.
+    struct Guard(Arc<AtomicUsize>);
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn shared() {
+        let value = ArcDynStruct::new(1u8, [2u64, 3, 4]);
+        assert_eq!(value.header, 1);
+        assert_eq!(&value.tail, &[2, 3, 4]);
+        assert_eq!(
+            &*value as *const DynStruct<u8, u64> as *const u8 as usize % 8,
+            0
+        );
+        assert_eq!(format!("{:?}", value), format!("{:?}", &*value));
+
+        let other = value.clone();
+        assert!(ArcDynStruct::ptr_eq(&value, &other));
+        assert_eq!(ArcDynStruct::strong_count(&value), 2);
+        let value = ArcDynStruct::try_unwrap(value).unwrap_err();
+        drop(other);
+        let boxed = ArcDynStruct::try_unwrap(value).unwrap();
+        assert_eq!(*boxed, *DynStruct::new(1u8, [2u64, 3, 4]));
+
+        let value = ArcDynStruct::from(boxed);
+        assert_eq!(&value.tail, &[2, 3, 4]);
+        let empty = ArcDynStruct::new((), Vec::<()>::new());
+        assert!(empty.is_empty());
+        assert!(ArcDynStruct::try_unwrap(empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn weak() {
+        let value = ArcDynStruct::new(String::from("header"), vec![String::from("tail")]);
+        let weak = ArcDynStruct::downgrade(&value);
+        assert_eq!(ArcDynStruct::weak_count(&value), 1);
+        assert_eq!(weak.strong_count(), 1);
+        let upgraded = weak.upgrade().unwrap();
+        assert_eq!(upgraded.header, "header");
+        drop((value, upgraded));
+
+        assert_eq!(weak.strong_count(), 0);
+        assert!(weak.upgrade().is_none());
+        let other = weak.clone();
+        drop(weak);
+        assert!(other.upgrade().is_none());
+
+        let value = ArcDynStruct::new(0u8, [1u8]);
+        let weak = ArcDynStruct::downgrade(&value);
+        let boxed = ArcDynStruct::try_unwrap(value).unwrap();
+        assert!(weak.upgrade().is_none());
+        assert_eq!(&boxed.tail, &[1]);
+    }
+
+    #[test]
+    fn make_mut() {
+        let counter = Arc::new(());
+
+        // Unique: the value is changed in place.
+        let mut value = ArcDynStruct::new(counter.clone(), vec![counter.clone(); 2]);
+        let before = &*value as *const DynStruct<Arc<()>, Arc<()>>;
+        let unique = ArcDynStruct::make_mut(&mut value) as *const DynStruct<Arc<()>, Arc<()>>;
+        assert_eq!(unique, before);
+        assert_eq!(ArcDynStruct::strong_count(&value), 1);
+        assert_eq!(Arc::strong_count(&counter), 4);
+
+        // Shared: the value is cloned, and the other owner keeps the original.
+        let other = value.clone();
+        ArcDynStruct::make_mut(&mut value).tail[1] = Arc::new(());
+        assert!(!ArcDynStruct::ptr_eq(&value, &other));
+        assert_eq!(&*other as *const DynStruct<Arc<()>, Arc<()>>, before);
+        assert!(Arc::ptr_eq(&other.tail[1], &counter));
+        assert!(!Arc::ptr_eq(&value.tail[1], &counter));
+        assert_eq!(ArcDynStruct::strong_count(&value), 1);
+        assert_eq!(ArcDynStruct::strong_count(&other), 1);
+        assert_eq!(Arc::strong_count(&counter), 6);
+        drop(other);
+        assert_eq!(Arc::strong_count(&counter), 3);
+
+        // Only weak references: the value is moved, and they cannot be upgraded anymore.
+        let weak = ArcDynStruct::downgrade(&value);
+        ArcDynStruct::make_mut(&mut value).header = Arc::new(());
+        assert!(weak.upgrade().is_none());
+        assert_eq!(ArcDynStruct::weak_count(&value), 0);
+        assert_eq!(Arc::strong_count(&counter), 2);
+        drop((value, weak));
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn trait_objects() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let closure = |i: u32| -> Box<dyn Fn() -> u32 + Send + Sync> {
+            let guard = Guard(dropped.clone());
+            Box::new(move || {
+                let _ = &guard;
+                i
+            })
+        };
+
+        let value = ArcDynStruct::new(closure(0), (1..4).map(closure));
+        let weak = ArcDynStruct::downgrade(&value);
+        let other = value.clone();
+        assert_eq!(other.tail.iter().map(|f| f()).sum::<u32>(), 6);
+        drop(value);
+        assert_eq!(dropped.load(Ordering::Relaxed), 0);
+        drop(other);
+        assert_eq!(dropped.load(Ordering::Relaxed), 4);
+        drop(weak);
+
+        // A panicking destructor still frees the memory.
+        struct Bomb;
+        impl Drop for Bomb {
+            fn drop(&mut self) {
+                panic!("boom");
+            }
+        }
+        let value = ArcDynStruct::new(Guard(dropped.clone()), [Bomb]);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(value)));
+        assert!(result.is_err());
+        assert_eq!(dropped.load(Ordering::Relaxed), 5);
+    }
+
+    #[test]
+    fn threads() {
+        const THREADS: usize = 4;
+        let rounds = if cfg!(miri) { 20 } else { 2000 };
+
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let tail: Vec<Guard> = (0..3).map(|_| Guard(dropped.clone())).collect();
+        let value = ArcDynStruct::new(Guard(dropped.clone()), tail);
+        let weak = ArcDynStruct::downgrade(&value);
+
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let value = value.clone();
+                let weak = weak.clone();
+                std::thread::spawn(move || {
+                    let mut clones = Vec::new();
+                    for i in 0..rounds {
+                        clones.push(value.clone());
+                        if i % 3 == 0 {
+                            clones.push(weak.upgrade().unwrap());
+                        }
+                        if i % 5 == 0 {
+                            clones.clear();
+                        }
+                        assert_eq!(value.tail.len(), 3);
+                    }
+                })
+            })
+            .collect();
+        drop(value);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(weak.strong_count(), 0);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(dropped.load(Ordering::Relaxed), 4);
+    }
+}
@@ -0,0 +1,230 @@
+//! The layout of a header followed by a tail.
+//!
+//! Every allocation is made, resized and freed with a layout from this module. `Box` frees a value
+//! with `Layout::for_value`, so these functions must agree exactly with the layout the compiler
+//! gives a `#[repr(C)]` struct whose last field is the tail, including its trailing padding.
+
+use crate::TryNewError;
+use core::alloc::Layout;
+use core::ops::Range;
+
+/// Returns the layout of the fields of `Header`, which excludes its trailing padding.
+pub(crate) const fn header<Header>(header_end: usize) -> Result<Layout, TryNewError> {
+    match Layout::from_size_align(header_end, align_of::<Header>()) {
+        Ok(layout) => Ok(layout),
+        Err(_) => Err(TryNewError::CapacityOverflow),
+    }
+}
+
+/// Returns the offset of a tail of `Tail` elements, which follows the fields of a header ending at
+/// `header_end`.
+pub(crate) const fn tail_offset<Tail>(header_end: usize) -> usize {
+    // Alignments are powers of two. A valid header ends before `isize::MAX`, so this can only
+    // overflow if it is called with a bogus `header_end`.
+    let align = align_of::<Tail>();
+    match header_end.checked_add(align - 1) {
+        Some(end) => end & !(align - 1),
+        None => panic!("the offset of the tail overflows"),
+    }
+}
+
+/// Returns the layout of a value with the fields in `header` followed by a tail with the layout
+/// `tail`, and the offset of the tail.
+pub(crate) const fn dyn_layout(
+    header: Layout,
+    tail: Layout,
+) -> Result<(Layout, usize), TryNewError> {
+    match header.extend(tail) {
+        Ok((layout, offset)) => Ok((layout.pad_to_align(), offset)),
+        Err(_) => Err(TryNewError::CapacityOverflow),
+    }
+}
+
+/// Returns the layout of a header followed by `len` elements of `Tail`, and the offset of the
+/// first element.
+///
+/// This is a `const fn`, so that values with a tail length known at compile time can be sized in
+/// constants, for example to set aside a buffer for them.
+#[inline]
+pub(crate) const fn for_len<Tail>(
+    header: Layout,
+    len: usize,
+) -> Result<(Layout, usize), TryNewError> {
+    for_element(header, Layout::new::<Tail>(), len)
+}
+
+/// Like [`for_len`], with the layout of the elements instead of their type, so that it is only
+/// compiled once.
+pub(crate) const fn for_element(
+    header: Layout,
+    element: Layout,
+    len: usize,
+) -> Result<(Layout, usize), TryNewError> {
+    // This is `Layout::array`, which checks that the size does not exceed `isize::MAX` once it is
+    // rounded up to the alignment.
+    let tail = match element.size().checked_mul(len) {
+        Some(size) => Layout::from_size_align(size, element.align()),
+        None => return Err(TryNewError::CapacityOverflow),
+    };
+    match tail {
+        Ok(tail) => dyn_layout(header, tail),
+        Err(_) => Err(TryNewError::CapacityOverflow),
+    }
+}
+
+/// Returns the padding of a value of `size` bytes with `len` tail elements: the bytes between the
+/// end of the header at `header_end` and the tail, and the bytes after the tail.
+pub(crate) fn padding<Tail>(header_end: usize, len: usize, size: usize) -> [Range<usize>; 2] {
+    let offset = tail_offset::<Tail>(header_end);
+    [header_end..offset, offset + len * size_of::<Tail>()..size]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::boxed::Box;
+    use core::fmt::Debug;
+    use core::ptr::addr_of;
+
+    #[repr(C)]
+    struct Pair<Header, Tail: ?Sized> {
+        header: Header,
+        tail: Tail,
+    }
+
+    fn offset_of_tail<Header, Tail: ?Sized>(value: &Pair<Header, Tail>) -> usize {
+        let base = (value as *const Pair<Header, Tail>).cast::<u8>();
+        let tail = addr_of!(value.tail).cast::<u8>();
+        tail as usize - base as usize
+    }
+
+    // Evaluates the layouts at compile time, and compares them with the ones at runtime.
+    #[test]
+    fn const_eval() {
+        const HEADER: Layout = Layout::new::<(u8, u16)>();
+        const PADDED: (Layout, usize) = match for_len::<u64>(HEADER, 3) {
+            Ok(layout) => layout,
+            Err(_) => panic!(),
+        };
+        const _: () = assert!(for_len::<u64>(HEADER, usize::MAX / 4).is_err());
+
+        assert_eq!(Ok(PADDED), for_len::<u64>(HEADER, 3));
+        assert_eq!((PADDED.0.size(), PADDED.0.align(), PADDED.1), (32, 8, 8));
+        let buffer = [0u8; PADDED.0.size()];
+        assert_eq!(buffer.len(), 32);
+    }
+
+    // Compares against the layout the compiler gives a struct with the same fields, both with a
+    // slice and with a trait object as the tail.
+    #[test]
+    fn matches_compiler() {
+        fn check<Header: Default, Tail: Default + Debug + 'static>() {
+            let slice: Box<Pair<Header, [Tail]>> = Box::new(Pair {
+                header: Header::default(),
+                tail: [Tail::default(), Tail::default(), Tail::default()],
+            });
+            for len in 0..=3 {
+                let value = slice_prefix(&slice, len);
+                let (layout, offset) = for_len::<Tail>(Layout::new::<Header>(), len).unwrap();
+                assert_eq!(Layout::for_value(value), layout);
+                assert_eq!(offset_of_tail(value), offset);
+                assert_eq!(tail_offset::<Tail>(size_of::<Header>()), offset);
+            }
+
+            let object: Box<Pair<Header, dyn Debug>> = Box::new(Pair {
+                header: Header::default(),
+                tail: Tail::default(),
+            });
+            let (layout, offset) =
+                dyn_layout(Layout::new::<Header>(), Layout::for_value(&object.tail)).unwrap();
+            assert_eq!(Layout::for_value(&*object), layout);
+            assert_eq!(offset_of_tail(&*object), offset);
+        }
+
+        // Shortens the tail of `value` without touching its header.
+        fn slice_prefix<Header, Tail>(
+            value: &Pair<Header, [Tail]>,
+            len: usize,
+        ) -> &Pair<Header, [Tail]> {
+            assert!(len <= value.tail.len());
+            let raw = core::ptr::slice_from_raw_parts(
+                (value as *const Pair<Header, [Tail]>).cast::<Tail>(),
+                len,
+            );
+            unsafe { &*(raw as *const Pair<Header, [Tail]>) }
+        }
+
+        macro_rules! matrix {
+            ($($header:ty),* ; $tails:tt) => {
+                $(matrix!(@row $header ; $tails);)*
+            };
+            (@row $header:ty ; [$($tail:ty),*]) => {
+                $(check::<$header, $tail>();)*
+            };
+        }
+
+        matrix!(
+            (), u8, u16, u64, u128, [u8; 3], (u64, u8), (u8, u16, u8), [u64; 0], (u8, [u32; 0]);
+            [(), u8, [u8; 3], u16, u64, u128, (u16, u8), (u8, u64), [u32; 0], [(); 4]]
+        );
+    }
+
+    #[test]
+    fn tail_offset_after_fields() {
+        fn check<Header, Tail>(header_end: usize) {
+            let header = header::<Header>(header_end).unwrap();
+            let (_, offset) = header.extend(Layout::new::<Tail>()).unwrap();
+            assert_eq!(tail_offset::<Tail>(header_end), offset);
+        }
+
+        for header_end in 0..=16 {
+            check::<u8, u8>(header_end);
+            check::<u8, u16>(header_end);
+            check::<u64, u32>(header_end);
+            check::<u16, u64>(header_end);
+            check::<(), [u128; 0]>(header_end);
+        }
+    }
+
+    #[test]
+    fn overflow() {
+        let header = Layout::new::<u64>();
+        let limit = isize::MAX as usize;
+
+        // The largest value which fits, and one byte more, which would need padding beyond
+        // `isize::MAX`.
+        let (layout, _) = for_len::<u8>(header, limit - 15).unwrap();
+        assert_eq!(layout.size(), limit - 7);
+        assert_eq!(
+            for_len::<u8>(header, limit - 14),
+            Err(TryNewError::CapacityOverflow)
+        );
+
+        // Multiplying the length by the size of the elements would wrap around.
+        for len in [
+            usize::MAX / 8,
+            usize::MAX / 8 + 1,
+            usize::MAX / 4,
+            usize::MAX,
+        ] {
+            assert_eq!(
+                for_len::<u64>(header, len),
+                Err(TryNewError::CapacityOverflow)
+            );
+        }
+        assert_eq!(
+            for_len::<[u8; 3]>(header, usize::MAX / 3 + 1),
+            Err(TryNewError::CapacityOverflow)
+        );
+
+        // Zero-sized elements never take any space.
+        let (layout, _) = for_len::<()>(header, usize::MAX).unwrap();
+        assert_eq!(layout, header);
+
+        // A header which ends beyond `isize::MAX` is rejected before anything is added to it.
+        assert_eq!(
+            super::header::<u8>(limit + 1),
+            Err(TryNewError::CapacityOverflow)
+        );
+    }
+}
@@ -50,8 +50,8 @@
 //! ## The Derive Macro
 //!
 //! The `DynStruct` macro can be applied to any `#[repr(C)]` struct that contains a
-//! dynamically sized array as its last field. Fields only have a single constraint:
-//! they have to implement `Copy`.
+//! dynamically sized array as its last field. There are no constraints on the types of the
+//! fields: they are moved into the allocation, and dropped together with it.
 //!
 //! ### Example
 //!
@@ -80,16 +80,286 @@
 //! }
 //! ```
 //!
+//! A `new_from_vec` function moves the elements out of a `Vec` instead, and if the elements are
+//! `Clone`, `new_from_slice` clones them from a slice.
+//!
+//! A field marked with `#[dyn_struct(into)]` can be passed to `new` as anything that implements
+//! `Into` its type, such as a `&str` for a `String`.
+//!
+//! Fields marked with `#[dyn_struct(skip)]`, such as `PhantomData` markers, are not taken by the
+//! generated functions at all and are set to their `Default` value instead.
+//!
+//! Fields behind `#[cfg(...)]` only appear in the generated functions when they are enabled, and
+//! attributes of other macros on the fields are ignored.
+//!
+//! In the other direction, `into_parts` moves all fields back out of the `Box`, with the last field as
+//! a `Vec` (or a `String` for `str`). `into_vec` and `into_string` only return the last field.
+//!
+//! For slices, `with_extended_tail` appends clones of more elements, `with_extended_tail_iter`
+//! appends the items of an `ExactSizeIterator`, and `with_truncated_tail` drops elements from the
+//! end. All of them resize the allocation with `realloc`, so the allocator can keep the value in
+//! place, which makes appending in a loop much cheaper than building a new value each time.
+//! `DynStruct` has these functions as well.
+//!
+//! To pass a value through FFI, `into_raw_parts` returns a thin pointer to it and the length of the
+//! tail, and the unsafe `from_raw_parts` turns them back into a `Box`.
+//!
+//! With `#[dyn_struct(header = "MyHeader")]`, the fields before a slice are also generated as a
+//! `#[repr(C)]` struct called `MyHeader`. `as_dyn_struct`, `as_dyn_struct_mut`, `into_dyn_struct` and
+//! `from_dyn_struct` then convert to and from `DynStruct<MyHeader, T>`, so that its methods can be used
+//! as well. The conversion fails to compile if the two types are laid out differently, which happens
+//! if the tail would start inside the trailing padding of `MyHeader`, or with `#[repr(align(N))]`.
+//!
+//! Other layouts can be vouched for once, with an `unsafe impl` of [`SameRepr<MyDynamicType>`] for a
+//! `DynStruct`. `DynStruct::into_repr` and `DynStruct::from_repr` then convert between the two without
+//! further `unsafe` code. Without such an impl, the unsafe `DynStruct::into_repr_checked` compares the
+//! offset of the tail, the tail elements and the size and alignment of both types first, and returns
+//! them as a `LayoutMismatch` if they differ.
+//!
+//! For both slices and `str`, `len` and `is_empty` methods are generated as well, unless the struct
+//! has the `#[dyn_struct(no_len)]` attribute.
+//!
+//! The layout is available without allocating a value: `HEADER_LAYOUT` holds the size and
+//! alignment of the fields before the tail, and for slices and `str`, `TAIL_OFFSET` is the offset of
+//! the tail and `layout_for_len(len)` returns the layout of a value with `len` elements in its tail.
+//! A plain `DynStruct` has the same `TAIL_OFFSET` and `layout_for_len`, as well as `ALIGN` and
+//! `size_for_len(len)`. These are all constants or `const fn`s, so a tail length known at compile time
+//! gives a constant layout, which can be used as an array length to set aside a buffer.
+//!
+//! An `empty` function, which takes only the fields before the tail, creates a value with an empty
+//! tail. With `#[dyn_struct(default)]`, `Default` is implemented for `Box<MyDynamicType>` as well,
+//! using the default value of every other field.
+//!
+//! To keep the fields private, `#[dyn_struct(getters)]` generates a method with the name of each
+//! field which returns it. Primitives, shared references and pointers, and arrays and tuples of those,
+//! are returned by value, as are fields marked with `#[dyn_struct(copy)]`. Other fields are returned by
+//! reference. A field's `#[dyn_struct(vis = "...")]` sets the visibility of its getter.
+//!
+//! For structs with many fields, `#[dyn_struct(builder)]` generates a `MyDynamicTypeBuilder`, returned
+//! by `MyDynamicType::builder()`, with a method to set each field and one to extend the tail (such as
+//! `extend_dynamic`). Its `build` method allocates the value, or returns [`MissingField`] if a field was
+//! not set. A builder requires named fields, and a slice or `str` as the tail.
+//!
+//! With `#[dyn_struct(owned)]`, a `MyDynamicTypeOwned` struct is generated as well, with the same
+//! fields but a `Vec` or `String` in place of the slice or `str`, so that the tail can grow. It is
+//! converted from a `Box<MyDynamicType>` with `From`, and back with `into_dyn`. Traits can be derived
+//! for it with `#[dyn_struct(owned(Debug, Clone, serde::Deserialize))]`, in which case `#[serde(...)]`
+//! attributes are copied over as well.
+//!
+//! `#[dyn_struct(view)]` generates a `MyDynamicTypeRef<'view>` struct, which holds a reference to
+//! every field including the tail, and `as_view` to create it. The view is `Copy`, so it is cheap to
+//! pass around without naming the dynamically sized type.
+//!
+//! If all fields are [`Pod`], `#[dyn_struct(pod)]` generates `as_bytes` and `as_bytes_mut`, which view
+//! the entire value as bytes. The struct may not contain any padding, which is checked at compile time.
+//!
+//! A `DynStruct` may have padding between its header and its tail, and after the tail. If both are
+//! [`Pod`], `DynStruct::as_bytes` zeroes that padding with `DynStruct::zero_padding` before viewing
+//! the value as bytes, so that equal values have equal bytes, for hashing or content addressing.
+//!
+//! If all fields implement `Clone`, `#[dyn_struct(clone)]` generates `clone_box`, which copies the
+//! value into a new `Box`, and implements `Clone` for `Box<MyDynamicType>`.
+//!
+//! `#[dyn_struct(partial_eq)]`, `#[dyn_struct(eq)]` and `#[dyn_struct(hash)]` implement `PartialEq`,
+//! `PartialEq` and `Eq`, and `Hash` for `MyDynamicType`, and through that for `Box<MyDynamicType>`, so
+//! it can be used as a `HashMap` key. All fields are compared, including the tail, so values with tails
+//! of different lengths are never equal. Only the field types are bound, not the generic parameters,
+//! and `Hash` is consistent with `Eq`, also for `str` tails.
+//!
+//! With the `serde` feature of this crate enabled, `#[dyn_struct(serde)]` implements `Serialize` for
+//! slice and `str` tails, and `Deserialize` for `Box<MyDynamicType>`. The fields are written like those
+//! of a regular struct, and `#[serde(...)]` attributes on the struct and its fields are respected.
+//!
+//! The generated code refers to this crate as `dyn_struct`. If it is only available under another
+//! path, for example through a re-export, set that path with `#[dyn_struct(crate = "facade::dst")]`.
+//!
+//! This crate is `no_std` and only needs `alloc`. The code generated by the derive macro does not
+//! name `std` either, so it can be used in `no_std` crates as well.
+//!
+//! With `#[dyn_struct(zeroable)]`, a `new_zeroed` function is generated too, which takes the length
+//! of the tail instead of its elements and leaves them all zero. The elements have to implement
+//! [`Zeroable`].
+//!
+//! To fill the tail in place, `#[dyn_struct(uninit)]` generates a `new_uninit` function which returns
+//! a `MyDynamicTypeUninit`: the same struct with a tail of `MaybeUninit` elements. Once all of
+//! them are initialized, `assume_init` turns it into a `MyDynamicType`.
+//!
+//! If the last field is a `str` instead of a slice, the generated `new` function takes a `&str`
+//! and copies its bytes into the allocation:
+//!
+//! ```ignore
+//! #[repr(C)]
+//! #[derive(DynStruct)]
+//! struct Symbol {
+//!     pub id: u32,
+//!     pub name: str,
+//! }
+//!
+//! let symbol: Box<Symbol> = Symbol::new(7, "hello");
+//! assert_eq!(&symbol.name, "hello");
+//! ```
+//!
+//! Trait objects work as well. In that case `new` is generic over the concrete type of the last
+//! field, which is converted into the trait object for you:
+//!
+//! ```ignore
+//! #[repr(C)]
+//! #[derive(DynStruct)]
+//! struct Task {
+//!     pub priority: u8,
+//!     pub job: dyn Fn() + Send,
+//! }
+//!
+//! let task: Box<Task> = Task::new(1, || println!("hello"));
+//! (task.job)();
+//! ```
+//!
+//! Unless the trait object names a lifetime, it is `'static`. Passing a reference to a local then
+//! fails to compile, with an error pointing at the trait object, rather than storing just the
+//! reference in the tail.
+//! To store borrowed values on purpose, add a lifetime to the struct: with `value: dyn Debug + 'a`,
+//! `new(&local)` stores a reference, and the struct borrows `local`.
+//!
+//! `DynStruct`, derived structs and the types generated for them are `Send`, `Sync`, `UnwindSafe` and
+//! `RefUnwindSafe` exactly when their fields are. A trait object tail therefore has to name the auto
+//! traits it should keep, as in `dyn Fn() + Send + UnwindSafe`.
+//!
+//! Any other dynamically sized type whose metadata is a length, such as another struct deriving
+//! `DynStruct`, can also be used as the last field. The generated `new` function then takes the
+//! last field as a `Box`, and moves its contents into the new allocation.
+//!
+//! Tuple structs, such as `struct Payload(pub u32, pub [u8]);`, are supported as well.
+//!
+//! The generated constructor can be renamed with `#[dyn_struct(constructor = "...")]`, for example
+//! to define your own `new` on top of it:
+//!
+//! ```ignore
+//! #[repr(C)]
+//! #[derive(DynStruct)]
+//! #[dyn_struct(constructor = "new_unchecked")]
+//! struct Sorted {
+//!     pub values: [u32],
+//! }
+//! ```
+//!
+//! The constructor has the same visibility as the struct, unless specified otherwise with
+//! `#[dyn_struct(vis = "pub(crate)")]`, and `#[dyn_struct(skip_new)]` leaves out the constructor
+//! entirely.
+//!
+//! With `#[dyn_struct(try_new)]`, a `try_new` function is generated as well, which returns a
+//! [`TryNewError`] instead of panicking or aborting when the value cannot be allocated.
+//!
 //! Due to the nature of dynamically sized types, the resulting value has to be
-//! built on the heap. For safety reasons we currently only allow returning `Box`,
-//! though in a future version we may also allow `Rc` and `Arc`. In the meantime it
-//! is posible to use `Arc::from(MyDynamicType::new(...))`.
+//! built on the heap. The generated `new` function returns a `Box`, and with
+//! `#[dyn_struct(rc)]` or `#[dyn_struct(arc)]` the functions `new_rc` and `new_arc` are generated as
+//! well. These build the value in a `Box` first and then move it, just like
+//! `Arc::from(MyDynamicType::new(...))` does.
+//!
+//! Many values with the same header and tail types can also share one allocation: a [`DynSlab`]
+//! appends each value to a single growing buffer, aligned just like its own `Box` would be, and
+//! returns a [`SlabHandle`] to look it up again. Growing the buffer moves the values, so references
+//! into the slab cannot be held across a `push`, while handles stay valid. The slab drops its values
+//! in the order they were pushed.
+//!
+//! A `Box<DynStruct>` is two words wide, since it stores the length of the tail next to the
+//! pointer. A [`ThinDynStruct`] stores the length at the start of the allocation instead, so it is a
+//! single pointer, like a `Box` of a sized type. It dereferences to the `DynStruct`, and converts
+//! from and to a `Box` by moving the value into a new allocation.
+//!
+//! [`ArcDynStruct`] is the shared counterpart: its strong and weak reference counts are stored in
+//! front of the value as well, so it is one pointer wide, unlike an `Arc<DynStruct>`. It can be
+//! cloned across threads, downgraded to a [`WeakDynStruct`], and moved back into a `Box` with
+//! `ArcDynStruct::try_unwrap` once it is the last strong reference. `ArcDynStruct::make_mut` clones
+//! the value into a new allocation if it is shared, and then returns a mutable reference to it.
+//!
+//! `DynStruct::new_aligned` aligns the tail to a chosen power of two, such as 64 bytes for SIMD
+//! loads, even if its elements need less. A `Box` would free such a value with the wrong alignment,
+//! so it returns an [`AlignedDynStruct`] instead, which dereferences to the `DynStruct` and frees the
+//! allocation with the layout it was made with.
+//!
+//! A [`SmallDynStruct<Header, Tail, N>`](SmallDynStruct) keeps values whose tail takes up at most `N` bytes inline,
+//! like a `SmallVec`, and only allocates longer ones as a `Box`. It dereferences to the `DynStruct`
+//! either way, and converts from and to a `Box`.
+//!
+//! A [`DynStructPool`] keeps the allocations of dropped values and reuses them for new values with
+//! the same tail length, to save the round trip through the allocator when many values of the same
+//! shape are built and dropped. The tail of a reused value keeps its old elements unless it is acquired
+//! with `acquire_zeroed`, so the elements must be `Copy` and [`Zeroable`].
+//!
+//! A [`DynVec`] is the growable counterpart of a `Box<DynStruct>`, like a `Vec` with a header in
+//! front of its elements. It reserves room for more elements than it holds, doubling its capacity as
+//! elements are pushed, and turns into a `Box<DynStruct>` without copying, shrinking the allocation if
+//! it has room left.
+//!
+//! On nightly, the `allocator_api` feature of this crate adds `DynStruct::new_in` and
+//! `DynStruct::try_new_in`, which allocate the value in a custom `Allocator`. With
+//! `#[dyn_struct(allocator_api)]`, the derive generates `new_in` (and `try_new_in`) as well, which
+//! take the allocator as their last parameter and return a `Box<MyDynamicType, A>`.
+//!
+//! The `bumpalo` feature adds `DynStruct::new_in_bump` and `DynStruct::try_new_in_bump`, which
+//! allocate the value in a [`bumpalo::Bump`](https://docs.rs/bumpalo) arena and return a
+//! `&'b mut DynStruct` borrowing it. With `#[dyn_struct(bumpalo)]`, the derive generates `new_in_bump`
+//! (and `try_new_in_bump`) as well, also for trait object tails, which take the arena as their last
+//! parameter. Like values allocated with `Bump::alloc`, these values are never dropped: the arena frees
+//! its memory without running destructors, so whatever the fields own is leaked.
+//!
+//! The `rayon` feature adds `DynStruct::from_par_iter` and `DynStruct::try_from_par_iter`, which
+//! build the tail from an `IndexedParallelIterator`. The workers write their items straight into the
+//! allocation, instead of collecting them into a `Vec` which is then copied. If one of them panics, the
+//! items written so far are dropped along with the header before the panic is passed on.
+//!
+//! With the `debug-poison` feature, debug builds overwrite memory with `0xDD` after the values in it
+//! have been moved out and before it is freed, for example by `into_parts` or `from_vec`. Unsafe code
+//! which keeps using a stale pointer then reads an obvious pattern instead of plausible values. The
+//! feature does nothing in release builds.
+//!
+//! With the `layout-tracking` feature, the layout of every allocated value is recorded. Turning a
+//! pointer back into a `Box`, with `from_raw_parts` or `DynStruct::into_repr` and `from_repr`, then
+//! panics if the new type would free the value with a different layout, instead of corrupting the
+//! allocator later on. The table holds one entry per address and is meant for debugging only.
+
+#![no_std]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+extern crate alloc;
+#[cfg(test)]
+extern crate std;
 
 #[cfg(feature = "derive")]
 pub use dyn_struct_derive::DynStruct;
 
-use std::mem::{align_of, size_of, MaybeUninit};
+mod aligned;
+mod arc;
+mod layout;
+#[cfg(feature = "rayon")]
+mod par;
+mod pool;
+mod slab;
+mod small;
+mod thin;
+mod tracking;
+mod vec;
+
+pub use aligned::AlignedDynStruct;
+pub use arc::{ArcDynStruct, WeakDynStruct};
+pub use pool::{DynStructPool, PooledDynStruct};
+pub use slab::{DynSlab, SlabHandle};
+pub use small::SmallDynStruct;
+pub use thin::ThinDynStruct;
+pub use vec::DynVec;
 
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{ManuallyDrop, MaybeUninit};
+
+/// A `Header` followed by a slice of `Tail`s, in a single allocation.
+///
+/// Like a struct holding its fields by value, it is covariant in both parameters, and dropping it
+/// drops the header and then every element of the tail, so the borrow checker requires both to
+/// outlive the value.
 #[repr(C)]
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct DynStruct<Header, Tail> {
@@ -105,213 +375,1658 @@ impl<Header, Tail> DynStruct<Header, Tail> {
         I: IntoIterator<Item = Tail>,
         I::IntoIter: ExactSizeIterator,
     {
-        let tail = tail.into_iter();
-
-        let mut writer = BoxWriter::<Header, Tail>::new(header, tail.len());
+        __private::unwrap(Self::try_new(header, tail))
+    }
 
-        for value in tail {
-            writer.write_tail::<I::IntoIter>(value);
+    /// Like [`DynStruct::new`], but returns an error instead of panicking or aborting if the value
+    /// cannot be allocated. In that case both `header` and the iterator are dropped.
+    #[inline]
+    pub fn try_new<I>(header: Header, tail: I) -> Result<Box<Self>, TryNewError>
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        unsafe {
+            let ptr = BoxWriter::from_iter(header, Layout::new::<Header>(), tail, Global)?;
+            Ok(Box::from_raw(ptr as *mut Self))
         }
+    }
 
-        writer.finish::<I::IntoIter>()
+    /// Like [`DynStruct::new`], but allocates the value in `alloc` instead of the global
+    /// allocator.
+    #[cfg(feature = "allocator_api")]
+    #[inline]
+    pub fn new_in<I, A>(header: Header, tail: I, alloc: A) -> Box<Self, A>
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+        A: core::alloc::Allocator,
+    {
+        __private::unwrap(Self::try_new_in(header, tail, alloc))
     }
 
-    /// Allocate a new `DynStruct` on the heap. Uses a slice instead of an iterator (as
-    /// [`DynStruct::new`]). This will probably be faster in most cases (provided the slice is
-    /// readily available).
-    pub fn from_slice(header: Header, tail: &[Tail]) -> Box<Self>
+    /// Like [`DynStruct::try_new`], but allocates the value in `alloc` instead of the global
+    /// allocator.
+    #[cfg(feature = "allocator_api")]
+    #[inline]
+    pub fn try_new_in<I, A>(header: Header, tail: I, alloc: A) -> Result<Box<Self, A>, TryNewError>
     where
-        Tail: Copy,
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+        A: core::alloc::Allocator,
     {
-        let mut writer = BoxWriter::<Header, Tail>::new(header, tail.len());
         unsafe {
-            writer.write_slice(tail);
+            let ptr =
+                BoxWriter::from_iter(header, Layout::new::<Header>(), tail, InAllocator(&alloc))?;
+            Ok(Box::from_raw_in(ptr as *mut Self, alloc))
         }
-        writer.finish::<()>()
     }
 
+    /// Like [`DynStruct::new`], but aligns the tail, and thus the allocation, to at least
+    /// `min_align` bytes, for example for SIMD loads. The header is moved back accordingly.
+    ///
+    /// A `Box` would free the value with its natural alignment, so the value is returned in an
+    /// [`AlignedDynStruct`], which frees it with the one it was allocated with.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min_align` is not a power of two.
     #[inline]
-    fn align() -> usize {
-        usize::max(align_of::<Header>(), align_of::<Tail>())
+    pub fn new_aligned<I>(
+        header: Header,
+        tail: I,
+        min_align: usize,
+    ) -> AlignedDynStruct<Header, Tail>
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        __private::unwrap(Self::try_new_aligned(header, tail, min_align))
     }
 
-    /// Returns the total size of the `DynStruct<Header, Tail>` structure, provided the length of the
-    /// tail.
+    /// Like [`DynStruct::new_aligned`], but returns an error instead of panicking or aborting if
+    /// the value cannot be allocated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min_align` is not a power of two.
     #[inline]
-    fn size(len: usize) -> usize {
-        let header = size_of::<Header>();
-
-        let tail_align = align_of::<Tail>();
-        let padding = if header % tail_align == 0 {
-            0
-        } else {
-            tail_align - header % tail_align
-        };
+    pub fn try_new_aligned<I>(
+        header: Header,
+        tail: I,
+        min_align: usize,
+    ) -> Result<AlignedDynStruct<Header, Tail>, TryNewError>
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        AlignedDynStruct::try_new(header, tail, min_align)
+    }
 
-        let tail = size_of::<Tail>() * len;
+    /// Like [`DynStruct::new`], but builds the tail with a `rayon` parallel iterator, whose items are
+    /// written straight into the allocation by the workers which produce them.
+    ///
+    /// If a worker panics, the elements which have been written are dropped along with the header,
+    /// and the memory is freed, before the panic is passed on.
+    #[cfg(feature = "rayon")]
+    #[inline]
+    pub fn from_par_iter<I>(header: Header, tail: I) -> Box<Self>
+    where
+        I: rayon::iter::IndexedParallelIterator<Item = Tail>,
+        Tail: Send,
+    {
+        __private::unwrap(Self::try_from_par_iter(header, tail))
+    }
 
-        header + padding + tail
+    /// Like [`DynStruct::from_par_iter`], but returns an error instead of panicking or aborting if
+    /// the value cannot be allocated.
+    #[cfg(feature = "rayon")]
+    pub fn try_from_par_iter<I>(header: Header, tail: I) -> Result<Box<Self>, TryNewError>
+    where
+        I: rayon::iter::IndexedParallelIterator<Item = Tail>,
+        Tail: Send,
+    {
+        let ptr = par::from_par_iter(header, tail)?;
+        Ok(unsafe { Box::from_raw(ptr as *mut Self) })
     }
-}
 
-struct BoxWriter<Header, Tail> {
-    raw: *mut DynStruct<Header, MaybeUninit<Tail>>,
-    written: usize,
-}
+    /// Like [`DynStruct::new`], but allocates the value in the arena `bump`, and returns a
+    /// reference which lives as long as the arena.
+    ///
+    /// The value is never dropped: like [`Bump::alloc`](bumpalo::Bump::alloc), the arena frees its
+    /// memory without running destructors, so anything owned by the header or the tail is leaked.
+    #[cfg(feature = "bumpalo")]
+    #[inline]
+    pub fn new_in_bump<I>(header: Header, tail: I, bump: &bumpalo::Bump) -> &mut Self
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        __private::unwrap(Self::try_new_in_bump(header, tail, bump))
+    }
 
-impl<Header, Tail> BoxWriter<Header, Tail> {
+    /// Like [`DynStruct::new_in_bump`], but returns an error instead of panicking or aborting if
+    /// the value cannot be allocated.
+    #[cfg(feature = "bumpalo")]
     #[inline]
-    pub fn new(header: Header, len: usize) -> Self {
-        let total_size = DynStruct::<Header, Tail>::size(len);
-        let align = DynStruct::<Header, Tail>::align();
-
-        let fat_ptr = if total_size == 0 {
-            // We cannot allocate a region of 0 bytes, thus we use `Box` to create a dangling
-            // pointer with the correct length.
-            let slice: Box<[()]> = Box::from(slice_with_len(len));
-
-            // This is a zero-size-type, so moving it into the allocation has no effect. Instead we
-            // just create a new one "out of thin air" by casting the allocation to the appropriate
-            // header. We need this call to `forget` to make sure the destructor of the original
-            // header does not run.
-            std::mem::forget(header);
-
-            Box::into_raw(slice) as *mut DynStruct<Header, MaybeUninit<Tail>>
-        } else {
-            unsafe {
-                // Allocate enough memory to store both the header and tail
-                let layout = std::alloc::Layout::from_size_align(total_size, align).unwrap();
-                let raw = std::alloc::alloc(layout);
-                if raw.is_null() {
-                    std::alloc::handle_alloc_error(layout)
-                }
-
-                // Initialize the header field
-                raw.cast::<Header>().write(header);
-
-                // use a slice as an intermediary to get a fat pointer containing the correct
-                // length of the tail
-                let slice = std::slice::from_raw_parts_mut(raw as *mut (), len);
-                slice as *mut [()] as *mut DynStruct<Header, MaybeUninit<Tail>>
-            }
-        };
+    // The reference is to new memory in the arena, just like the one from `Bump::alloc`.
+    #[allow(clippy::mut_from_ref)]
+    pub fn try_new_in_bump<I>(
+        header: Header,
+        tail: I,
+        bump: &bumpalo::Bump,
+    ) -> Result<&mut Self, TryNewError>
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        unsafe {
+            let ptr = BoxWriter::from_iter(header, Layout::new::<Header>(), tail, InBump(bump))?;
+            Ok(&mut *InBump::untrack(ptr as *mut Self))
+        }
+    }
 
-        BoxWriter {
-            raw: fat_ptr,
-            written: 0,
+    /// Allocate a new `DynStruct` on the heap. Uses a slice instead of an iterator (as
+    /// [`DynStruct::new`]). This will probably be faster in most cases (provided the slice is
+    /// readily available).
+    pub fn from_slice(header: Header, tail: &[Tail]) -> Box<Self>
+    where
+        Tail: Copy,
+    {
+        unsafe {
+            let ptr = __private::unwrap(BoxWriter::from_slice(
+                header,
+                Layout::new::<Header>(),
+                tail,
+                Global,
+            ));
+            Box::from_raw(ptr as *mut Self)
         }
     }
 
-    #[inline]
-    fn write_tail<I>(&mut self, value: Tail) {
-        let written = self.written;
-        let tail = &mut self.as_mut().tail;
+    /// Allocate a new `DynStruct` on the heap, moving the elements of the tail out of a `Vec`.
+    pub fn from_vec(header: Header, tail: Vec<Tail>) -> Box<Self> {
+        unsafe {
+            let ptr = __private::unwrap(BoxWriter::from_vec(
+                header,
+                Layout::new::<Header>(),
+                tail,
+                Global,
+            ));
+            Box::from_raw(ptr as *mut Self)
+        }
+    }
 
-        assert!(
-            written < tail.len(),
-            "got more items than expected. Probable bug in `ExactSizeIterator` for `{}`?",
-            std::any::type_name::<I>(),
-        );
+    /// Moves the header and the elements of the tail out of the allocation.
+    pub fn into_parts(this: Box<Self>) -> (Header, Vec<Tail>) {
+        let this = ManuallyDrop::new(this);
+        unsafe {
+            let header = core::ptr::read(&this.header);
+            let tail = __private::read_vec(&this.tail);
+            __private::free(ManuallyDrop::into_inner(this));
+            (header, tail)
+        }
+    }
 
-        tail[written].write(value);
-        self.written += 1;
+    /// Appends clones of `extra` to the tail, reallocating the value in place if possible.
+    pub fn with_extended_tail(this: Box<Self>, extra: &[Tail]) -> Box<Self>
+    where
+        Tail: Clone,
+    {
+        let len = this.tail.len();
+        let raw = Box::into_raw(this).cast::<u8>();
+        unsafe {
+            let ptr = extend_tail::<Header, Tail, _>(
+                raw,
+                Layout::new::<Header>(),
+                len,
+                extra.iter().cloned(),
+            );
+            Box::from_raw(ptr as *mut Self)
+        }
     }
 
-    #[inline]
-    fn finish<I>(mut self) -> Box<DynStruct<Header, Tail>> {
-        let len = self.as_mut().tail.len();
-        assert_eq!(
-            self.written,
-            len,
-            "got fewer items than expected. Probable bug in `ExactSizeIterator` for `{}`?",
-            std::any::type_name::<I>(),
-        );
+    /// Appends the items of `extra` to the tail, reallocating the value in place if possible. Like
+    /// [`DynStruct::new`], this relies on the length reported by the iterator.
+    pub fn with_extended_tail_iter<I>(this: Box<Self>, extra: I) -> Box<Self>
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let len = this.tail.len();
+        let raw = Box::into_raw(this).cast::<u8>();
+        unsafe {
+            let ptr = extend_tail::<Header, Tail, _>(
+                raw,
+                Layout::new::<Header>(),
+                len,
+                extra.into_iter(),
+            );
+            Box::from_raw(ptr as *mut Self)
+        }
+    }
 
-        // This cast from `DynStruct<Header, MaybeUninit<Tail>>` is sound since all tail elements
-        // now have been initialized
-        let init = self.raw as *mut DynStruct<Header, Tail>;
+    /// Shortens the tail to `len` elements, dropping the rest and shrinking the allocation. Like
+    /// [`Vec::truncate`], this does nothing if the tail is not longer than `len`.
+    pub fn with_truncated_tail(this: Box<Self>, len: usize) -> Box<Self> {
+        let old_len = this.tail.len();
+        let raw = Box::into_raw(this).cast::<u8>();
+        unsafe {
+            let ptr = truncate_tail::<Header, Tail>(raw, Layout::new::<Header>(), old_len, len);
+            Box::from_raw(ptr as *mut Self)
+        }
+    }
 
-        // once we have finished constructing the value, don't run the destructor
-        std::mem::forget(self);
+    /// Allocate a new `DynStruct` on the heap, with `len` elements in the tail that are all zero.
+    /// The memory is zeroed by the allocator, which is often cheaper than writing each element:
+    /// large allocations usually come straight from the operating system, whose fresh pages are
+    /// zero already.
+    pub fn new_zeroed(header: Header, len: usize) -> Box<Self>
+    where
+        Tail: Zeroable,
+    {
+        unsafe {
+            let ptr = __private::unwrap(BoxWriter::<Header, Tail>::zeroed(
+                header,
+                Layout::new::<Header>(),
+                len,
+                Global,
+            ));
+            Box::from_raw(ptr as *mut Self)
+        }
+    }
+
+    /// Consumes the `Box`, returning a thin pointer to the start of the allocation and the length
+    /// of the tail.
+    ///
+    /// The value is not dropped. Use [`DynStruct::from_raw_parts`] to turn the parts back into a
+    /// `Box`.
+    pub fn into_raw_parts(this: Box<Self>) -> (*mut (), usize) {
+        let len = this.tail.len();
+        let raw = Box::into_raw(this);
+        (raw.cast::<()>(), len)
+    }
+
+    /// Reassembles a `Box` from the parts returned by [`DynStruct::into_raw_parts`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` and `len` must have been returned by a call to [`DynStruct::into_raw_parts`] for the
+    /// same `Header` and `Tail`, and the parts may only be turned back into a `Box` once.
+    pub unsafe fn from_raw_parts(ptr: *mut (), len: usize) -> Box<Self> {
+        tracking::check(ptr.cast::<u8>(), || {
+            __private::unwrap(layout::for_len::<Tail>(Layout::new::<Header>(), len)).0
+        });
+        let slice = core::ptr::slice_from_raw_parts_mut(ptr, len);
+        Box::from_raw(slice as *mut Self)
+    }
 
-        unsafe { Box::from_raw(init) }
+    /// Converts the value into a `T` with the same layout, without reallocating it. `T` may be
+    /// another `DynStruct`, or a struct with a slice tail that derives `DynStruct`.
+    #[inline]
+    pub fn into_repr<T>(this: Box<Self>) -> Box<T>
+    where
+        T: ?Sized + __private::RawParts,
+        Self: SameRepr<T>,
+    {
+        let len = this.tail.len();
+        tracking::check((&*this as *const Self).cast::<u8>(), || {
+            __private::unwrap(T::layout_for_len(len))
+        });
+        let (ptr, len) = Self::into_raw_parts(this);
+        unsafe { Box::from_raw(T::from_raw(ptr, len)) }
     }
 
-    fn as_mut(&mut self) -> &mut DynStruct<Header, MaybeUninit<Tail>> {
-        unsafe { &mut *self.raw }
+    /// Converts a `T` with the same layout into a `DynStruct`, without reallocating it. This is the
+    /// reverse of [`DynStruct::into_repr`].
+    #[inline]
+    pub fn from_repr<T>(value: Box<T>) -> Box<Self>
+    where
+        T: ?Sized + __private::RawParts,
+        Self: SameRepr<T>,
+    {
+        // Check before giving up the `Box`, so that the value is still dropped on a mismatch.
+        let (ptr, len) = T::into_raw(&*value as *const T as *mut T);
+        tracking::check(ptr.cast::<u8>(), || {
+            __private::unwrap(layout::for_len::<Tail>(Layout::new::<Header>(), len)).0
+        });
+        let (ptr, len) = T::into_raw(Box::into_raw(value));
+        unsafe { Self::from_raw_parts(ptr, len) }
     }
 
+    /// Converts the value into a `T` like [`DynStruct::into_repr`], after checking that `T` puts
+    /// the tail at the same offset, has the same tail elements, and has the same size and alignment
+    /// with the current length of the tail. If any of these differ, the value is dropped and the
+    /// layouts of both types are returned.
+    ///
     /// # Safety
     ///
-    /// Assumes that this writer was created with a capacity for `values.len()`
-    unsafe fn write_slice(&mut self, values: &[Tail])
+    /// The checks cannot see the fields of `Header`: they must be at the same offsets in `T`, and
+    /// every value of `Self` must be a valid value of `T`.
+    pub unsafe fn into_repr_checked<T>(this: Box<Self>) -> Result<Box<T>, LayoutMismatch>
     where
-        Tail: Copy,
+        T: ?Sized + __private::RawParts,
     {
-        self.as_mut()
-            .tail
-            .as_mut_ptr()
-            .copy_from_nonoverlapping(values.as_ptr().cast(), values.len());
-        self.written = values.len();
+        use __private::RawParts;
+
+        let len = this.tail.len();
+        let mismatch = LayoutMismatch {
+            len,
+            source: Layout::for_value::<Self>(&this),
+            source_tail_offset: <Self as RawParts>::TAIL_OFFSET,
+            source_element: <Self as RawParts>::ELEMENT_LAYOUT,
+            target: T::layout_for_len(len).ok(),
+            target_tail_offset: T::TAIL_OFFSET,
+            target_element: T::ELEMENT_LAYOUT,
+        };
+        if mismatch.target != Some(mismatch.source)
+            || mismatch.target_tail_offset != mismatch.source_tail_offset
+            || mismatch.target_element != mismatch.source_element
+        {
+            return Err(mismatch);
+        }
+
+        let (ptr, len) = Self::into_raw_parts(this);
+        Ok(Box::from_raw(T::from_raw(ptr, len)))
     }
-}
 
-impl<Header, Tail> Drop for BoxWriter<Header, Tail> {
-    fn drop(&mut self) {
-        unsafe {
-            // SAFETY: the header field is always initialized
-            std::ptr::drop_in_place(self.raw.cast::<Header>());
+    /// The offset of the tail from the start of the value, which is the same for every length of
+    /// the tail.
+    pub const TAIL_OFFSET: usize = layout::tail_offset::<Tail>(size_of::<Header>());
 
-            let initialized = self.written;
-            let tail = &mut self.as_mut().tail;
-            for i in 0..initialized {
-                tail[i].as_mut_ptr().drop_in_place();
-            }
+    /// The alignment of the value, which is the same for every length of the tail.
+    pub const ALIGN: usize = Self::layout_for_len(0).align();
+
+    /// Returns the size of a value with `len` elements in its tail, including its trailing padding.
+    /// Together with [`DynStruct::TAIL_OFFSET`], this allows placing values in memory before any of
+    /// them exist.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the value would exceed `isize::MAX`.
+    pub const fn size_for_len(len: usize) -> usize {
+        Self::layout_for_len(len).size()
+    }
+
+    /// Returns the layout of a value with `len` elements in its tail, as it is allocated by the
+    /// constructors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size of the value would exceed `isize::MAX`.
+    pub const fn layout_for_len(len: usize) -> Layout {
+        match layout::for_len::<Tail>(Layout::new::<Header>(), len) {
+            Ok((layout, _)) => layout,
+            Err(_) => panic!("size of `DynStruct` exceeds `isize::MAX`"),
         }
     }
-}
 
-impl<T> DynStruct<T, T> {
-    /// Get a `DynStruct` as a view over a slice (this does not allocate).
-    pub fn slice_view(values: &[T]) -> &Self {
-        assert!(
-            !values.is_empty(),
-            "attempted to create `{}` from empty slice (needs at least 1 element)",
-            std::any::type_name::<Self>()
-        );
-        let slice = &values[..values.len() - 1];
-        unsafe { &*(slice as *const [T] as *const Self) }
+    /// Returns the number of elements in the tail.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.tail.len()
+    }
+
+    /// Returns `true` if the tail has no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.tail.is_empty()
     }
 }
 
-impl<T, const N: usize> DynStruct<[T; N], T> {
-    /// Get a `DynStruct` as a view over a slice (this does not allocate).
-    pub fn slice_view(values: &[T]) -> &Self {
-        assert!(
-            values.len() >= N,
-            "attempted to create `{}` from empty slice (needs at least {} elements)",
-            std::any::type_name::<Self>(),
-            N,
-        );
-        let slice = &values[..values.len() - N];
-        unsafe { &*(slice as *const [T] as *const Self) }
+impl<Header: Pod, Tail: Pod> DynStruct<Header, Tail> {
+    /// Sets the padding between the header and the tail, and after the tail, to zero. Neither the
+    /// header nor the elements of the tail contain padding, so afterwards every byte of the value is
+    /// initialized, and equal values consist of equal bytes.
+    ///
+    /// New padding is left uninitialized when the tail is resized, so this has to be called again
+    /// afterwards.
+    pub fn zero_padding(&mut self) {
+        let size = core::mem::size_of_val(self);
+        let raw = (self as *mut Self).cast::<u8>();
+        for gap in layout::padding::<Tail>(core::mem::size_of::<Header>(), self.tail.len(), size) {
+            unsafe { raw.add(gap.start).write_bytes(0, gap.len()) };
+        }
+    }
+
+    /// Returns the bytes of the entire value, including its padding, which is set to zero first
+    /// with [`DynStruct::zero_padding`]. This gives a canonical representation, which can be hashed or
+    /// compared byte by byte.
+    pub fn as_bytes(&mut self) -> &[u8] {
+        self.zero_padding();
+        let len = core::mem::size_of_val(self);
+        unsafe { core::slice::from_raw_parts((self as *const Self).cast::<u8>(), len) }
     }
 }
 
-fn slice_with_len(len: usize) -> &'static [()] {
-    static ARBITRARY: [(); usize::MAX] = [(); usize::MAX];
-    &ARBITRARY[..len]
+/// Types for which a value with all bytes set to zero is valid, such as integers.
+///
+/// # Safety
+///
+/// A value consisting only of zero bytes must be a valid value of the type.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be zero-initialized",
+    note = "implement `dyn_struct::Zeroable` for `{Self}` if all zero bytes are a valid value"
+)]
+pub unsafe trait Zeroable {}
+
+macro_rules! zeroable {
+    ($($ty:ty),* $(,)?) => {
+        $(unsafe impl Zeroable for $ty {})*
+    };
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+zeroable!(
+    (),
+    bool,
+    char,
+    f32,
+    f64,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+);
 
-    #[test]
-    fn mixed_types() {
-        let mixed = DynStruct::new((true, 32u16), [1u64, 2, 3, 4]);
-        assert_eq!(mixed.header, (true, 32u16));
-        assert_eq!(&mixed.tail, &[1, 2, 3, 4]);
+unsafe impl<T: Zeroable, const N: usize> Zeroable for [T; N] {}
+unsafe impl<T> Zeroable for MaybeUninit<T> {}
+unsafe impl<T: Zeroable> Zeroable for core::num::Wrapping<T> {}
+unsafe impl<T> Zeroable for *const T {}
+unsafe impl<T> Zeroable for *mut T {}
+
+/// Asserts that a `DynStruct` is laid out exactly like `T`, which makes [`DynStruct::into_repr`]
+/// and [`DynStruct::from_repr`] available to convert between the two.
+///
+/// # Safety
+///
+/// For every length of the tail, `T` must have the same size and alignment, the fields of `Header`
+/// at the same offsets, and the tail of `T` must have the same length at the same offset. Every
+/// value of either type must also be a valid value of the other.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not known to be laid out like `{T}`",
+    note = "implement `dyn_struct::SameRepr<{T}>` for `{Self}` if their layouts are identical"
+)]
+pub unsafe trait SameRepr<T: ?Sized> {}
+
+/// Plain old data: types without padding for which any bytes make a valid value, such as integers.
+///
+/// # Safety
+///
+/// The type may not contain padding, and any initialized bytes must be a valid value of the type.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not plain old data",
+    note = "implement `dyn_struct::Pod` for `{Self}` if it has no padding and any bytes are a valid value"
+)]
+pub unsafe trait Pod: Zeroable {}
+
+macro_rules! pod {
+    ($($ty:ty),* $(,)?) => {
+        $(unsafe impl Pod for $ty {})*
+    };
+}
+
+pod!(f32, f64, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+unsafe impl<T: Pod> Pod for core::num::Wrapping<T> {}
+
+/// The error returned when a `DynStruct` cannot be allocated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryNewError {
+    /// The size of the value would exceed `isize::MAX`.
+    CapacityOverflow,
+    /// The allocator failed to allocate memory with the given layout.
+    AllocError { layout: Layout },
+}
+
+impl fmt::Display for TryNewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryNewError::CapacityOverflow => {
+                f.write_str("size of `DynStruct` exceeds `isize::MAX`")
+            }
+            TryNewError::AllocError { layout } => write!(
+                f,
+                "failed to allocate {} bytes with an alignment of {}",
+                layout.size(),
+                layout.align()
+            ),
+        }
+    }
+}
+
+impl core::error::Error for TryNewError {}
+
+/// The error returned when a builder generated by `#[dyn_struct(builder)]` is built before all
+/// fields were set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingField {
+    /// The name of the field that was not set.
+    pub field: &'static str,
+}
+
+impl fmt::Display for MissingField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "field `{}` was not set", self.field)
+    }
+}
+
+impl core::error::Error for MissingField {}
+
+/// The error returned by [`DynStruct::into_repr_checked`] when the target type is laid out
+/// differently from the value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutMismatch {
+    /// The number of elements in the tail of the value.
+    pub len: usize,
+    /// The layout of the value.
+    pub source: Layout,
+    /// The offset of the tail of the value.
+    pub source_tail_offset: usize,
+    /// The layout of one element in the tail of the value.
+    pub source_element: Layout,
+    /// The layout of the target type with `len` elements in its tail, or `None` if its size would
+    /// exceed `isize::MAX`.
+    pub target: Option<Layout>,
+    /// The offset of the tail of the target type.
+    pub target_tail_offset: usize,
+    /// The layout of one element in the tail of the target type.
+    pub target_element: Layout,
+}
+
+impl fmt::Display for LayoutMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the types are laid out differently with {} elements in the tail: the source has ",
+            self.len
+        )?;
+        write!(
+            f,
+            "size {} and alignment {}",
+            self.source.size(),
+            self.source.align()
+        )?;
+        write!(
+            f,
+            ", its tail at offset {} with elements of size {} and alignment {}; the target has ",
+            self.source_tail_offset,
+            self.source_element.size(),
+            self.source_element.align(),
+        )?;
+        match self.target {
+            Some(target) => write!(f, "size {} and alignment {}", target.size(), target.align())?,
+            None => write!(f, "a size exceeding `isize::MAX`")?,
+        }
+        write!(
+            f,
+            ", its tail at offset {} with elements of size {} and alignment {}",
+            self.target_tail_offset,
+            self.target_element.size(),
+            self.target_element.align(),
+        )
+    }
+}
+
+impl core::error::Error for LayoutMismatch {}
+
+/// Overwrites `len` bytes at `raw` with `0xDD` in debug builds with the `debug-poison` feature.
+/// This is called on memory whose values have been moved out, just before it is freed, so that
+/// stale pointers into it read an obvious pattern instead of values which still look alive.
+///
+/// # Safety
+///
+/// `raw` must be valid for writing `len` bytes, and nothing may be read from them afterwards.
+#[inline]
+unsafe fn poison(raw: *mut u8, len: usize) {
+    #[cfg(all(feature = "debug-poison", debug_assertions))]
+    raw.write_bytes(0xDD, len);
+    #[cfg(not(all(feature = "debug-poison", debug_assertions)))]
+    let _ = (raw, len);
+}
+
+/// Copies the value at `value` into a new `Box`, for owners which keep their values elsewhere.
+///
+/// # Safety
+///
+/// `value` must point to a valid value, which belongs to the `Box` afterwards: the caller must not
+/// drop it again, only free its memory.
+unsafe fn copy_into_box<Header, Tail>(
+    value: *const DynStruct<Header, Tail>,
+) -> Box<DynStruct<Header, Tail>> {
+    let len = (*value).len();
+    let layout = Layout::for_value(&*value);
+    let raw = __private::unwrap(allocate(layout, false));
+    raw.copy_from_nonoverlapping(value.cast::<u8>(), layout.size());
+    tracking::record(raw, layout);
+    let ptr = core::ptr::slice_from_raw_parts_mut(raw.cast::<()>(), len);
+    Box::from_raw(ptr as *mut DynStruct<Header, Tail>)
+}
+
+/// Appends the items of `extra` to the tail of a value with `len` tail elements, reallocating it.
+/// The allocator keeps the value in place if it can, and otherwise moves the memory for us. Either
+/// way, no element is moved twice.
+///
+/// If `extra` panics, or yields another number of items than its length, the writer drops the
+/// whole value.
+///
+/// # Safety
+///
+/// `raw` must point to a value owned by the caller, which was allocated by the global allocator
+/// like in [`BoxWriter::new`] with the same `header_layout` and `len`.
+unsafe fn extend_tail<Header, Tail, I>(
+    raw: *mut u8,
+    header_layout: Layout,
+    len: usize,
+    extra: I,
+) -> *mut [()]
+where
+    I: ExactSizeIterator<Item = Tail>,
+{
+    let mut writer = BoxWriter::<Header, Tail>::from_raw(raw, header_layout, len);
+    let new_len = len
+        .checked_add(extra.len())
+        .ok_or(TryNewError::CapacityOverflow);
+    __private::unwrap(new_len.and_then(|new_len| writer.resize(header_layout, new_len)));
+
+    for value in extra {
+        writer.write_tail::<I>(value);
+    }
+    writer.finish::<I>()
+}
+
+/// Drops the tail elements of a value past `new_len`, and shrinks its memory. Does nothing if
+/// the tail is not longer than `new_len`.
+///
+/// # Safety
+///
+/// See [`extend_tail`].
+unsafe fn truncate_tail<Header, Tail>(
+    raw: *mut u8,
+    header_layout: Layout,
+    len: usize,
+    new_len: usize,
+) -> *mut [()] {
+    let mut writer = BoxWriter::<Header, Tail>::from_raw(raw, header_layout, len);
+    if new_len < len {
+        // If dropping an element panics, the writer drops the rest of the value.
+        writer.written = new_len;
+        let dropped =
+            core::ptr::slice_from_raw_parts_mut(writer.tail().add(new_len), len - new_len);
+        core::ptr::drop_in_place(dropped);
+        // Shrinking the allocation may only fail if the allocator does not support it.
+        __private::unwrap(writer.resize(header_layout, new_len));
+    }
+    writer.finish::<()>()
+}
+
+/// Allocates memory with the given layout, returning a dangling pointer if its size is zero. If
+/// `zeroed` is `true`, the memory is set to zero.
+fn allocate(layout: Layout, zeroed: bool) -> Result<*mut u8, TryNewError> {
+    if layout.size() == 0 {
+        // We cannot allocate a region of 0 bytes, thus we use a dangling pointer instead. It
+        // still has to be aligned, with the alignment coming from the tail if the header is
+        // empty.
+        Ok(core::ptr::without_provenance_mut(layout.align()))
+    } else {
+        let raw = unsafe {
+            if zeroed {
+                alloc::alloc::alloc_zeroed(layout)
+            } else {
+                alloc::alloc::alloc(layout)
+            }
+        };
+        if raw.is_null() {
+            Err(TryNewError::AllocError { layout })
+        } else {
+            Ok(raw)
+        }
+    }
+}
+
+/// The panic of [`BoxWriter::finish`] when an iterator returned fewer items than its length, kept
+/// out of line since it is the same for every iterator.
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn wrong_len(written: usize, len: usize, iter: &str) -> ! {
+    panic!(
+        "got fewer items than expected ({} instead of {}). Probable bug in `ExactSizeIterator` for \
+         `{}`?",
+        written, len, iter,
+    );
+}
+
+/// Where a [`BoxWriter`] gets its memory from.
+///
+/// # Safety
+///
+/// The memory returned by `allocate` must fit the layout, and be accepted by `deallocate`.
+unsafe trait RawAlloc {
+    /// Like [`allocate`].
+    fn allocate(&self, layout: Layout, zeroed: bool) -> Result<*mut u8, TryNewError>;
+
+    /// Allocates a value with the fields in `header` followed by `len` elements with the layout
+    /// `element`, and returns it with the layout of the value and the offset of the tail.
+    #[inline(never)]
+    fn allocate_value(
+        &self,
+        header: Layout,
+        element: Layout,
+        len: usize,
+        zeroed: bool,
+    ) -> Result<(*mut u8, Layout, usize), TryNewError> {
+        let (layout, offset) = layout::for_element(header, element, len)?;
+        Ok((self.allocate(layout, zeroed)?, layout, offset))
+    }
+
+    /// # Safety
+    ///
+    /// `raw` must have been returned by `allocate` for the same layout. Zero-sized layouts are passed
+    /// as well, for allocators which need memory even for those.
+    unsafe fn deallocate(&self, raw: *mut u8, layout: Layout);
+}
+
+/// The global allocator, which a `Box` uses by default.
+struct Global;
+
+unsafe impl RawAlloc for Global {
+    fn allocate(&self, layout: Layout, zeroed: bool) -> Result<*mut u8, TryNewError> {
+        allocate(layout, zeroed)
+    }
+
+    unsafe fn deallocate(&self, raw: *mut u8, layout: Layout) {
+        if layout.size() != 0 {
+            alloc::alloc::dealloc(raw, layout)
+        }
+    }
+}
+
+/// A `bumpalo` arena, which frees all of its memory at once.
+#[cfg(feature = "bumpalo")]
+struct InBump<'a>(&'a bumpalo::Bump);
+
+#[cfg(feature = "bumpalo")]
+impl InBump<'_> {
+    /// Forgets the layout of a value in an arena: it is never freed through a `Box`, and its
+    /// address will be reused without going through this crate.
+    fn untrack<T: ?Sized>(ptr: *mut T) -> *mut T {
+        tracking::forget(ptr.cast::<u8>());
+        ptr
+    }
+}
+
+#[cfg(feature = "bumpalo")]
+unsafe impl RawAlloc for InBump<'_> {
+    fn allocate(&self, layout: Layout, zeroed: bool) -> Result<*mut u8, TryNewError> {
+        if layout.size() == 0 {
+            return Ok(core::ptr::without_provenance_mut(layout.align()));
+        }
+        let raw = self
+            .0
+            .try_alloc_layout(layout)
+            .map_err(|_| TryNewError::AllocError { layout })?
+            .as_ptr();
+        if zeroed {
+            unsafe { raw.write_bytes(0, layout.size()) };
+        }
+        Ok(raw)
+    }
+
+    unsafe fn deallocate(&self, _: *mut u8, _: Layout) {
+        // The memory is reclaimed with the rest of the arena. A failed construction only wastes it
+        // until then.
+    }
+}
+
+/// An allocator of the unstable `allocator_api`.
+#[cfg(feature = "allocator_api")]
+struct InAllocator<'a, A>(&'a A);
+
+#[cfg(feature = "allocator_api")]
+unsafe impl<A: core::alloc::Allocator> RawAlloc for InAllocator<'_, A> {
+    fn allocate(&self, layout: Layout, zeroed: bool) -> Result<*mut u8, TryNewError> {
+        if layout.size() == 0 {
+            // Just like `Box`, zero-sized values are never allocated.
+            return Ok(core::ptr::without_provenance_mut(layout.align()));
+        }
+        let result = if zeroed {
+            self.0.allocate_zeroed(layout)
+        } else {
+            self.0.allocate(layout)
+        };
+        result
+            .map(|ptr| ptr.cast::<u8>().as_ptr())
+            .map_err(|_| TryNewError::AllocError { layout })
+    }
+
+    unsafe fn deallocate(&self, raw: *mut u8, layout: Layout) {
+        if layout.size() != 0 {
+            self.0
+                .deallocate(core::ptr::NonNull::new_unchecked(raw), layout)
+        }
+    }
+}
+
+/// Writes a value into a new allocation, one tail element at a time.
+///
+/// This is the guard that every constructor producing elements one by one goes through. If it is
+/// dropped before [`BoxWriter::finish`], for example because an iterator or `Clone` panicked, the
+/// header and the elements written so far are dropped and the memory is freed.
+struct BoxWriter<Header, Tail, A: RawAlloc = Global> {
+    /// Thin pointer to the start of the allocation, where the header is stored.
+    raw: *mut u8,
+    /// Layout of the entire allocation, including trailing padding.
+    layout: Layout,
+    /// Offset of the first tail element from the start of the allocation.
+    offset: usize,
+    len: usize,
+    written: usize,
+    /// The allocator the memory came from.
+    alloc: A,
+    _marker: PhantomData<(Header, Tail)>,
+}
+
+impl<Header, Tail, A: RawAlloc> BoxWriter<Header, Tail, A> {
+    /// Allocates memory for `header` followed by `len` tail elements from `alloc`, and writes the
+    /// header. If `zeroed` is `true`, the memory is set to zero before the header is written.
+    ///
+    /// # Safety
+    ///
+    /// `header_layout` must have the alignment of `Header`, and its size must be the offset just
+    /// past the last field of `Header`, such that rounding it up to the alignment gives
+    /// `size_of::<Header>()`. The tail starts at the first suitably aligned offset after it.
+    #[inline]
+    unsafe fn new(
+        header: Header,
+        header_layout: Layout,
+        len: usize,
+        zeroed: bool,
+        alloc: A,
+    ) -> Result<Self, TryNewError> {
+        // Allocate enough memory to store both the header and tail. This does not depend on the
+        // types, only on their layouts, so it is compiled once for each allocator rather than for
+        // every `Header` and `Tail`.
+        let (raw, layout, offset) =
+            alloc.allocate_value(header_layout, Layout::new::<Tail>(), len, zeroed)?;
+
+        // Initialize the header field. The trailing padding of the header may overlap the start of
+        // the tail, so this has to happen before any tail element is written.
+        raw.cast::<Header>().write(header);
+
+        Ok(BoxWriter {
+            raw,
+            layout,
+            offset,
+            len,
+            written: 0,
+            alloc,
+            _marker: PhantomData,
+        })
+    }
+
+    /// # Safety
+    ///
+    /// See [`BoxWriter::new`].
+    #[inline]
+    unsafe fn from_iter<I>(
+        header: Header,
+        header_layout: Layout,
+        tail: I,
+        alloc: A,
+    ) -> Result<*mut [()], TryNewError>
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let tail = tail.into_iter();
+
+        let mut writer =
+            BoxWriter::<Header, Tail, A>::new(header, header_layout, tail.len(), false, alloc)?;
+
+        for value in tail {
+            writer.write_tail::<I::IntoIter>(value);
+        }
+
+        Ok(writer.finish::<I::IntoIter>())
+    }
+
+    /// # Safety
+    ///
+    /// See [`BoxWriter::new`].
+    #[inline]
+    unsafe fn from_slice(
+        header: Header,
+        header_layout: Layout,
+        tail: &[Tail],
+        alloc: A,
+    ) -> Result<*mut [()], TryNewError>
+    where
+        Tail: Copy,
+    {
+        let mut writer =
+            BoxWriter::<Header, Tail, A>::new(header, header_layout, tail.len(), false, alloc)?;
+        writer.write_slice(tail);
+        Ok(writer.finish::<()>())
+    }
+
+    /// Like [`BoxWriter::from_slice`], but moves the elements out of a `Vec`.
+    ///
+    /// # Safety
+    ///
+    /// See [`BoxWriter::new`].
+    unsafe fn from_vec(
+        header: Header,
+        header_layout: Layout,
+        mut tail: Vec<Tail>,
+        alloc: A,
+    ) -> Result<*mut [()], TryNewError> {
+        let mut writer =
+            BoxWriter::<Header, Tail, A>::new(header, header_layout, tail.len(), false, alloc)?;
+
+        // The elements are now owned by the writer, the vector only frees its buffer.
+        writer.copy_tail(tail.as_ptr(), tail.len());
+        poison(
+            tail.as_mut_ptr().cast::<u8>(),
+            core::mem::size_of_val(&tail[..]),
+        );
+        tail.set_len(0);
+
+        Ok(writer.finish::<()>())
+    }
+
+    /// Like [`BoxWriter::from_slice`], but every tail element is zero.
+    ///
+    /// # Safety
+    ///
+    /// See [`BoxWriter::new`].
+    unsafe fn zeroed(
+        header: Header,
+        header_layout: Layout,
+        len: usize,
+        alloc: A,
+    ) -> Result<*mut [()], TryNewError>
+    where
+        Tail: Zeroable,
+    {
+        let mut writer =
+            BoxWriter::<Header, Tail, A>::new(header, header_layout, len, true, alloc)?;
+
+        // The trailing padding of the header may have overwritten the start of the tail.
+        let overlap = core::mem::size_of::<Header>()
+            .saturating_sub(writer.offset)
+            .min(writer.layout.size() - writer.offset);
+        writer.tail().cast::<u8>().write_bytes(0, overlap);
+
+        writer.written = len;
+        Ok(writer.finish::<()>())
+    }
+
+    #[inline]
+    fn write_tail<I>(&mut self, value: Tail) {
+        assert!(
+            self.written < self.len,
+            "got more items than expected. Probable bug in `ExactSizeIterator` for `{}`?",
+            core::any::type_name::<I>(),
+        );
+
+        unsafe {
+            self.tail().add(self.written).write(value);
+        }
+        self.written += 1;
+    }
+
+    /// Returns a pointer to the complete allocation, with the length of the tail as metadata.
+    #[inline]
+    fn finish<I>(self) -> *mut [()] {
+        if self.written != self.len {
+            wrong_len(self.written, self.len, core::any::type_name::<I>());
+        }
+
+        // Casting this slice pointer to a pointer to the struct keeps its address, provenance and
+        // length, so neither `transmute` nor the unstable `ptr::from_raw_parts_mut` is needed.
+        let ptr = core::ptr::slice_from_raw_parts_mut(self.raw.cast::<()>(), self.len);
+
+        tracking::record(self.raw, self.layout);
+
+        // once we have finished constructing the value, don't run the destructor
+        core::mem::forget(self);
+
+        ptr
+    }
+
+    fn tail(&self) -> *mut Tail {
+        // SAFETY: the offset lies within the allocation (or is 0 if nothing was allocated)
+        unsafe { self.raw.add(self.offset).cast::<Tail>() }
+    }
+
+    /// # Safety
+    ///
+    /// Assumes that this writer was created with a capacity for `values.len()`
+    unsafe fn write_slice(&mut self, values: &[Tail])
+    where
+        Tail: Copy,
+    {
+        self.copy_tail(values.as_ptr(), values.len());
+    }
+
+    /// Copies `count` elements from `src` after the elements which have been written so far.
+    ///
+    /// # Safety
+    ///
+    /// `src` must be valid for reading `count` elements, which are owned by the writer afterwards.
+    /// The writer must have room for them: no more than `len` elements may be written in total,
+    /// which debug builds also check against the size of the allocation.
+    unsafe fn copy_tail(&mut self, src: *const Tail, count: usize) {
+        let room = self.len - self.written;
+        let room_bytes = self.layout.size() - self.offset - self.written * size_of::<Tail>();
+        let bytes = count * size_of::<Tail>();
+        debug_assert!(
+            count <= room && bytes <= room_bytes,
+            "copying {} tail elements ({} bytes) into a value with room for {} more ({} bytes)",
+            count,
+            bytes,
+            room,
+            room_bytes,
+        );
+        self.tail()
+            .add(self.written)
+            .copy_from_nonoverlapping(src, count);
+        self.written += count;
+    }
+}
+
+impl<Header, Tail> BoxWriter<Header, Tail> {
+    /// Takes over a complete value with `len` tail elements, whose memory comes from the global
+    /// allocator.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must point to a value allocated like in [`BoxWriter::new`] with the same
+    /// `header_layout` and `len`, which is then owned by the writer.
+    unsafe fn from_raw(raw: *mut u8, header_layout: Layout, len: usize) -> Self {
+        // This cannot fail, since the value was allocated with the same layout.
+        let (layout, offset) = __private::unwrap(layout::for_len::<Tail>(header_layout, len));
+        BoxWriter {
+            raw,
+            layout,
+            offset,
+            len,
+            written: len,
+            alloc: Global,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Reallocates the memory for `len` tail elements, keeping the header and the elements which
+    /// have been written so far.
+    ///
+    /// # Safety
+    ///
+    /// No more than `len` elements may have been written.
+    unsafe fn resize(&mut self, header_layout: Layout, len: usize) -> Result<(), TryNewError> {
+        debug_assert!(self.written <= len);
+        let (raw, layout, offset) = reallocate(
+            self.raw,
+            self.layout,
+            header_layout,
+            Layout::new::<Tail>(),
+            len,
+        )?;
+        debug_assert_eq!(offset, self.offset);
+        self.raw = raw;
+        self.layout = layout;
+        self.len = len;
+        Ok(())
+    }
+}
+
+/// Reallocates a value allocated with `old` for `len` elements with the layout `element` after the
+/// fields in `header`, and returns it with its new layout and the offset of the tail. Like
+/// [`RawAlloc::allocate_value`], this is only compiled once.
+///
+/// # Safety
+///
+/// `raw` must have been allocated by the global allocator with `old`, or be dangling if its size
+/// is zero. It is only freed if this succeeds.
+#[inline(never)]
+unsafe fn reallocate(
+    raw: *mut u8,
+    old: Layout,
+    header: Layout,
+    element: Layout,
+    len: usize,
+) -> Result<(*mut u8, Layout, usize), TryNewError> {
+    let (layout, offset) = layout::for_element(header, element, len)?;
+
+    // Whenever either layout is empty, the header and the written elements take up no memory at
+    // all, so there is nothing to move.
+    //
+    // The old layout is only forgotten once the value has moved, since it is still in use if this
+    // fails. The value is recorded again at its new address once it is finished.
+    let new = if old.size() == 0 {
+        let new = allocate(layout, false)?;
+        tracking::forget(raw);
+        new
+    } else if layout.size() == 0 {
+        tracking::forget(raw);
+        alloc::alloc::dealloc(raw, old);
+        core::ptr::without_provenance_mut(layout.align())
+    } else {
+        // The alignment stays the same, since it only depends on the types.
+        let new = alloc::alloc::realloc(raw, old, layout.size());
+        if new.is_null() {
+            return Err(TryNewError::AllocError { layout });
+        }
+        tracking::forget(raw);
+        new
+    };
+    Ok((new, layout, offset))
+}
+
+impl<Header, Tail, A: RawAlloc> Drop for BoxWriter<Header, Tail, A> {
+    fn drop(&mut self) {
+        unsafe {
+            // SAFETY: the header field is always initialized
+            core::ptr::drop_in_place(self.raw.cast::<Header>());
+
+            let initialized = core::ptr::slice_from_raw_parts_mut(self.tail(), self.written);
+            core::ptr::drop_in_place(initialized);
+
+            // We only get here if construction never finished (for example if the iterator
+            // panicked), so we are still responsible for the allocation itself.
+            // SAFETY: this is the same layout the memory was allocated with in `new`
+            self.alloc.deallocate(self.raw, self.layout);
+        }
+    }
+}
+
+impl<T> DynStruct<T, T> {
+    /// Get a `DynStruct` as a view over a slice (this does not allocate).
+    pub fn slice_view(values: &[T]) -> &Self {
+        assert!(
+            !values.is_empty(),
+            "attempted to create `{}` from empty slice (needs at least 1 element)",
+            core::any::type_name::<Self>()
+        );
+        // Build the fat pointer from `values` directly: reborrowing a shorter subslice would
+        // restrict the pointer to only cover the elements of that subslice.
+        let slice = core::ptr::slice_from_raw_parts(values.as_ptr(), values.len() - 1);
+        unsafe { &*(slice as *const Self) }
+    }
+}
+
+impl<T, const N: usize> DynStruct<[T; N], T> {
+    /// Get a `DynStruct` as a view over a slice (this does not allocate).
+    pub fn slice_view(values: &[T]) -> &Self {
+        assert!(
+            values.len() >= N,
+            "attempted to create `{}` from empty slice (needs at least {} elements)",
+            core::any::type_name::<Self>(),
+            N,
+        );
+        let slice = core::ptr::slice_from_raw_parts(values.as_ptr(), values.len() - N);
+        unsafe { &*(slice as *const Self) }
+    }
+}
+
+/// Implementation details of the `DynStruct` derive macro. Not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+    #[cfg(feature = "allocator_api")]
+    use super::InAllocator;
+    use super::{allocate, layout, tracking, BoxWriter, DynStruct, Global, TryNewError, Zeroable};
+    use alloc::boxed::Box;
+    use alloc::vec::Vec;
+    use core::alloc::Layout;
+    use core::mem::{align_of, size_of, ManuallyDrop, MaybeUninit};
+
+    pub extern crate alloc;
+    pub extern crate core;
+    #[cfg(feature = "serde")]
+    pub use serde;
+
+    /// Converts between a pointer to a value, whose metadata is the length of its tail, and a thin
+    /// pointer with that length. Also describes the layout of the value.
+    ///
+    /// # Safety
+    ///
+    /// `from_raw` must be the inverse of `into_raw`, and the layout must be that of the type.
+    pub unsafe trait RawParts {
+        const TAIL_OFFSET: usize;
+        const ELEMENT_LAYOUT: Layout;
+        fn layout_for_len(len: usize) -> Result<Layout, TryNewError>;
+        fn into_raw(ptr: *mut Self) -> (*mut (), usize);
+        fn from_raw(ptr: *mut (), len: usize) -> *mut Self;
+    }
+
+    unsafe impl<Header, Tail> RawParts for DynStruct<Header, Tail> {
+        const TAIL_OFFSET: usize = Self::TAIL_OFFSET;
+        const ELEMENT_LAYOUT: Layout = Layout::new::<Tail>();
+
+        #[inline]
+        fn layout_for_len(len: usize) -> Result<Layout, TryNewError> {
+            layout::for_len::<Tail>(Layout::new::<Header>(), len).map(|(layout, _)| layout)
+        }
+
+        #[inline]
+        fn into_raw(ptr: *mut Self) -> (*mut (), usize) {
+            (ptr.cast::<()>(), (ptr as *mut [()]).len())
+        }
+
+        #[inline]
+        fn from_raw(ptr: *mut (), len: usize) -> *mut Self {
+            core::ptr::slice_from_raw_parts_mut(ptr, len) as *mut Self
+        }
+    }
+
+    /// With the `layout-tracking` feature, panics if the value at `ptr` was allocated with a
+    /// different layout than the one a `Box` of the type it is about to become would free it with.
+    #[inline]
+    #[track_caller]
+    pub fn assert_layout_on_free(ptr: *const (), layout: impl FnOnce() -> Layout) {
+        tracking::check(ptr.cast::<u8>(), layout)
+    }
+
+    /// Panics or aborts on an error, the way the infallible constructors of the standard library do.
+    #[inline]
+    pub fn unwrap<T>(result: Result<T, TryNewError>) -> T {
+        match result {
+            Ok(value) => value,
+            Err(error) => fail(error),
+        }
+    }
+
+    /// The error path of [`unwrap`], which does not depend on `T`.
+    #[cold]
+    #[inline(never)]
+    fn fail(error: TryNewError) -> ! {
+        match error {
+            TryNewError::CapacityOverflow => panic!("{}", TryNewError::CapacityOverflow),
+            TryNewError::AllocError { layout } => alloc::alloc::handle_alloc_error(layout),
+        }
+    }
+
+    /// Like [`DynStruct::try_new`](crate::DynStruct::try_new), but the tail is placed directly
+    /// after the last field of `Header` instead of after its trailing padding. Returns a pointer
+    /// to the allocation with the length of the tail as metadata.
+    ///
+    /// # Safety
+    ///
+    /// `Header` must be `#[repr(C)]` and `header_end` must be the offset just past its last field,
+    /// such that `size_of::<Header>()` is `header_end` rounded up to the alignment of `Header`.
+    pub unsafe fn new<Header, Tail, I>(
+        header: Header,
+        header_end: usize,
+        tail: I,
+    ) -> Result<*mut [()], TryNewError>
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        BoxWriter::from_iter(header, header_layout::<Header>(header_end)?, tail, Global)
+    }
+
+    /// Like [`new`], but copies the tail from a slice.
+    ///
+    /// # Safety
+    ///
+    /// See [`new`].
+    pub unsafe fn from_slice<Header, Tail: Copy>(
+        header: Header,
+        header_end: usize,
+        tail: &[Tail],
+    ) -> Result<*mut [()], TryNewError> {
+        BoxWriter::from_slice(header, header_layout::<Header>(header_end)?, tail, Global)
+    }
+
+    /// Like [`new`], but allocates in `alloc`.
+    ///
+    /// # Safety
+    ///
+    /// See [`new`].
+    #[cfg(feature = "allocator_api")]
+    pub unsafe fn new_in<Header, Tail, I, A>(
+        header: Header,
+        header_end: usize,
+        tail: I,
+        alloc: &A,
+    ) -> Result<*mut [()], TryNewError>
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+        A: core::alloc::Allocator,
+    {
+        BoxWriter::from_iter(
+            header,
+            header_layout::<Header>(header_end)?,
+            tail,
+            InAllocator(alloc),
+        )
+    }
+
+    /// Like [`from_slice`], but allocates in `alloc`.
+    ///
+    /// # Safety
+    ///
+    /// See [`from_slice`].
+    #[cfg(feature = "allocator_api")]
+    pub unsafe fn from_slice_in<Header, Tail: Copy, A: core::alloc::Allocator>(
+        header: Header,
+        header_end: usize,
+        tail: &[Tail],
+        alloc: &A,
+    ) -> Result<*mut [()], TryNewError> {
+        BoxWriter::from_slice(
+            header,
+            header_layout::<Header>(header_end)?,
+            tail,
+            InAllocator(alloc),
+        )
+    }
+
+    #[cfg(feature = "bumpalo")]
+    pub use bumpalo::Bump;
+
+    /// Like [`new`], but allocates in `bump`. The value is never dropped.
+    ///
+    /// # Safety
+    ///
+    /// See [`new`].
+    #[cfg(feature = "bumpalo")]
+    pub unsafe fn new_in_bump<Header, Tail, I>(
+        header: Header,
+        header_end: usize,
+        tail: I,
+        bump: &Bump,
+    ) -> Result<*mut [()], TryNewError>
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let header_layout = header_layout::<Header>(header_end)?;
+        BoxWriter::from_iter(header, header_layout, tail, super::InBump(bump))
+            .map(super::InBump::untrack)
+    }
+
+    /// Like [`from_slice`], but allocates in `bump`.
+    ///
+    /// # Safety
+    ///
+    /// See [`from_slice`].
+    #[cfg(feature = "bumpalo")]
+    pub unsafe fn from_slice_in_bump<Header, Tail: Copy>(
+        header: Header,
+        header_end: usize,
+        tail: &[Tail],
+        bump: &Bump,
+    ) -> Result<*mut [()], TryNewError> {
+        let header_layout = header_layout::<Header>(header_end)?;
+        BoxWriter::from_slice(header, header_layout, tail, super::InBump(bump))
+            .map(super::InBump::untrack)
+    }
+
+    /// Like [`new_box`], but allocates in `bump`. The value is never dropped.
+    #[cfg(feature = "bumpalo")]
+    pub fn new_sized_in_bump<T>(value: T, bump: &Bump) -> Result<*mut T, TryNewError> {
+        bump.try_alloc(value)
+            .map(|value| value as *mut T)
+            .map_err(|_| TryNewError::AllocError {
+                layout: Layout::new::<T>(),
+            })
+    }
+
+    /// Moves the elements of a slice into a new `Vec`.
+    ///
+    /// # Safety
+    ///
+    /// The elements are duplicated: they may not be used or dropped through `tail` afterwards.
+    pub unsafe fn read_vec<T>(tail: &[T]) -> Vec<T> {
+        let mut vec = Vec::<T>::with_capacity(tail.len());
+        vec.as_mut_ptr()
+            .copy_from_nonoverlapping(tail.as_ptr(), tail.len());
+        vec.set_len(tail.len());
+        vec
+    }
+
+    /// Frees the memory of a `Box` without dropping its value.
+    pub fn free<T: ?Sized>(value: Box<T>) {
+        let size = core::mem::size_of_val::<T>(&value);
+        let raw = Box::into_raw(value) as *mut ManuallyDrop<T>;
+        unsafe {
+            super::poison(raw.cast::<u8>(), size);
+            tracking::forget(raw.cast::<u8>());
+            drop(Box::from_raw(raw));
+        }
+    }
+
+    /// Like [`DynStruct::with_extended_tail`](crate::DynStruct::with_extended_tail), where the
+    /// value has the fields of `Header` followed by a tail of `len` elements.
+    ///
+    /// # Safety
+    ///
+    /// `value` must have been created by [`new`] (or the other constructors here) with the same
+    /// `Header`, `Tail` and `header_end`, and have a tail of `len` elements.
+    pub unsafe fn with_extended_tail<T: ?Sized, Header, Tail: Clone>(
+        value: Box<T>,
+        header_end: usize,
+        len: usize,
+        extra: &[Tail],
+    ) -> *mut [()] {
+        let header_layout = unwrap(header_layout::<Header>(header_end));
+        let raw = Box::into_raw(value).cast::<u8>();
+        super::extend_tail::<Header, Tail, _>(raw, header_layout, len, extra.iter().cloned())
+    }
+
+    /// Like [`DynStruct::with_extended_tail_iter`](crate::DynStruct::with_extended_tail_iter),
+    /// where the value has the fields of `Header` followed by a tail of `len` elements.
+    ///
+    /// # Safety
+    ///
+    /// See [`with_extended_tail`].
+    pub unsafe fn with_extended_tail_iter<T: ?Sized, Header, Tail, I>(
+        value: Box<T>,
+        header_end: usize,
+        len: usize,
+        extra: I,
+    ) -> *mut [()]
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let header_layout = unwrap(header_layout::<Header>(header_end));
+        let raw = Box::into_raw(value).cast::<u8>();
+        super::extend_tail::<Header, Tail, _>(raw, header_layout, len, extra.into_iter())
+    }
+
+    /// Like [`DynStruct::with_truncated_tail`](crate::DynStruct::with_truncated_tail), where
+    /// the value has the fields of `Header` followed by a tail of `len` elements.
+    ///
+    /// # Safety
+    ///
+    /// See [`with_extended_tail`].
+    pub unsafe fn with_truncated_tail<T: ?Sized, Header, Tail>(
+        value: Box<T>,
+        header_end: usize,
+        len: usize,
+        new_len: usize,
+    ) -> *mut [()] {
+        let header_layout = unwrap(header_layout::<Header>(header_end));
+        let raw = Box::into_raw(value).cast::<u8>();
+        super::truncate_tail::<Header, Tail>(raw, header_layout, len, new_len)
+    }
+
+    /// Returns `true` if a `#[repr(C)]` struct with fields that have no padding themselves, followed
+    /// by a slice of `Tail`, has no padding either. `fields` is the sum of the sizes of the fields
+    /// in `Header`.
+    pub const fn padding_free<Header, Tail>(header_end: usize, fields: usize) -> bool {
+        let align = if align_of::<Header>() > align_of::<Tail>() {
+            align_of::<Header>()
+        } else {
+            align_of::<Tail>()
+        };
+        // Without gaps between the fields, the tail starts where the header ends, and no number of
+        // elements in the tail may require trailing padding.
+        fields == header_end && header_end % align == 0 && size_of::<Tail>() % align == 0
+    }
+
+    /// Like [`new`], but moves the elements of the tail out of a `Vec`.
+    ///
+    /// # Safety
+    ///
+    /// See [`new`].
+    pub unsafe fn from_vec<Header, Tail>(
+        header: Header,
+        header_end: usize,
+        tail: Vec<Tail>,
+    ) -> Result<*mut [()], TryNewError> {
+        BoxWriter::from_vec(header, header_layout::<Header>(header_end)?, tail, Global)
+    }
+
+    /// Like [`new`], but with `len` tail elements that are all zero.
+    ///
+    /// # Safety
+    ///
+    /// See [`new`].
+    pub unsafe fn new_zeroed<Header, Tail: Zeroable>(
+        header: Header,
+        header_end: usize,
+        len: usize,
+    ) -> Result<*mut [()], TryNewError> {
+        BoxWriter::<Header, Tail>::zeroed(header, header_layout::<Header>(header_end)?, len, Global)
+    }
+
+    /// Like [`new`], but with `len` tail elements that are not initialized.
+    ///
+    /// # Safety
+    ///
+    /// See [`new`].
+    pub unsafe fn new_uninit<Header, Tail>(
+        header: Header,
+        header_end: usize,
+        len: usize,
+    ) -> Result<*mut [()], TryNewError> {
+        let mut writer = BoxWriter::<Header, MaybeUninit<Tail>>::new(
+            header,
+            header_layout::<Header>(header_end)?,
+            len,
+            false,
+            Global,
+        )?;
+        // there is nothing to initialize
+        writer.written = len;
+        Ok(writer.finish::<()>())
+    }
+
+    /// Returns the layout of the fields of `Header`, which excludes its trailing padding.
+    pub const fn header_layout<Header>(header_end: usize) -> Result<Layout, TryNewError> {
+        layout::header::<Header>(header_end)
+    }
+
+    /// Returns the offset of the tail, which follows the fields of `Header`.
+    pub const fn tail_offset<Header, Tail>(header_end: usize) -> usize {
+        layout::tail_offset::<Tail>(header_end)
+    }
+
+    /// Returns the layout of a value with the fields of `Header` followed by `len` elements of
+    /// `Tail`, exactly as it would be allocated.
+    pub const fn layout_for_len<Header, Tail>(
+        header_end: usize,
+        len: usize,
+    ) -> Result<Layout, TryNewError> {
+        match header_layout::<Header>(header_end) {
+            Ok(header) => match layout::for_len::<Tail>(header, len) {
+                Ok((layout, _)) => Ok(layout),
+                Err(error) => Err(error),
+            },
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Like [`unwrap`], for the layouts returned by [`layout_for_len`] in a const context.
+    pub const fn unwrap_layout(result: Result<Layout, TryNewError>) -> Layout {
+        match result {
+            Ok(layout) => layout,
+            Err(_) => panic!("size of `DynStruct` exceeds `isize::MAX`"),
+        }
+    }
+
+    /// Like `Box::new`, but returns an error if the allocation fails.
+    pub fn new_box<T>(value: T) -> Result<*mut T, TryNewError> {
+        let raw = allocate(Layout::new::<T>(), false)?.cast::<T>();
+        unsafe { raw.write(value) };
+        Ok(raw)
+    }
+
+    /// Moves `header` and the value in `tail` into a single new allocation, laid out like a
+    /// `#[repr(C)]` struct with the fields of `Header` followed by the tail. Returns a thin pointer
+    /// to the start of the allocation.
+    ///
+    /// # Safety
+    ///
+    /// `Header` must be `#[repr(C)]` and `header_end` must be the offset just past its last field,
+    /// such that `size_of::<Header>()` is `header_end` rounded up to the alignment of `Header`.
+    pub unsafe fn new_unsized<Header, Tail: ?Sized>(
+        header: Header,
+        header_end: usize,
+        tail: Box<Tail>,
+    ) -> Result<*mut u8, TryNewError> {
+        // The layout of the tail is only measured here, while `tail` is still a valid box. After it
+        // has been moved, the value is never looked at again.
+        let tail_layout = Layout::for_value::<Tail>(&tail);
+        let (layout, offset) =
+            layout::dyn_layout(header_layout::<Header>(header_end)?, tail_layout)?;
+
+        let raw = allocate(layout, false)?;
+
+        // Write the header first: its trailing padding may overlap the start of the tail.
+        raw.cast::<Header>().write(header);
+
+        debug_assert!(
+            tail_layout.size() <= layout.size() - offset,
+            "moving a tail of {} bytes to offset {} of an allocation of {} bytes",
+            tail_layout.size(),
+            offset,
+            layout.size(),
+        );
+        let tail = Box::into_raw(tail);
+        core::ptr::copy_nonoverlapping(
+            tail.cast::<MaybeUninit<u8>>(),
+            raw.add(offset).cast::<MaybeUninit<u8>>(),
+            tail_layout.size(),
+        );
+
+        // The value has been moved, so only free the memory of the box. `Box` allocates non-zero
+        // sizes with the global allocator and the layout of the value.
+        if tail_layout.size() != 0 {
+            super::poison(tail.cast::<u8>(), tail_layout.size());
+            tracking::forget(tail.cast::<u8>());
+            alloc::alloc::dealloc(tail.cast::<u8>(), tail_layout);
+        }
+
+        Ok(raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::String;
+    use std::vec;
+
+    #[test]
+    fn mixed_types() {
+        let mixed = DynStruct::new((true, 32u16), [1u64, 2, 3, 4]);
+        assert_eq!(mixed.header, (true, 32u16));
+        assert_eq!(&mixed.tail, &[1, 2, 3, 4]);
     }
 
     #[test]
@@ -321,6 +2036,168 @@ mod tests {
         assert_eq!(&zero.tail, &[(), ()]);
     }
 
+    // These are mostly useful under Miri, which rejects null or misaligned boxes and any attempt to
+    // deallocate a dangling pointer.
+    #[test]
+    fn zero_sized_allocations() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        let empty = DynStruct::new((), vec::Vec::<u64>::new());
+        assert_eq!(core::mem::size_of_val(&*empty), 0);
+        assert_eq!(
+            (&*empty as *const DynStruct<(), u64>).cast::<u8>() as usize % 8,
+            0
+        );
+        drop(empty);
+
+        let empty = DynStruct::<[u16; 0], u8>::from_slice([], &[]);
+        assert_eq!(core::mem::size_of_val(&*empty), 0);
+        let (raw, len) = DynStruct::into_raw_parts(empty);
+        assert!(!raw.is_null());
+        drop(unsafe { DynStruct::<[u16; 0], u8>::from_raw_parts(raw, len) });
+
+        // Zero-sized elements are still dropped, even though nothing is allocated for them.
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+        struct Counted([u32; 0]);
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        let counted = DynStruct::new((), (0..5).map(|_| Counted([])));
+        assert_eq!(counted.tail.len(), 5);
+        assert_eq!(core::mem::size_of_val(&*counted), 0);
+        assert_eq!(
+            (&counted.tail as *const [Counted]).cast::<u8>() as usize % 4,
+            0
+        );
+        drop(counted);
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 5);
+
+        let zeroed = DynStruct::<(), [u64; 0]>::new_zeroed((), 3);
+        assert_eq!(zeroed.tail.len(), 3);
+    }
+
+    // `Box` deallocates with `Layout::for_value`, so the allocation must use the same layout,
+    // including trailing padding. Miri also checks that the layouts agree.
+    #[test]
+    fn layout_matrix() {
+        fn check<Header: Default, Tail: Default + Copy>() {
+            for len in [0, 1, 3] {
+                let expected = |value: &DynStruct<Header, Tail>| {
+                    // The constants have to agree with the value as well.
+                    let base = (value as *const DynStruct<Header, Tail>).cast::<u8>();
+                    let tail = core::ptr::addr_of!(value.tail).cast::<u8>();
+                    assert_eq!(
+                        tail as usize - base as usize,
+                        DynStruct::<Header, Tail>::TAIL_OFFSET
+                    );
+                    assert_eq!(
+                        core::mem::size_of_val(value),
+                        DynStruct::<Header, Tail>::size_for_len(value.tail.len())
+                    );
+
+                    layout::for_len::<Tail>(Layout::new::<Header>(), value.tail.len())
+                        .unwrap()
+                        .0
+                };
+
+                let value = DynStruct::new(Header::default(), vec![Tail::default(); len]);
+                assert_eq!(Layout::for_value(&*value), expected(&value));
+                let value = DynStruct::with_extended_tail(value, &[Tail::default()]);
+                assert_eq!(Layout::for_value(&*value), expected(&value));
+                let value = DynStruct::with_truncated_tail(value, len / 2);
+                assert_eq!(Layout::for_value(&*value), expected(&value));
+
+                let value = DynStruct::from_slice(Header::default(), &vec![Tail::default(); len]);
+                assert_eq!(Layout::for_value(&*value), expected(&value));
+                let value = DynStruct::from_vec(Header::default(), vec![Tail::default(); len]);
+                assert_eq!(Layout::for_value(&*value), expected(&value));
+            }
+        }
+
+        macro_rules! matrix {
+            ($($header:ty),* ; $tails:tt) => {
+                $(matrix!(@row $header ; $tails);)*
+            };
+            (@row $header:ty ; [$($tail:ty),*]) => {
+                $(check::<$header, $tail>();)*
+            };
+        }
+
+        matrix!(
+            (), u8, u16, u64, [u8; 3], (u64, u8), [u64; 0], (u8, [u32; 0]);
+            [(), u8, [u8; 3], u16, u64, (u16, u8), [u32; 0]]
+        );
+    }
+
+    #[test]
+    fn const_layout() {
+        const OFFSET: usize = DynStruct::<(u8, u16), u64>::TAIL_OFFSET;
+        const SIZE: usize = DynStruct::<(u8, u16), u64>::size_for_len(3);
+        assert_eq!((OFFSET, SIZE), (8, 32));
+        const LAYOUT: Layout = DynStruct::<(u8, u16), u64>::layout_for_len(3);
+        assert_eq!((LAYOUT.size(), LAYOUT.align()), (SIZE, 8));
+        assert_eq!(DynStruct::<(u8, u16), u64>::ALIGN, 8);
+        assert_eq!(DynStruct::<u8, [u16; 0]>::ALIGN, 2);
+
+        // Used as array lengths, these set aside room for a value with three elements.
+        static BUFFER: [u64; DynStruct::<(u8, u16), u64>::size_for_len(3) / 8] = [0; 4];
+        let words = [0u16; DynStruct::<u16, u16>::size_for_len(5) / 2];
+        assert_eq!((BUFFER.len(), words.len()), (4, 6));
+        assert_eq!(DynStruct::<u64, u8>::size_for_len(1), 16);
+        assert_eq!(DynStruct::<(), ()>::size_for_len(usize::MAX), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "size of `DynStruct` exceeds `isize::MAX`")]
+    fn size_for_len_overflow() {
+        DynStruct::<u8, u16>::size_for_len(isize::MAX as usize / 2);
+    }
+
+    #[test]
+    fn canonical_bytes() {
+        fn bytes<Header: Pod, Tail: Pod>(value: &mut DynStruct<Header, Tail>) -> Vec<u8> {
+            value.as_bytes().to_vec()
+        }
+
+        // Leave non-zero bytes behind in freed memory, which the next allocations may reuse.
+        drop(vec![0xAAu8; 64]);
+        let first = bytes(&mut DynStruct::new(1u8, [2u64, 3]));
+        drop(DynStruct::from_slice(u64::MAX, &[u64::MAX; 3]));
+        let second = bytes(&mut DynStruct::from_vec(1u8, vec![2u64, 3]));
+        assert_eq!(first, second);
+        let mut expected = vec![1, 0, 0, 0, 0, 0, 0, 0];
+        expected.extend_from_slice(&2u64.to_ne_bytes());
+        expected.extend_from_slice(&3u64.to_ne_bytes());
+        assert_eq!(first, expected);
+
+        // Trailing padding: the value is padded to the alignment of the header.
+        let mut value = DynStruct::with_extended_tail(DynStruct::new(7u32, [1u8]), &[2]);
+        assert_eq!(
+            bytes(&mut value),
+            [7u32.to_ne_bytes().to_vec(), vec![1, 2, 0, 0]].concat()
+        );
+        assert_eq!(
+            bytes(&mut DynStruct::new(7u16, [0u8; 0])),
+            7u16.to_ne_bytes()
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(
+        expected = "copying 2 tail elements (8 bytes) into a value with room for 1 more (4 bytes)"
+    )]
+    fn copy_tail_overflow() {
+        let tail = [1u32, 2];
+        unsafe {
+            let mut writer =
+                BoxWriter::<u32, u32>::new(0, Layout::new::<u32>(), 1, false, Global).unwrap();
+            writer.copy_tail(tail.as_ptr(), tail.len());
+        }
+    }
+
     #[test]
     fn from_slice() {
         let slice = DynStruct::from_slice((true, 32u16), &[1, 2, 3]);
@@ -332,6 +2209,196 @@ mod tests {
         assert_eq!(&array.tail, &[4, 5]);
     }
 
+    #[test]
+    fn padded_types() {
+        let padded = DynStruct::new((1u8, 2u64), [(3u8, 4u64), (5, 6)]);
+        assert_eq!(padded.header, (1, 2));
+        assert_eq!(&padded.tail, &[(3, 4), (5, 6)]);
+
+        let padded = DynStruct::from_slice((1u8, 2u64), &[(3u8, 4u64), (5, 6)]);
+        assert_eq!(padded.header, (1, 2));
+        assert_eq!(&padded.tail, &[(3, 4), (5, 6)]);
+    }
+
+    #[test]
+    fn len() {
+        let full = DynStruct::new(0u8, [1u32, 2, 3]);
+        assert_eq!(full.len(), 3);
+        assert!(!full.is_empty());
+
+        let empty = DynStruct::<u8, u32>::new(0, []);
+        assert_eq!(empty.len(), 0);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn raw_parts() {
+        let boxed = DynStruct::new(String::from("header"), vec![String::from("tail"); 3]);
+        let (ptr, len) = DynStruct::into_raw_parts(boxed);
+        assert_eq!(len, 3);
+
+        let boxed = unsafe { DynStruct::<String, String>::from_raw_parts(ptr, len) };
+        assert_eq!(boxed.header, "header");
+        assert_eq!(&boxed.tail, &["tail", "tail", "tail"]);
+
+        let zero = DynStruct::new((), [(), ()]);
+        let (ptr, len) = DynStruct::into_raw_parts(zero);
+        let zero = unsafe { DynStruct::<(), ()>::from_raw_parts(ptr, len) };
+        assert_eq!(zero.len(), 2);
+    }
+
+    #[test]
+    fn construct_on_other_thread() {
+        let tail = vec![String::from("a"), String::from("b")];
+        let boxed = std::thread::spawn(move || DynStruct::new(1u32, tail))
+            .join()
+            .unwrap();
+        assert_eq!(boxed.header, 1);
+        assert_eq!(&boxed.tail, &["a", "b"]);
+
+        let shared = std::sync::Arc::<DynStruct<u32, String>>::from(boxed);
+        let other = shared.clone();
+        let len = std::thread::spawn(move || other.tail.len()).join().unwrap();
+        assert_eq!(len, 2);
+    }
+
+    /// An iterator which reports a length of `len`, but only yields `values` and then panics.
+    struct Lying<T> {
+        values: std::vec::IntoIter<T>,
+        len: usize,
+    }
+
+    impl<T> Iterator for Lying<T> {
+        type Item = T;
+
+        fn next(&mut self) -> Option<T> {
+            Some(self.values.next().expect("ran out of values"))
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (self.len, Some(self.len))
+        }
+    }
+
+    impl<T> ExactSizeIterator for Lying<T> {}
+
+    #[test]
+    fn panicking_iterator_releases_values() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        use std::rc::Rc;
+
+        let header = Rc::new(());
+        let tail = Rc::new(());
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let values = vec![tail.clone(), tail.clone()];
+            DynStruct::new(
+                header.clone(),
+                Lying {
+                    values: values.into_iter(),
+                    len: 4,
+                },
+            )
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(Rc::strong_count(&header), 1);
+        assert_eq!(Rc::strong_count(&tail), 1);
+    }
+
+    #[test]
+    fn layout_failure_releases_values() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        use std::rc::Rc;
+
+        let header = Rc::new(());
+        let tail = Rc::new(());
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let values = vec![tail.clone()];
+            // large enough that the allocation exceeds `isize::MAX` bytes
+            let len = isize::MAX as usize / size_of::<Rc<()>>();
+            DynStruct::new(
+                header.clone(),
+                Lying {
+                    values: values.into_iter(),
+                    len,
+                },
+            )
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(Rc::strong_count(&header), 1);
+        assert_eq!(Rc::strong_count(&tail), 1);
+    }
+
+    #[test]
+    fn from_vec() {
+        use std::rc::Rc;
+
+        let tail = Rc::new(());
+        let moved = DynStruct::from_vec(1u8, vec![tail.clone(), tail.clone()]);
+        assert_eq!(moved.header, 1);
+        assert_eq!(moved.len(), 2);
+        assert_eq!(Rc::strong_count(&tail), 3);
+        drop(moved);
+        assert_eq!(Rc::strong_count(&tail), 1);
+
+        let strings = DynStruct::from_vec((), vec![String::from("a"), String::from("b")]);
+        assert_eq!(&strings.tail, &["a", "b"]);
+    }
+
+    #[test]
+    fn into_parts() {
+        use std::rc::Rc;
+
+        let counter = Rc::new(());
+        let value = DynStruct::new(counter.clone(), vec![counter.clone(), counter.clone()]);
+        let (header, tail) = DynStruct::into_parts(value);
+        assert_eq!(Rc::strong_count(&counter), 4);
+        assert_eq!(tail.len(), 2);
+        drop((header, tail));
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn new_zeroed() {
+        let zeroed = DynStruct::<(u8, u64), u32>::new_zeroed((1, 2), 1000);
+        assert_eq!(zeroed.header, (1, 2));
+        assert_eq!(zeroed.len(), 1000);
+        assert!(zeroed.tail.iter().all(|&value| value == 0));
+
+        let empty = DynStruct::<u8, u64>::new_zeroed(3, 0);
+        assert_eq!(empty.header, 3);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn try_new() {
+        use std::rc::Rc;
+
+        let ok = DynStruct::try_new(1u8, [2u32, 3]).unwrap();
+        assert_eq!(ok.header, 1);
+        assert_eq!(&ok.tail, &[2, 3]);
+
+        let header = Rc::new(());
+        let tail = Rc::new(());
+
+        let values = vec![tail.clone()];
+        let len = isize::MAX as usize / size_of::<Rc<()>>();
+        let result = DynStruct::try_new(
+            header.clone(),
+            Lying {
+                values: values.into_iter(),
+                len,
+            },
+        );
+
+        assert_eq!(result.unwrap_err(), TryNewError::CapacityOverflow);
+        assert_eq!(Rc::strong_count(&header), 1);
+        assert_eq!(Rc::strong_count(&tail), 1);
+    }
+
     #[test]
     fn slice_view() {
         let same = DynStruct::<u32, u32>::slice_view(&[1, 2, 3]);
@@ -342,4 +2409,163 @@ mod tests {
         assert_eq!(array.header, [1, 2, 3]);
         assert_eq!(&array.tail, &[4, 5]);
     }
+
+    #[test]
+    fn with_extended_tail() {
+        let value = DynStruct::new(String::from("header"), vec![String::from("a")]);
+        let value = DynStruct::with_extended_tail(value, &[String::from("b"), String::from("c")]);
+        assert_eq!(value.header, "header");
+        assert_eq!(&value.tail, &["a", "b", "c"]);
+
+        let empty = DynStruct::new((), Vec::<u64>::new());
+        let grown = DynStruct::with_extended_tail(empty, &[1, 2]);
+        assert_eq!(&grown.tail, &[1, 2]);
+
+        let zero_sized = DynStruct::new((), vec![(); 3]);
+        let zero_sized = DynStruct::with_extended_tail(zero_sized, &[(); 2]);
+        assert_eq!(zero_sized.tail.len(), 5);
+    }
+
+    #[test]
+    fn with_truncated_tail() {
+        use std::rc::Rc;
+
+        let header = Rc::new(());
+        let tail = Rc::new(());
+        let value = DynStruct::new(header.clone(), vec![tail.clone(); 4]);
+
+        let value = DynStruct::with_truncated_tail(value, 5);
+        assert_eq!(value.tail.len(), 4);
+
+        let value = DynStruct::with_truncated_tail(value, 1);
+        assert_eq!(value.tail.len(), 1);
+        assert_eq!(Rc::strong_count(&tail), 2);
+
+        let value = DynStruct::with_truncated_tail(value, 0);
+        assert!(value.tail.is_empty());
+        assert_eq!(Rc::strong_count(&tail), 1);
+
+        drop(value);
+        assert_eq!(Rc::strong_count(&header), 1);
+
+        let zero_sized = DynStruct::new((), vec![(); 3]);
+        assert_eq!(DynStruct::with_truncated_tail(zero_sized, 0).tail.len(), 0);
+    }
+
+    #[test]
+    fn with_extended_tail_panicking_clone() {
+        use std::rc::Rc;
+
+        struct Bomb(Rc<()>, bool);
+        impl Clone for Bomb {
+            fn clone(&self) -> Self {
+                assert!(!self.1, "boom");
+                Bomb(self.0.clone(), self.1)
+            }
+        }
+
+        let counter = Rc::new(());
+        let value = DynStruct::new(counter.clone(), vec![Bomb(counter.clone(), false)]);
+        let extra = [Bomb(counter.clone(), false), Bomb(counter.clone(), true)];
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            DynStruct::with_extended_tail(value, &extra)
+        }));
+        assert!(result.is_err());
+        drop(extra);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn with_extended_tail_iter() {
+        use std::rc::Rc;
+        use std::string::ToString;
+
+        let value = DynStruct::new(0u8, vec![String::from("a")]);
+        let value = DynStruct::with_extended_tail_iter(value, (0..20u8).map(|i| i.to_string()));
+        assert_eq!(value.tail.len(), 21);
+        assert_eq!(value.tail[0], "a");
+        assert_eq!(value.tail[20], "19");
+        let value = DynStruct::with_extended_tail_iter(value, Vec::new());
+        assert_eq!(value.tail.len(), 21);
+
+        // A panicking iterator drops the header, the old elements and the new ones written so far.
+        let counter = Rc::new(());
+        let value = DynStruct::new(counter.clone(), vec![counter.clone()]);
+        let extra = (0..3).map(|i| {
+            assert!(i < 2, "boom");
+            counter.clone()
+        });
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            DynStruct::with_extended_tail_iter(value, extra)
+        }));
+        assert!(result.is_err());
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "got fewer items than expected")]
+    fn with_extended_tail_iter_too_short() {
+        struct Lying(usize);
+        impl Iterator for Lying {
+            type Item = u32;
+            fn next(&mut self) -> Option<u32> {
+                None
+            }
+        }
+        impl ExactSizeIterator for Lying {
+            fn len(&self) -> usize {
+                self.0
+            }
+        }
+
+        DynStruct::with_extended_tail_iter(DynStruct::new(1u32, [2u32]), Lying(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "size of `DynStruct` exceeds `isize::MAX`")]
+    fn new_overflow() {
+        struct Lying(usize);
+        impl Iterator for Lying {
+            type Item = u32;
+            fn next(&mut self) -> Option<u32> {
+                None
+            }
+        }
+        impl ExactSizeIterator for Lying {
+            fn len(&self) -> usize {
+                self.0
+            }
+        }
+
+        DynStruct::new(1u32, Lying(usize::MAX / 4 + 1));
+    }
+
+    #[test]
+    #[cfg(feature = "layout-tracking")]
+    #[cfg_attr(miri, ignore)] // Miri reports the huge allocation as exhausting its resources.
+    fn failed_reallocation_keeps_layout() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let (ptr, len) = DynStruct::into_raw_parts(DynStruct::new(1u32, [2u8, 3, 4]));
+        let (old, _) = layout::for_len::<u8>(Layout::new::<u32>(), len).unwrap();
+        let result = unsafe {
+            reallocate(
+                ptr.cast::<u8>(),
+                old,
+                Layout::new::<u32>(),
+                Layout::new::<u8>(),
+                isize::MAX as usize / 2,
+            )
+        };
+        assert!(matches!(result, Err(TryNewError::AllocError { .. })));
+
+        // The value is still in use, so freeing it with the wrong layout is caught.
+        let result = catch_unwind(AssertUnwindSafe(|| unsafe {
+            core::mem::ManuallyDrop::new(DynStruct::<u64, u8>::from_raw_parts(ptr, len))
+        }));
+        assert!(result.is_err());
+        let value = unsafe { DynStruct::<u32, u8>::from_raw_parts(ptr, len) };
+        assert_eq!(&value.tail, &[2, 3, 4]);
+    }
 }
@@ -0,0 +1,222 @@
+//! The `rayon` feature: building the tail of a value in parallel, directly in its allocation.
+//!
+//! This works just like `collect_into_vec` of `rayon`: the tail is split into disjoint parts along
+//! with the iterator, and every part is written by a [`Written`], which owns the elements it wrote so
+//! far and drops them if a worker panics. Adjacent parts are merged again as the workers finish, so
+//! that at the end a single `Written` owns the whole tail.
+
+use core::alloc::Layout;
+use core::marker::PhantomData;
+
+use rayon::iter::plumbing::{Consumer, Folder, Reducer};
+use rayon::iter::IndexedParallelIterator;
+
+use crate::{BoxWriter, Global, TryNewError};
+
+/// See [`DynStruct::try_from_par_iter`](crate::DynStruct::try_from_par_iter).
+pub(crate) fn from_par_iter<Header, Tail, I>(
+    header: Header,
+    tail: I,
+) -> Result<*mut [()], TryNewError>
+where
+    I: IndexedParallelIterator<Item = Tail>,
+    Tail: Send,
+{
+    let len = tail.len();
+    let mut writer = unsafe {
+        BoxWriter::<Header, Tail>::new(header, Layout::new::<Header>(), len, false, Global)?
+    };
+
+    let written = tail.drive(Consume {
+        start: writer.tail(),
+        len,
+        _marker: PhantomData,
+    });
+
+    // The parts are only merged if they are adjacent, so `written` always starts at the beginning
+    // of the tail. The writer takes over its elements, and drops them if there are too few.
+    debug_assert_eq!(written.start, writer.tail());
+    writer.written = written.len;
+    core::mem::forget(written);
+    if writer.written != len {
+        panic!(
+            "got fewer items than expected ({} instead of {}). Probable bug in \
+             `IndexedParallelIterator` for `{}`?",
+            writer.written,
+            len,
+            core::any::type_name::<I>(),
+        );
+    }
+    Ok(writer.finish::<I>())
+}
+
+/// Writes items to `len` uninitialized elements starting at `start`.
+struct Consume<'a, T> {
+    start: *mut T,
+    len: usize,
+    _marker: PhantomData<&'a mut [T]>,
+}
+
+// SAFETY: the consumer has exclusive access to its elements, just like a `&mut [T]`.
+unsafe impl<T: Send> Send for Consume<'_, T> {}
+
+/// The first `len` of `capacity` elements starting at `start`, which have been written and are
+/// owned by this.
+struct Written<'a, T> {
+    start: *mut T,
+    capacity: usize,
+    len: usize,
+    _marker: PhantomData<&'a mut [T]>,
+}
+
+// SAFETY: see `Consume`.
+unsafe impl<T: Send> Send for Written<'_, T> {}
+
+impl<T> Drop for Written<'_, T> {
+    fn drop(&mut self) {
+        let written = core::ptr::slice_from_raw_parts_mut(self.start, self.len);
+        unsafe { core::ptr::drop_in_place(written) };
+    }
+}
+
+impl<'a, T: Send + 'a> Consumer<T> for Consume<'a, T> {
+    type Folder = Written<'a, T>;
+    type Reducer = Merge;
+    type Result = Written<'a, T>;
+
+    fn split_at(self, index: usize) -> (Self, Self, Merge) {
+        assert!(index <= self.len, "split past the end of the tail");
+        let right = Consume {
+            start: self.start.wrapping_add(index),
+            len: self.len - index,
+            _marker: PhantomData,
+        };
+        let left = Consume {
+            start: self.start,
+            len: index,
+            _marker: PhantomData,
+        };
+        (left, right, Merge)
+    }
+
+    fn into_folder(self) -> Written<'a, T> {
+        Written {
+            start: self.start,
+            capacity: self.len,
+            len: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    fn full(&self) -> bool {
+        false
+    }
+}
+
+impl<'a, T: 'a> Folder<T> for Written<'a, T> {
+    type Result = Self;
+
+    fn consume(mut self, item: T) -> Self {
+        assert!(
+            self.len < self.capacity,
+            "got more items than expected. Probable bug in `IndexedParallelIterator`?"
+        );
+        unsafe { self.start.add(self.len).write(item) };
+        self.len += 1;
+        self
+    }
+
+    fn complete(self) -> Self {
+        self
+    }
+
+    fn full(&self) -> bool {
+        false
+    }
+}
+
+/// Merges the elements written by two adjacent parts.
+struct Merge;
+
+impl<'a, T> Reducer<Written<'a, T>> for Merge {
+    fn reduce(self, mut left: Written<'a, T>, right: Written<'a, T>) -> Written<'a, T> {
+        // If the left part is missing elements, the right one cannot be merged, and its elements
+        // are dropped here. The length of the tail is checked once all parts are merged.
+        if left.start.wrapping_add(left.len) == right.start {
+            left.len += right.len;
+            left.capacity += right.capacity;
+            core::mem::forget(right);
+        }
+        left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DynStruct;
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use rayon::prelude::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn values() {
+        let square = |i: u32| u64::from(i) * u64::from(i);
+        let value = DynStruct::from_par_iter(1u8, (0..10_000).into_par_iter().map(square));
+        assert_eq!(value.header, 1);
+        assert_eq!(
+            &value.tail,
+            &(0..10_000).map(square).collect::<Vec<_>>()[..]
+        );
+
+        let strings = DynStruct::from_par_iter(
+            String::from("header"),
+            (0..100).into_par_iter().map(|i: u32| i.to_string()),
+        );
+        assert_eq!(strings.tail[42], "42");
+
+        let empty = DynStruct::from_par_iter((), Vec::<u32>::new().into_par_iter());
+        assert!(empty.is_empty());
+        let zero_sized = DynStruct::from_par_iter(0u64, rayon::iter::repeat_n((), 1000));
+        assert_eq!(zero_sized.len(), 1000);
+    }
+
+    /// Counts how many times it was dropped.
+    struct Guard(Arc<AtomicUsize>);
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn drops() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let value = DynStruct::from_par_iter(
+            Guard(dropped.clone()),
+            (0..1000).into_par_iter().map(|_| Guard(dropped.clone())),
+        );
+        assert_eq!(dropped.load(Ordering::Relaxed), 0);
+        drop(value);
+        assert_eq!(dropped.load(Ordering::Relaxed), 1001);
+
+        // A panicking worker drops every element which was written, and the header.
+        let created = Arc::new(AtomicUsize::new(0));
+        dropped.store(0, Ordering::Relaxed);
+        let result = std::panic::catch_unwind(|| {
+            let tail = (0..1000).into_par_iter().map(|i| {
+                assert!(i != 500, "boom");
+                created.fetch_add(1, Ordering::Relaxed);
+                Guard(dropped.clone())
+            });
+            DynStruct::from_par_iter(Guard(dropped.clone()), tail)
+        });
+        assert!(result.is_err());
+        assert_eq!(
+            dropped.load(Ordering::Relaxed),
+            created.load(Ordering::Relaxed) + 1
+        );
+    }
+}
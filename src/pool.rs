@@ -0,0 +1,341 @@
+//! [`DynStructPool`]: reuses the allocations of dropped values with the same tail length.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::cell::RefCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+use crate::{tracking, BoxWriter, DynStruct, Global, RawAlloc, TryNewError, Zeroable};
+
+/// A cache of allocations for `DynStruct<Header, Tail>`, grouped by the length of their tail.
+///
+/// [`acquire`](Self::acquire) hands out a [`PooledDynStruct`], which returns its allocation to the
+/// pool when it is dropped, so that the next value with the same tail length reuses it instead of
+/// going through the allocator. New allocations are only made while no allocation of the requested
+/// length is free.
+///
+/// The elements are restricted to `Copy` types, which have nothing to drop, and to [`Zeroable`]
+/// types, so that the tail of a new allocation can start out as zeros. A reused tail keeps the
+/// elements which were last written to it, unless it is acquired with
+/// [`acquire_zeroed`](Self::acquire_zeroed). The header is dropped as usual when the handle is.
+///
+/// The pool is not `Sync`, since handles return their allocations through a shared reference to
+/// it. Every thread can use a pool of its own instead.
+///
+/// ```
+/// use dyn_struct::DynStructPool;
+///
+/// let pool = DynStructPool::<u32, u8>::new();
+/// let mut frame = pool.acquire(1, 1500);
+/// frame.tail[0] = 0xff;
+/// let first = frame.tail.as_ptr();
+/// drop(frame);
+///
+/// // The next frame of the same length reuses the allocation.
+/// let frame = pool.acquire(2, 1500);
+/// assert_eq!(frame.tail.as_ptr(), first);
+/// assert_eq!(frame.header, 2);
+/// ```
+pub struct DynStructPool<Header, Tail> {
+    /// The free allocations, by the length of their tail.
+    free: RefCell<BTreeMap<usize, Vec<NonNull<u8>>>>,
+    _marker: PhantomData<DynStruct<Header, Tail>>,
+}
+
+// SAFETY: the free allocations hold no values, and the pool only creates them through handles which
+// borrow it.
+unsafe impl<Header: Send, Tail: Send> Send for DynStructPool<Header, Tail> {}
+
+/// A value which was handed out by a [`DynStructPool`], and returns its allocation to it when
+/// dropped.
+pub struct PooledDynStruct<'p, Header, Tail> {
+    ptr: NonNull<DynStruct<Header, Tail>>,
+    pool: &'p DynStructPool<Header, Tail>,
+}
+
+// SAFETY: sharing the handle only shares the value. It cannot be sent, since its pool cannot be
+// shared.
+unsafe impl<Header: Sync, Tail: Sync> Sync for PooledDynStruct<'_, Header, Tail> {}
+
+impl<Header, Tail: Copy + Zeroable> DynStructPool<Header, Tail> {
+    /// Creates an empty pool.
+    #[inline]
+    pub const fn new() -> Self {
+        DynStructPool {
+            free: RefCell::new(BTreeMap::new()),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a value with the given header and `len` elements in its tail, reusing a free
+    /// allocation if there is one.
+    ///
+    /// The tail holds whatever was last written to that allocation, or zeros if it is new.
+    #[inline]
+    pub fn acquire(&self, header: Header, len: usize) -> PooledDynStruct<'_, Header, Tail> {
+        crate::__private::unwrap(self.acquire_with(header, len, false))
+    }
+
+    /// Like [`DynStructPool::acquire`], but sets every element of the tail to zero.
+    #[inline]
+    pub fn acquire_zeroed(&self, header: Header, len: usize) -> PooledDynStruct<'_, Header, Tail> {
+        crate::__private::unwrap(self.acquire_with(header, len, true))
+    }
+
+    /// Like [`DynStructPool::acquire`], but returns an error instead of panicking or aborting if a
+    /// new value cannot be allocated.
+    #[inline]
+    pub fn try_acquire(
+        &self,
+        header: Header,
+        len: usize,
+    ) -> Result<PooledDynStruct<'_, Header, Tail>, TryNewError> {
+        self.acquire_with(header, len, false)
+    }
+
+    /// Like [`DynStructPool::acquire_zeroed`], but returns an error instead of panicking or
+    /// aborting if a new value cannot be allocated.
+    #[inline]
+    pub fn try_acquire_zeroed(
+        &self,
+        header: Header,
+        len: usize,
+    ) -> Result<PooledDynStruct<'_, Header, Tail>, TryNewError> {
+        self.acquire_with(header, len, true)
+    }
+
+    fn acquire_with(
+        &self,
+        header: Header,
+        len: usize,
+        zeroed: bool,
+    ) -> Result<PooledDynStruct<'_, Header, Tail>, TryNewError> {
+        let free = self.free.borrow_mut().get_mut(&len).and_then(Vec::pop);
+        let ptr = match free {
+            Some(raw) => unsafe {
+                let ptr = core::ptr::slice_from_raw_parts_mut(raw.as_ptr().cast::<()>(), len)
+                    as *mut DynStruct<Header, Tail>;
+                core::ptr::addr_of_mut!((*ptr).header).write(header);
+                if zeroed {
+                    // `Tail` is `Zeroable`, so this leaves valid elements behind.
+                    let tail = core::ptr::addr_of_mut!((*ptr).tail).cast::<Tail>();
+                    tail.write_bytes(0, len);
+                }
+                ptr
+            },
+            None => unsafe {
+                let ptr = BoxWriter::<Header, Tail>::zeroed(
+                    header,
+                    Layout::new::<Header>(),
+                    len,
+                    Global,
+                )?;
+                // The value is freed by the pool, not through a `Box`.
+                tracking::forget(ptr.cast::<u8>());
+                ptr as *mut DynStruct<Header, Tail>
+            },
+        };
+        Ok(PooledDynStruct {
+            ptr: unsafe { NonNull::new_unchecked(ptr) },
+            pool: self,
+        })
+    }
+}
+
+impl<Header, Tail> DynStructPool<Header, Tail> {
+    /// Returns the number of free allocations in the pool.
+    pub fn free_len(&self) -> usize {
+        self.free.borrow().values().map(Vec::len).sum()
+    }
+
+    /// Frees all allocations in the pool.
+    ///
+    /// Handles which are still in use return their allocations to the pool as usual.
+    pub fn clear(&self) {
+        let free = core::mem::take(&mut *self.free.borrow_mut());
+        for (len, allocations) in free {
+            // This cannot fail, since a value with the same length was allocated before.
+            let layout = crate::layout::for_len::<Tail>(Layout::new::<Header>(), len);
+            let (layout, _) = crate::__private::unwrap(layout);
+            for raw in allocations {
+                unsafe { Global.deallocate(raw.as_ptr(), layout) };
+            }
+        }
+    }
+}
+
+impl<Header, Tail: Copy + Zeroable> Default for DynStructPool<Header, Tail> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Header, Tail> Drop for DynStructPool<Header, Tail> {
+    #[inline]
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<Header, Tail> fmt::Debug for DynStructPool<Header, Tail> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let free = self.free.borrow();
+        let mut map = f.debug_map();
+        for (len, allocations) in free.iter() {
+            map.entry(len, &allocations.len());
+        }
+        map.finish()
+    }
+}
+
+impl<Header, Tail> PooledDynStruct<'_, Header, Tail> {
+    /// Moves the value out of the pool into a `Box`, without copying it. Its allocation is not
+    /// returned to the pool afterwards.
+    pub fn into_box(this: Self) -> Box<DynStruct<Header, Tail>> {
+        let this = ManuallyDrop::new(this);
+        let ptr = this.ptr.as_ptr();
+        unsafe {
+            tracking::record(ptr.cast::<u8>(), Layout::for_value(&*ptr));
+            Box::from_raw(ptr)
+        }
+    }
+}
+
+impl<Header, Tail> Drop for PooledDynStruct<'_, Header, Tail> {
+    fn drop(&mut self) {
+        /// Returns the allocation to the pool, even if dropping the header panics.
+        struct Recycle<'a> {
+            free: &'a RefCell<BTreeMap<usize, Vec<NonNull<u8>>>>,
+            raw: NonNull<u8>,
+            len: usize,
+        }
+
+        impl Drop for Recycle<'_> {
+            fn drop(&mut self) {
+                let mut free = self.free.borrow_mut();
+                free.entry(self.len).or_default().push(self.raw);
+            }
+        }
+
+        let value = self.ptr.as_ptr();
+        unsafe {
+            let _recycle = Recycle {
+                free: &self.pool.free,
+                raw: self.ptr.cast::<u8>(),
+                len: (*value).len(),
+            };
+            // The elements are `Copy`, so only the header has to be dropped.
+            core::ptr::drop_in_place(core::ptr::addr_of_mut!((*value).header));
+        }
+    }
+}
+
+impl<Header, Tail> Deref for PooledDynStruct<'_, Header, Tail> {
+    type Target = DynStruct<Header, Tail>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<Header, Tail> DerefMut for PooledDynStruct<'_, Header, Tail> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<Header: fmt::Debug, Tail: fmt::Debug> fmt::Debug for PooledDynStruct<'_, Header, Tail> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use std::rc::Rc;
+
+    #[test]
+    fn reuse() {
+        let pool = DynStructPool::<u8, u32>::new();
+        let mut value = pool.acquire(1, 4);
+        assert_eq!(value.header, 1);
+        assert_eq!(&value.tail, &[0; 4]);
+        value.tail.copy_from_slice(&[1, 2, 3, 4]);
+        let first = &*value as *const DynStruct<u8, u32>;
+        drop(value);
+        assert_eq!(pool.free_len(), 1);
+
+        // Only allocations with the same tail length are reused.
+        let longer = pool.acquire(2, 5);
+        assert_eq!(&longer.tail, &[0; 5]);
+        assert_eq!(pool.free_len(), 1);
+
+        let reused = pool.acquire(3, 4);
+        assert_eq!(&*reused as *const DynStruct<u8, u32>, first);
+        assert_eq!(reused.header, 3);
+        assert_eq!(&reused.tail, &[1, 2, 3, 4]);
+        let other = pool.acquire(4, 4);
+        assert_ne!(&*other as *const DynStruct<u8, u32>, first);
+        drop((reused, other));
+
+        let zeroed = pool.acquire_zeroed(5, 4);
+        assert_eq!(&zeroed.tail, &[0; 4]);
+        assert_eq!(zeroed.header, 5);
+        drop((zeroed, longer));
+
+        assert_eq!(pool.free_len(), 3);
+        pool.clear();
+        assert_eq!(pool.free_len(), 0);
+    }
+
+    #[test]
+    fn into_box() {
+        let pool = DynStructPool::<String, u8>::new();
+        let mut value = pool.acquire(String::from("header"), 3);
+        value.tail[2] = 7;
+        let boxed = PooledDynStruct::into_box(value);
+        assert_eq!(*boxed, *DynStruct::new(String::from("header"), [0, 0, 7]));
+        assert_eq!(pool.free_len(), 0);
+
+        let empty = DynStructPool::<(), ()>::new();
+        drop([empty.acquire((), 0), empty.acquire((), 10)]);
+        assert_eq!(empty.acquire((), 10).len(), 10);
+        assert_eq!(empty.free_len(), 2);
+    }
+
+    #[test]
+    fn drops() {
+        let counter = Rc::new(());
+        let pool = DynStructPool::<Rc<()>, u64>::new();
+        let value = pool.acquire(counter.clone(), 8);
+        assert_eq!(Rc::strong_count(&counter), 2);
+        drop(value);
+        assert_eq!(Rc::strong_count(&counter), 1);
+
+        // A panicking header still returns the allocation.
+        struct Bomb(bool);
+        impl Drop for Bomb {
+            fn drop(&mut self) {
+                assert!(!self.0, "boom");
+            }
+        }
+        let pool = DynStructPool::<Bomb, u64>::new();
+        let value = pool.acquire(Bomb(true), 8);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(value)));
+        assert!(result.is_err());
+        assert_eq!(pool.free_len(), 1);
+        drop(pool.acquire(Bomb(false), 8));
+        assert_eq!(pool.free_len(), 1);
+    }
+}
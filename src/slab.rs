@@ -0,0 +1,477 @@
+//! [`DynSlab`]: many `DynStruct`s in a single growing allocation.
+
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Index, IndexMut};
+
+use crate::{layout, tracking, BoxWriter, DynStruct, RawAlloc, TryNewError};
+
+/// Records of a `Header` followed by a slice of `Tail`s, stored back to back in one allocation
+/// instead of a `Box` each.
+///
+/// Every record is aligned just like a `Box<DynStruct<Header, Tail>>` would be, and can be looked
+/// up as a `DynStruct` with the [`SlabHandle`] returned by [`DynSlab::push`]. When the buffer
+/// grows, it is reallocated and the records may move: references into the slab cannot be held
+/// across a `push` anyway, but raw pointers to records are invalidated by it. Handles stay valid
+/// for as long as the slab exists.
+///
+/// All records are dropped in insertion order when the slab is.
+pub struct DynSlab<Header, Tail> {
+    /// The start of the buffer, or a dangling pointer while nothing has been allocated.
+    raw: *mut u8,
+    /// The size of the buffer in bytes.
+    capacity: usize,
+    /// The number of bytes taken by records, including the padding between them.
+    bytes: usize,
+    /// The offset of each record in the buffer, and the length of its tail.
+    records: Vec<(usize, usize)>,
+    _marker: PhantomData<(Header, Tail)>,
+}
+
+/// Identifies a record in a [`DynSlab`], as the position at which it was pushed.
+///
+/// A handle from another slab still refers to a record with the same position, or to none at all.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SlabHandle(usize);
+
+impl SlabHandle {
+    /// Returns the position of the record among all records of the slab.
+    #[inline]
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+// SAFETY: the slab owns its records like a `Vec` owns its elements.
+unsafe impl<Header: Send, Tail: Send> Send for DynSlab<Header, Tail> {}
+unsafe impl<Header: Sync, Tail: Sync> Sync for DynSlab<Header, Tail> {}
+
+impl<Header, Tail> DynSlab<Header, Tail> {
+    /// The alignment of every record, and of the buffer.
+    const ALIGN: usize = {
+        let (header, tail) = (
+            Layout::new::<Header>().align(),
+            Layout::new::<Tail>().align(),
+        );
+        if header > tail {
+            header
+        } else {
+            tail
+        }
+    };
+
+    /// Creates an empty slab, without allocating.
+    #[inline]
+    pub const fn new() -> Self {
+        DynSlab {
+            raw: core::ptr::without_provenance_mut(Self::ALIGN),
+            capacity: 0,
+            bytes: 0,
+            records: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates an empty slab with room for `bytes` bytes of records.
+    pub fn with_capacity(bytes: usize) -> Self {
+        let mut slab = Self::new();
+        slab.reserve(bytes);
+        slab
+    }
+
+    /// Appends a record, with the elements of `tail` in its tail, and returns its handle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer would exceed `isize::MAX` bytes, and aborts if it cannot be allocated.
+    pub fn push<I>(&mut self, header: Header, tail: I) -> SlabHandle
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        crate::__private::unwrap(self.try_push(header, tail))
+    }
+
+    /// Like [`DynSlab::push`], but returns an error instead of panicking or aborting if the buffer
+    /// cannot grow. In that case both `header` and the iterator are dropped.
+    ///
+    /// If the iterator panics, the header and the elements written so far are dropped, and the
+    /// slab is left as it was.
+    pub fn try_push<I>(&mut self, header: Header, tail: I) -> Result<SlabHandle, TryNewError>
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let tail = tail.into_iter();
+        let header_layout = Layout::new::<Header>();
+        let (layout, _) = layout::for_len::<Tail>(header_layout, tail.len())?;
+
+        // `bytes` is always a multiple of the alignment, since every record is padded to it.
+        let offset = self.bytes;
+        let end = offset
+            .checked_add(layout.size())
+            .ok_or(TryNewError::CapacityOverflow)?;
+        self.try_reserve(end - self.bytes)?;
+
+        let slot = InSlab {
+            raw: self.raw.wrapping_add(offset),
+        };
+        let ptr = unsafe {
+            BoxWriter::<Header, Tail, InSlab>::from_iter(header, header_layout, tail, slot)?
+        };
+        // The record is freed with the buffer, not through a `Box`.
+        tracking::forget(ptr.cast::<u8>());
+
+        self.records.push((offset, ptr.len()));
+        self.bytes = end;
+        Ok(SlabHandle(self.records.len() - 1))
+    }
+
+    /// Makes sure that `additional` more bytes of records fit into the buffer, growing it if
+    /// needed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer would exceed `isize::MAX` bytes, and aborts if it cannot be allocated.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        crate::__private::unwrap(self.try_reserve(additional))
+    }
+
+    /// Like [`DynSlab::reserve`], but returns an error instead of panicking or aborting if the
+    /// buffer cannot grow.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryNewError> {
+        let needed = self
+            .bytes
+            .checked_add(additional)
+            .ok_or(TryNewError::CapacityOverflow)?;
+        if needed <= self.capacity {
+            return Ok(());
+        }
+
+        // Grow geometrically, so that pushing many records takes amortized constant time.
+        let capacity = needed.max(self.capacity.saturating_mul(2)).max(64);
+        let layout = Layout::from_size_align(capacity, Self::ALIGN)
+            .or_else(|_| Layout::from_size_align(needed, Self::ALIGN))
+            .map_err(|_| TryNewError::CapacityOverflow)?;
+        let raw = unsafe {
+            if self.capacity == 0 {
+                alloc::alloc::alloc(layout)
+            } else {
+                let old = Layout::from_size_align_unchecked(self.capacity, Self::ALIGN);
+                alloc::alloc::realloc(self.raw, old, layout.size())
+            }
+        };
+        if raw.is_null() {
+            return Err(TryNewError::AllocError { layout });
+        }
+        self.raw = raw;
+        self.capacity = layout.size();
+        Ok(())
+    }
+
+    /// Returns the record with the given handle, or `None` if there is none.
+    #[inline]
+    pub fn get(&self, handle: SlabHandle) -> Option<&DynStruct<Header, Tail>> {
+        let &(offset, len) = self.records.get(handle.0)?;
+        Some(unsafe { &*self.record(offset, len) })
+    }
+
+    /// Returns the record with the given handle mutably, or `None` if there is none.
+    #[inline]
+    pub fn get_mut(&mut self, handle: SlabHandle) -> Option<&mut DynStruct<Header, Tail>> {
+        let &(offset, len) = self.records.get(handle.0)?;
+        Some(unsafe { &mut *self.record(offset, len) })
+    }
+
+    /// Returns the number of records.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns `true` if there are no records.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Returns the number of bytes taken by the records, including their padding.
+    #[inline]
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Returns the size of the buffer in bytes.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Iterates over the records in the order they were pushed.
+    pub fn iter(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = &DynStruct<Header, Tail>> + ExactSizeIterator {
+        self.records
+            .iter()
+            .map(move |&(offset, len)| unsafe { &*self.record(offset, len) })
+    }
+
+    /// Returns a pointer to the record at `offset`, with `len` elements in its tail.
+    #[inline]
+    fn record(&self, offset: usize, len: usize) -> *mut DynStruct<Header, Tail> {
+        let start = self.raw.wrapping_add(offset).cast::<()>();
+        core::ptr::slice_from_raw_parts_mut(start, len) as *mut DynStruct<Header, Tail>
+    }
+}
+
+impl<Header, Tail> Drop for DynSlab<Header, Tail> {
+    fn drop(&mut self) {
+        /// Drops the records from `next` on if dropping one of them panics, and frees the buffer.
+        struct Guard<'a, Header, Tail> {
+            slab: &'a mut DynSlab<Header, Tail>,
+            next: usize,
+        }
+
+        impl<Header, Tail> Drop for Guard<'_, Header, Tail> {
+            fn drop(&mut self) {
+                while let Some(&(offset, len)) = self.slab.records.get(self.next) {
+                    self.next += 1;
+                    unsafe { core::ptr::drop_in_place(self.slab.record(offset, len)) };
+                }
+                if self.slab.capacity != 0 {
+                    unsafe {
+                        let layout = Layout::from_size_align_unchecked(
+                            self.slab.capacity,
+                            DynSlab::<Header, Tail>::ALIGN,
+                        );
+                        alloc::alloc::dealloc(self.slab.raw, layout);
+                    }
+                }
+            }
+        }
+
+        let mut guard = Guard {
+            slab: self,
+            next: 0,
+        };
+        while let Some(&(offset, len)) = guard.slab.records.get(guard.next) {
+            guard.next += 1;
+            unsafe { core::ptr::drop_in_place(guard.slab.record(offset, len)) };
+        }
+    }
+}
+
+impl<Header, Tail> Default for DynSlab<Header, Tail> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Header, Tail> Index<SlabHandle> for DynSlab<Header, Tail> {
+    type Output = DynStruct<Header, Tail>;
+
+    #[track_caller]
+    fn index(&self, handle: SlabHandle) -> &Self::Output {
+        match self.get(handle) {
+            Some(record) => record,
+            None => panic!(
+                "no record at index {} in a slab of {}",
+                handle.0,
+                self.len()
+            ),
+        }
+    }
+}
+
+impl<Header, Tail> IndexMut<SlabHandle> for DynSlab<Header, Tail> {
+    #[track_caller]
+    fn index_mut(&mut self, handle: SlabHandle) -> &mut Self::Output {
+        let len = self.len();
+        match self.get_mut(handle) {
+            Some(record) => record,
+            None => panic!("no record at index {} in a slab of {}", handle.0, len),
+        }
+    }
+}
+
+impl<Header: fmt::Debug, Tail: fmt::Debug> fmt::Debug for DynSlab<Header, Tail> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Space reserved in a slab for one record, which is handed out as its allocation.
+struct InSlab {
+    raw: *mut u8,
+}
+
+unsafe impl RawAlloc for InSlab {
+    fn allocate(&self, _: Layout, zeroed: bool) -> Result<*mut u8, TryNewError> {
+        debug_assert!(!zeroed);
+        Ok(self.raw)
+    }
+
+    unsafe fn deallocate(&self, _: *mut u8, _: Layout) {
+        // The space is still reserved in the buffer, and is reused by the next record.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use alloc::vec;
+    use std::rc::Rc;
+
+    #[test]
+    fn records() {
+        let mut slab = DynSlab::<u8, u64>::new();
+        assert!(slab.is_empty());
+        assert_eq!(slab.capacity(), 0);
+        slab.reserve(100);
+        assert!(slab.capacity() >= 100);
+
+        let handles: Vec<SlabHandle> = (0..50u8)
+            .map(|i| slab.push(i, (0..u32::from(i % 4)).map(|j| u64::from(j) * 10)))
+            .collect();
+        assert_eq!(slab.len(), 50);
+
+        for (i, &handle) in handles.iter().enumerate() {
+            let record = &slab[handle];
+            assert_eq!(usize::from(record.header), i);
+            assert_eq!(record.tail.len(), i % 4);
+            assert_eq!(
+                (record as *const DynStruct<u8, u64>).cast::<u8>() as usize % 8,
+                0
+            );
+            assert_eq!(
+                core::mem::size_of_val(record),
+                DynStruct::<u8, u64>::size_for_len(i % 4)
+            );
+        }
+        let expected: usize = (0..50)
+            .map(|i| DynStruct::<u8, u64>::size_for_len(i % 4))
+            .sum();
+        assert_eq!(slab.bytes(), expected);
+        assert!(slab.capacity() >= slab.bytes());
+
+        slab[handles[3]].tail[2] = 7;
+        assert_eq!(&slab.get(handles[3]).unwrap().tail, &[0, 10, 7]);
+        assert!(slab.get(SlabHandle(50)).is_none());
+
+        let headers: Vec<u8> = slab.iter().map(|record| record.header).collect();
+        assert_eq!(headers, (0..50).collect::<Vec<_>>());
+        assert_eq!(slab.iter().next_back().unwrap().header, 49);
+    }
+
+    #[test]
+    fn zero_sized() {
+        let mut slab = DynSlab::<(), ()>::new();
+        let handle = slab.push((), [(), ()]);
+        slab.push((), []);
+        assert_eq!(slab[handle].tail.len(), 2);
+        assert_eq!((slab.len(), slab.bytes(), slab.capacity()), (2, 0, 0));
+    }
+
+    #[test]
+    fn drops() {
+        let counter = Rc::new(());
+        {
+            let mut slab = DynSlab::new();
+            for i in 0..20 {
+                slab.push(
+                    (counter.clone(), String::from("header")),
+                    vec![counter.clone(); i],
+                );
+            }
+            assert_eq!(Rc::strong_count(&counter), 1 + 20 + (0..20).sum::<usize>());
+        }
+        assert_eq!(Rc::strong_count(&counter), 1);
+
+        // A panicking iterator leaves the slab as it was.
+        let mut slab = DynSlab::new();
+        slab.push(counter.clone(), [counter.clone()]);
+        let bytes = slab.bytes();
+        let tail = (0..3).map(|i| {
+            assert!(i < 2, "boom");
+            counter.clone()
+        });
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            slab.push(counter.clone(), tail);
+        }));
+        assert!(result.is_err());
+        assert_eq!((slab.len(), slab.bytes()), (1, bytes));
+        assert_eq!(Rc::strong_count(&counter), 3);
+        let handle = slab.push(counter.clone(), []);
+        assert_eq!(handle.index(), 1);
+        drop(slab);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn panicking_drop() {
+        struct Bomb {
+            _counter: Rc<()>,
+            armed: bool,
+        }
+        impl Drop for Bomb {
+            fn drop(&mut self) {
+                assert!(!self.armed, "boom");
+            }
+        }
+
+        let counter = Rc::new(());
+        let mut slab = DynSlab::new();
+        slab.push(
+            0,
+            [Bomb {
+                _counter: counter.clone(),
+                armed: false,
+            }],
+        );
+        slab.push(
+            1,
+            [Bomb {
+                _counter: counter.clone(),
+                armed: true,
+            }],
+        );
+        slab.push(
+            2,
+            [Bomb {
+                _counter: counter.clone(),
+                armed: false,
+            }],
+        );
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(slab)));
+        assert!(result.is_err());
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn overflow() {
+        let mut slab = DynSlab::<u8, u8>::new();
+        slab.push(1, [2]);
+        assert_eq!(
+            slab.try_reserve(usize::MAX),
+            Err(TryNewError::CapacityOverflow)
+        );
+        assert_eq!(
+            slab.try_reserve(isize::MAX as usize),
+            Err(TryNewError::CapacityOverflow)
+        );
+        assert_eq!(&slab[SlabHandle(0)].tail, &[2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "size of `DynStruct` exceeds `isize::MAX`")]
+    fn reserve_overflow() {
+        let mut slab = DynSlab::<u8, u8>::new();
+        slab.push(1, [2]);
+        slab.reserve(usize::MAX);
+    }
+}
@@ -0,0 +1,331 @@
+//! [`SmallDynStruct`]: a `DynStruct` which stores short tails inline instead of allocating.
+
+use alloc::boxed::Box;
+use core::alloc::Layout;
+use core::fmt;
+use core::mem::{ManuallyDrop, MaybeUninit};
+use core::ops::{Deref, DerefMut};
+
+use crate::{tracking, BoxWriter, DynStruct, RawAlloc, TryNewError};
+
+/// Like a `Box<DynStruct<Header, Tail>>`, but keeps the value inline when its tail takes up at most
+/// `N` bytes.
+///
+/// Only longer tails are allocated, as a `Box`, so collections of mostly short values avoid most of
+/// their allocations, at the cost of every owner taking up the space of the largest inline value.
+/// Either way, the value is reached through [`Deref`], and a `Box` can be turned into a
+/// `SmallDynStruct` and back with `From`, which moves short values out of or into an allocation.
+///
+/// ```
+/// use dyn_struct::{DynStruct, SmallDynStruct};
+///
+/// let short = SmallDynStruct::<u32, u8, 16>::new(1, *b"inline");
+/// assert!(SmallDynStruct::is_inline(&short));
+/// assert_eq!(&short.tail, b"inline");
+///
+/// let long = SmallDynStruct::<u32, u8, 16>::new(2, [0; 100]);
+/// assert!(!SmallDynStruct::is_inline(&long));
+/// assert_eq!(long.len(), 100);
+/// ```
+pub struct SmallDynStruct<Header, Tail, const N: usize> {
+    repr: Repr<Header, Tail, N>,
+}
+
+enum Repr<Header, Tail, const N: usize> {
+    Inline {
+        value: Inline<Header, Tail, N>,
+        len: usize,
+    },
+    Boxed(Box<DynStruct<Header, Tail>>),
+}
+
+/// Room for a `DynStruct<Header, Tail>` with up to `N` bytes of tail.
+///
+/// Just like in a `DynStruct`, the header comes first and the tail starts at the next multiple of
+/// its alignment, so that a pointer to this, with the length of the tail as metadata, points to a
+/// valid value once it is written.
+#[repr(C)]
+struct Inline<Header, Tail, const N: usize> {
+    header: MaybeUninit<Header>,
+    _align: [Tail; 0],
+    tail: [MaybeUninit<u8>; N],
+}
+
+impl<Header, Tail, const N: usize> Inline<Header, Tail, N> {
+    const UNINIT: Self = Inline {
+        header: MaybeUninit::uninit(),
+        _align: [],
+        tail: [MaybeUninit::uninit(); N],
+    };
+}
+
+impl<Header, Tail, const N: usize> SmallDynStruct<Header, Tail, N> {
+    /// The number of elements which fit into the tail of an inline value.
+    pub const INLINE_LEN: usize = match N.checked_div(size_of::<Tail>()) {
+        Some(len) => len,
+        None => usize::MAX,
+    };
+
+    /// Creates a new value like [`DynStruct::new`], which is only allocated if its tail is longer
+    /// than [`INLINE_LEN`](Self::INLINE_LEN).
+    #[inline]
+    pub fn new<I>(header: Header, tail: I) -> Self
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        crate::__private::unwrap(Self::try_new(header, tail))
+    }
+
+    /// Like [`SmallDynStruct::new`], but returns an error instead of panicking or aborting if a
+    /// long value cannot be allocated.
+    pub fn try_new<I>(header: Header, tail: I) -> Result<Self, TryNewError>
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let tail = tail.into_iter();
+        if tail.len() > Self::INLINE_LEN {
+            return DynStruct::try_new(header, tail).map(Self::from_box_unchecked);
+        }
+
+        let mut value = Inline::<Header, Tail, N>::UNINIT;
+        let slot = InBuffer {
+            raw: &mut value as *mut Inline<Header, Tail, N> as *mut u8,
+        };
+        let ptr = unsafe {
+            BoxWriter::<Header, Tail, InBuffer>::from_iter(
+                header,
+                Layout::new::<Header>(),
+                tail,
+                slot,
+            )?
+        };
+        // The value is dropped by `Drop` below, not through a `Box`.
+        tracking::forget(ptr.cast::<u8>());
+        Ok(SmallDynStruct {
+            repr: Repr::Inline {
+                value,
+                len: ptr.len(),
+            },
+        })
+    }
+
+    /// Returns `true` if the value is stored inline, and `false` if it is allocated.
+    #[inline]
+    pub fn is_inline(this: &Self) -> bool {
+        matches!(this.repr, Repr::Inline { .. })
+    }
+
+    /// Moves the value into a `Box`, allocating it if it was stored inline.
+    pub fn into_box(this: Self) -> Box<DynStruct<Header, Tail>> {
+        let mut this = ManuallyDrop::new(this);
+        if let Repr::Boxed(value) = &this.repr {
+            // SAFETY: the original is never used again, since `Drop` does not run.
+            return unsafe { core::ptr::read(value) };
+        }
+        unsafe { crate::copy_into_box(this.as_ptr()) }
+    }
+
+    fn from_box_unchecked(value: Box<DynStruct<Header, Tail>>) -> Self {
+        SmallDynStruct {
+            repr: Repr::Boxed(value),
+        }
+    }
+
+    /// Returns a pointer to the value, with the length of its tail as metadata.
+    #[inline]
+    fn as_ptr(&mut self) -> *mut DynStruct<Header, Tail> {
+        match &mut self.repr {
+            Repr::Inline { value, len } => {
+                let raw = value as *mut Inline<Header, Tail, N> as *mut ();
+                core::ptr::slice_from_raw_parts_mut(raw, *len) as *mut DynStruct<Header, Tail>
+            }
+            Repr::Boxed(value) => &mut **value,
+        }
+    }
+}
+
+impl<Header, Tail, const N: usize> Drop for SmallDynStruct<Header, Tail, N> {
+    fn drop(&mut self) {
+        if let Repr::Inline { .. } = self.repr {
+            // SAFETY: the value was written by `try_new` or `from`, and is only dropped here.
+            unsafe { core::ptr::drop_in_place(self.as_ptr()) };
+        }
+        // A `Box` drops its value by itself.
+    }
+}
+
+impl<Header, Tail, const N: usize> Deref for SmallDynStruct<Header, Tail, N> {
+    type Target = DynStruct<Header, Tail>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        match &self.repr {
+            Repr::Inline { value, len } => {
+                let raw = value as *const Inline<Header, Tail, N> as *const ();
+                let ptr = core::ptr::slice_from_raw_parts(raw, *len);
+                unsafe { &*(ptr as *const DynStruct<Header, Tail>) }
+            }
+            Repr::Boxed(value) => value,
+        }
+    }
+}
+
+impl<Header, Tail, const N: usize> DerefMut for SmallDynStruct<Header, Tail, N> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.as_ptr() }
+    }
+}
+
+impl<Header: Clone, Tail: Clone, const N: usize> Clone for SmallDynStruct<Header, Tail, N> {
+    fn clone(&self) -> Self {
+        Self::new(self.header.clone(), self.tail.iter().cloned())
+    }
+}
+
+impl<Header, Tail, const N: usize> From<Box<DynStruct<Header, Tail>>>
+    for SmallDynStruct<Header, Tail, N>
+{
+    /// Moves the value out of its allocation if it fits inline, and keeps the `Box` otherwise.
+    fn from(boxed: Box<DynStruct<Header, Tail>>) -> Self {
+        let len = boxed.len();
+        if len > Self::INLINE_LEN {
+            return Self::from_box_unchecked(boxed);
+        }
+
+        let mut value = Inline::<Header, Tail, N>::UNINIT;
+        unsafe {
+            let src = &*boxed as *const DynStruct<Header, Tail> as *const u8;
+            let dst = &mut value as *mut Inline<Header, Tail, N> as *mut u8;
+            // The padding after the tail does not need to be copied.
+            let end = DynStruct::<Header, Tail>::TAIL_OFFSET + len * size_of::<Tail>();
+            dst.copy_from_nonoverlapping(src, end);
+            crate::__private::free(boxed);
+        }
+        SmallDynStruct {
+            repr: Repr::Inline { value, len },
+        }
+    }
+}
+
+impl<Header, Tail, const N: usize> From<SmallDynStruct<Header, Tail, N>>
+    for Box<DynStruct<Header, Tail>>
+{
+    #[inline]
+    fn from(value: SmallDynStruct<Header, Tail, N>) -> Self {
+        SmallDynStruct::into_box(value)
+    }
+}
+
+impl<Header: fmt::Debug, Tail: fmt::Debug, const N: usize> fmt::Debug
+    for SmallDynStruct<Header, Tail, N>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Writes a value into a buffer which is already large enough for it.
+struct InBuffer {
+    raw: *mut u8,
+}
+
+unsafe impl RawAlloc for InBuffer {
+    fn allocate(&self, _: Layout, zeroed: bool) -> Result<*mut u8, TryNewError> {
+        debug_assert!(!zeroed);
+        Ok(self.raw)
+    }
+
+    unsafe fn deallocate(&self, _: *mut u8, _: Layout) {
+        // The buffer belongs to the caller.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use std::format;
+    use std::rc::Rc;
+
+    #[test]
+    fn inline_and_boxed() {
+        type Small = SmallDynStruct<u8, u32, 12>;
+        assert_eq!(Small::INLINE_LEN, 3);
+
+        for len in 0..6u32 {
+            let mut value = Small::new(len as u8, 0..len);
+            assert_eq!(Small::is_inline(&value), len <= 3);
+            assert_eq!(value.header, len as u8);
+            assert_eq!(value.len(), len as usize);
+            assert_eq!(&value.tail, &(0..len).collect::<Vec<_>>()[..]);
+            let start = &*value as *const DynStruct<u8, u32> as *const u8 as usize;
+            assert_eq!(start % 4, 0);
+
+            // Moving the owner moves an inline value along with it.
+            value.tail.iter_mut().for_each(|x| *x += 1);
+            let moved = vec![value];
+            let boxed = Box::from(moved.into_iter().next().unwrap());
+            assert_eq!(*boxed, *DynStruct::new(len as u8, 1..len + 1));
+            let value = Small::from(boxed);
+            assert_eq!(Small::is_inline(&value), len <= 3);
+            assert_eq!(&value.tail, &(1..len + 1).collect::<Vec<_>>()[..]);
+            assert_eq!(format!("{:?}", value), format!("{:?}", &*value));
+        }
+
+        let zero_sized = SmallDynStruct::<(), (), 0>::new((), vec![(); 1000]);
+        assert!(SmallDynStruct::is_inline(&zero_sized));
+        assert_eq!(zero_sized.len(), 1000);
+        let empty = SmallDynStruct::<String, u64, 0>::new(String::from("header"), []);
+        assert!(SmallDynStruct::is_inline(&empty));
+        assert_eq!(empty.header, "header");
+    }
+
+    #[test]
+    fn drops() {
+        let counter = Rc::new(());
+        for len in [1, 4] {
+            let value = SmallDynStruct::<_, _, 16>::new(
+                (counter.clone(), String::from("header")),
+                vec![counter.clone(); len],
+            );
+            let copy = value.clone();
+            assert_eq!(Rc::strong_count(&counter), 2 * len + 3);
+            drop(copy);
+            let value = SmallDynStruct::<_, _, 16>::from(SmallDynStruct::into_box(value));
+            assert_eq!(Rc::strong_count(&counter), len + 2);
+            drop(value);
+            assert_eq!(Rc::strong_count(&counter), 1);
+        }
+
+        // An iterator panicking drops the elements which were already written.
+        let tail = (0..3).map(|i| {
+            assert!(i < 2, "boom");
+            counter.clone()
+        });
+        let result =
+            std::panic::catch_unwind(|| SmallDynStruct::<_, _, 64>::new(counter.clone(), tail));
+        assert!(result.is_err());
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn panicking_drop() {
+        struct Bomb;
+        impl Drop for Bomb {
+            fn drop(&mut self) {
+                panic!("boom");
+            }
+        }
+
+        let counter = Rc::new(());
+        let value = SmallDynStruct::<_, _, 8>::new(Bomb, [counter.clone()]);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(value)));
+        assert!(result.is_err());
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+}
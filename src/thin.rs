@@ -0,0 +1,315 @@
+//! [`ThinDynStruct`]: an owning pointer to a `DynStruct` which is a single word.
+
+use alloc::boxed::Box;
+use core::alloc::Layout;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+use crate::{tracking, BoxWriter, DynStruct, RawAlloc, TryNewError};
+
+/// Like a `Box<DynStruct<Header, Tail>>`, but only one pointer wide.
+///
+/// The length of the tail, which a `Box` keeps next to its pointer, is stored at the start of the
+/// allocation instead, followed by the value itself. Reaching the value through [`Deref`] reads the
+/// length first. A `Box` can be turned into a `ThinDynStruct` and back with `From`, which moves the
+/// value into a new allocation, since the length has to be inserted before it.
+pub struct ThinDynStruct<Header, Tail> {
+    /// The start of the allocation, where the length of the tail is stored.
+    raw: NonNull<u8>,
+    _marker: PhantomData<Box<DynStruct<Header, Tail>>>,
+}
+
+// The owner is a single pointer, and so is an `Option` of it.
+const _: () = assert!(size_of::<ThinDynStruct<u8, u64>>() == size_of::<usize>());
+const _: () = assert!(size_of::<Option<ThinDynStruct<(), ()>>>() == size_of::<usize>());
+
+// SAFETY: the value is owned, just like in a `Box`.
+unsafe impl<Header: Send, Tail: Send> Send for ThinDynStruct<Header, Tail> {}
+unsafe impl<Header: Sync, Tail: Sync> Sync for ThinDynStruct<Header, Tail> {}
+
+impl<Header, Tail> ThinDynStruct<Header, Tail> {
+    /// The offset of the value from the start of the allocation, just past the length.
+    const OFFSET: usize = Prefixed::<usize>::offset::<Header, Tail>();
+
+    /// Allocates a new value, like [`DynStruct::new`].
+    #[inline]
+    pub fn new<I>(header: Header, tail: I) -> Self
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        crate::__private::unwrap(Self::try_new(header, tail))
+    }
+
+    /// Like [`ThinDynStruct::new`], but returns an error instead of panicking or aborting if the
+    /// value cannot be allocated.
+    pub fn try_new<I>(header: Header, tail: I) -> Result<Self, TryNewError>
+    where
+        I: IntoIterator<Item = Tail>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        unsafe {
+            let ptr = BoxWriter::<Header, Tail, Prefixed<usize>>::from_iter(
+                header,
+                Layout::new::<Header>(),
+                tail,
+                Prefixed::new(),
+            )?;
+            // The value is freed by `Drop` below, not through a `Box`.
+            tracking::forget(ptr.cast::<u8>());
+            Ok(Self::from_value(ptr.cast::<u8>(), ptr.len()))
+        }
+    }
+
+    /// Moves the value into a `Box`.
+    pub fn into_box(this: Self) -> Box<DynStruct<Header, Tail>> {
+        let this = ManuallyDrop::new(this);
+        let value = this.as_ptr();
+        unsafe {
+            let layout = Layout::for_value(&*value);
+            let boxed = crate::copy_into_box(value);
+            crate::poison(value.cast::<u8>(), layout.size());
+            Prefixed::<usize>::new().deallocate(value.cast::<u8>(), layout);
+            boxed
+        }
+    }
+
+    /// Takes over the value at `value`, with `len` elements in its tail, which was allocated by
+    /// [`Prefixed`].
+    #[inline]
+    unsafe fn from_value(value: *mut u8, len: usize) -> Self {
+        let raw = value.sub(Self::OFFSET);
+        raw.cast::<usize>().write(len);
+        ThinDynStruct {
+            raw: NonNull::new_unchecked(raw),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a pointer to the value, with the length stored in front of it as metadata.
+    #[inline]
+    fn as_ptr(&self) -> *mut DynStruct<Header, Tail> {
+        let raw = self.raw.as_ptr();
+        // SAFETY: the allocation starts with the length, and the value follows at `OFFSET`.
+        let (len, value) = unsafe { (raw.cast::<usize>().read(), raw.add(Self::OFFSET)) };
+        core::ptr::slice_from_raw_parts_mut(value.cast::<()>(), len) as *mut DynStruct<Header, Tail>
+    }
+}
+
+impl<Header, Tail> Drop for ThinDynStruct<Header, Tail> {
+    fn drop(&mut self) {
+        /// Frees the allocation, even if dropping the value panics.
+        struct Free {
+            value: *mut u8,
+            layout: Layout,
+        }
+
+        impl Drop for Free {
+            fn drop(&mut self) {
+                unsafe { Prefixed::<usize>::new().deallocate(self.value, self.layout) };
+            }
+        }
+
+        let value = self.as_ptr();
+        unsafe {
+            let _free = Free {
+                value: value.cast::<u8>(),
+                layout: Layout::for_value(&*value),
+            };
+            core::ptr::drop_in_place(value);
+        }
+    }
+}
+
+impl<Header, Tail> Deref for ThinDynStruct<Header, Tail> {
+    type Target = DynStruct<Header, Tail>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.as_ptr() }
+    }
+}
+
+impl<Header, Tail> DerefMut for ThinDynStruct<Header, Tail> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.as_ptr() }
+    }
+}
+
+impl<Header, Tail> From<Box<DynStruct<Header, Tail>>> for ThinDynStruct<Header, Tail> {
+    /// Moves the value into a new allocation, with its length in front of it.
+    fn from(value: Box<DynStruct<Header, Tail>>) -> Self {
+        let len = value.len();
+        let layout = Layout::for_value(&*value);
+        let raw = crate::__private::unwrap(Prefixed::<usize>::new().allocate(layout, false));
+        unsafe {
+            let src = &*value as *const DynStruct<Header, Tail> as *const u8;
+            raw.copy_from_nonoverlapping(src, layout.size());
+            crate::__private::free(value);
+            Self::from_value(raw, len)
+        }
+    }
+}
+
+impl<Header, Tail> From<ThinDynStruct<Header, Tail>> for Box<DynStruct<Header, Tail>> {
+    #[inline]
+    fn from(value: ThinDynStruct<Header, Tail>) -> Self {
+        ThinDynStruct::into_box(value)
+    }
+}
+
+impl<Header: fmt::Debug, Tail: fmt::Debug> fmt::Debug for ThinDynStruct<Header, Tail> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// The global allocator, with room for a `P` in front of every value, which the owner of the
+/// value writes itself.
+pub(crate) struct Prefixed<P>(PhantomData<P>);
+
+impl<P> Prefixed<P> {
+    #[inline]
+    pub(crate) const fn new() -> Self {
+        Prefixed(PhantomData)
+    }
+
+    /// Returns the offset of a `DynStruct<Header, Tail>` from the start of its allocation.
+    pub(crate) const fn offset<Header, Tail>() -> usize {
+        let (header, tail) = (
+            Layout::new::<Header>().align(),
+            Layout::new::<Tail>().align(),
+        );
+        let align = if header > tail { header } else { tail };
+        (size_of::<P>() + align - 1) & !(align - 1)
+    }
+
+    /// Returns the layout of the allocation for a value with the given layout, and the offset of
+    /// the value in it.
+    fn layout(value: Layout) -> Result<(Layout, usize), TryNewError> {
+        Layout::new::<P>()
+            .extend(value)
+            .map_err(|_| TryNewError::CapacityOverflow)
+    }
+}
+
+unsafe impl<P> RawAlloc for Prefixed<P> {
+    fn allocate(&self, layout: Layout, zeroed: bool) -> Result<*mut u8, TryNewError> {
+        let (full, offset) = Self::layout(layout)?;
+        // The prefix takes up memory even if the value does not.
+        let raw = crate::allocate(full, zeroed)?;
+        Ok(unsafe { raw.add(offset) })
+    }
+
+    unsafe fn deallocate(&self, raw: *mut u8, layout: Layout) {
+        // This cannot fail, since the same layout was allocated before.
+        let (full, offset) = crate::__private::unwrap(Self::layout(layout));
+        alloc::alloc::dealloc(raw.sub(offset), full);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::cell::Cell;
+    use std::format;
+    use std::rc::Rc;
+
+    #[test]
+    fn values() {
+        let mut value = ThinDynStruct::new(1u8, [2u64, 3, 4]);
+        assert_eq!(value.header, 1);
+        assert_eq!(&value.tail, &[2, 3, 4]);
+        value.tail[1] = 7;
+        assert_eq!(
+            &*value as *const DynStruct<u8, u64> as *const u8 as usize % 8,
+            0
+        );
+
+        let boxed = Box::from(value);
+        assert_eq!(*boxed, *DynStruct::new(1u8, [2u64, 7, 4]));
+        let value = ThinDynStruct::from(boxed);
+        assert_eq!(&value.tail, &[2, 7, 4]);
+        assert_eq!(format!("{:?}", value), format!("{:?}", &*value));
+
+        let empty = ThinDynStruct::new((), Vec::<()>::new());
+        assert_eq!(empty.len(), 0);
+        let empty = ThinDynStruct::from(ThinDynStruct::into_box(empty));
+        assert!(empty.is_empty());
+
+        let aligned = ThinDynStruct::new(0u8, [0u128; 2]);
+        assert_eq!(
+            &*aligned as *const DynStruct<u8, u128> as *const u8 as usize % 16,
+            0
+        );
+    }
+
+    #[test]
+    fn drops() {
+        let counter = Rc::new(());
+        let value = ThinDynStruct::new(
+            (counter.clone(), String::from("header")),
+            vec![counter.clone(); 5],
+        );
+        assert_eq!(Rc::strong_count(&counter), 7);
+        let value = ThinDynStruct::from(ThinDynStruct::into_box(value));
+        assert_eq!(Rc::strong_count(&counter), 7);
+        drop(value);
+        assert_eq!(Rc::strong_count(&counter), 1);
+
+        // Zero-sized values are still allocated, and freed if the iterator panics.
+        let tail = (0..3).map(|i| assert!(i < 2, "boom"));
+        let result = std::panic::catch_unwind(|| ThinDynStruct::new((), tail));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn trait_objects() {
+        let dropped = Rc::new(Cell::new(0));
+        let counter = |i: u32| -> Box<dyn Fn() -> u32> {
+            let dropped = Guard(dropped.clone());
+            Box::new(move || {
+                let _ = &dropped;
+                i
+            })
+        };
+
+        struct Guard(Rc<Cell<u32>>);
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let value = ThinDynStruct::new(counter(0), (1..4).map(counter));
+        assert_eq!((value.header)(), 0);
+        assert_eq!(value.tail.iter().map(|f| f()).sum::<u32>(), 6);
+        let value = ThinDynStruct::from(Box::from(value));
+        assert_eq!(dropped.get(), 0);
+        drop(value);
+        assert_eq!(dropped.get(), 4);
+    }
+
+    #[test]
+    fn panicking_drop() {
+        struct Bomb;
+        impl Drop for Bomb {
+            fn drop(&mut self) {
+                panic!("boom");
+            }
+        }
+
+        let counter = Rc::new(());
+        let value = ThinDynStruct::new(counter.clone(), [Bomb]);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(value)));
+        assert!(result.is_err());
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+}
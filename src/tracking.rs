@@ -0,0 +1,96 @@
+//! The `layout-tracking` feature: a table of the layout each value was allocated with, keyed by its
+//! address.
+//!
+//! Whenever a `Box` is reassembled from a pointer, which is where a wrong type would later make it
+//! free the memory with the wrong layout, the layout of the new type is checked against the table.
+//! Values dropped through their `Box` still have an entry, which is replaced once the address is
+//! reused for a new value. Without the feature, all of this compiles to nothing.
+
+use core::alloc::Layout;
+
+/// Records that `raw` was allocated with `layout`.
+#[inline]
+pub(crate) fn record(raw: *const u8, layout: Layout) {
+    #[cfg(feature = "layout-tracking")]
+    if layout.size() != 0 {
+        table::with(|layouts| layouts.insert(raw.addr(), layout));
+    }
+    #[cfg(not(feature = "layout-tracking"))]
+    let _ = (raw, layout);
+}
+
+/// Forgets the layout of `raw`, which is about to be freed.
+#[inline]
+pub(crate) fn forget(raw: *const u8) {
+    #[cfg(feature = "layout-tracking")]
+    table::with(|layouts| layouts.remove(&raw.addr()));
+    #[cfg(not(feature = "layout-tracking"))]
+    let _ = raw;
+}
+
+/// Panics if `raw` was allocated with a different layout than `layout()`.
+#[inline]
+#[track_caller]
+pub(crate) fn check(raw: *const u8, layout: impl FnOnce() -> Layout) {
+    #[cfg(feature = "layout-tracking")]
+    {
+        let layout = layout();
+        // Look the layout up first, so that the table is not locked while panicking.
+        let allocated = table::with(|layouts| layouts.get(&raw.addr()).copied());
+        if let Some(allocated) = allocated.filter(|&allocated| allocated != layout) {
+            panic!(
+                "the value at {:p} was allocated with size {} and alignment {}, but would be \
+                 freed with size {} and alignment {}",
+                raw,
+                allocated.size(),
+                allocated.align(),
+                layout.size(),
+                layout.align(),
+            );
+        }
+    }
+    #[cfg(not(feature = "layout-tracking"))]
+    let _ = (raw, layout);
+}
+
+#[cfg(feature = "layout-tracking")]
+mod table {
+    use alloc::collections::BTreeMap;
+    use core::alloc::Layout;
+    use core::cell::UnsafeCell;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    struct Table {
+        locked: AtomicBool,
+        layouts: UnsafeCell<BTreeMap<usize, Layout>>,
+    }
+
+    // SAFETY: `layouts` is only accessed while `locked` is held.
+    unsafe impl Sync for Table {}
+
+    static TABLE: Table = Table {
+        locked: AtomicBool::new(false),
+        layouts: UnsafeCell::new(BTreeMap::new()),
+    };
+
+    /// Runs `f` with the table locked. This is a spin lock, since there is no `Mutex` in `core`.
+    pub(super) fn with<R>(f: impl FnOnce(&mut BTreeMap<usize, Layout>) -> R) -> R {
+        struct Unlock;
+
+        impl Drop for Unlock {
+            fn drop(&mut self) {
+                TABLE.locked.store(false, Ordering::Release);
+            }
+        }
+
+        while TABLE
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+        let _unlock = Unlock;
+        f(unsafe { &mut *TABLE.layouts.get() })
+    }
+}
@@ -0,0 +1,394 @@
+//! [`DynVec`]: a `DynStruct` whose tail can grow, like a `Vec` with a header.
+
+use alloc::boxed::Box;
+use core::alloc::Layout;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+use crate::{tracking, DynStruct, Global, RawAlloc, TryNewError};
+
+/// A header followed by a growable tail, in one allocation with room for `capacity` elements.
+///
+/// This is to a `Box<DynStruct<Header, Tail>>` what a `Vec<T>` is to a `Box<[T]>`: elements can be
+/// pushed and popped, and the allocation grows by doubling its capacity, so pushing takes amortized
+/// constant time. It dereferences to the `DynStruct` of the current elements, and turns into a
+/// `Box` with [`DynVec::into_boxed`], in the same allocation.
+///
+/// ```
+/// use dyn_struct::DynVec;
+///
+/// let mut values = DynVec::new("header");
+/// values.push(1u32);
+/// values.extend([2, 3, 4]);
+/// assert_eq!(values.pop(), Some(4));
+/// assert_eq!(&values.tail, &[1, 2, 3]);
+///
+/// let boxed = DynVec::into_boxed(values);
+/// assert_eq!(boxed.header, "header");
+/// assert_eq!(&boxed.tail, &[1, 2, 3]);
+/// ```
+pub struct DynVec<Header, Tail> {
+    /// The start of the allocation, which holds the header and room for `capacity` elements.
+    raw: NonNull<u8>,
+    len: usize,
+    capacity: usize,
+    _marker: PhantomData<Box<DynStruct<Header, Tail>>>,
+}
+
+// SAFETY: the value is owned, just like in a `Box`.
+unsafe impl<Header: Send, Tail: Send> Send for DynVec<Header, Tail> {}
+unsafe impl<Header: Sync, Tail: Sync> Sync for DynVec<Header, Tail> {}
+
+impl<Header, Tail> DynVec<Header, Tail> {
+    /// Creates a value with an empty tail, which does not allocate room for any elements yet.
+    #[inline]
+    pub fn new(header: Header) -> Self {
+        Self::with_capacity(header, 0)
+    }
+
+    /// Creates a value with an empty tail and room for at least `capacity` elements.
+    #[inline]
+    pub fn with_capacity(header: Header, capacity: usize) -> Self {
+        crate::__private::unwrap(Self::try_with_capacity(header, capacity))
+    }
+
+    /// Like [`DynVec::with_capacity`], but returns an error instead of panicking or aborting if the
+    /// value cannot be allocated.
+    pub fn try_with_capacity(header: Header, capacity: usize) -> Result<Self, TryNewError> {
+        let capacity = Self::room_for(capacity);
+        let raw = Global.allocate(Self::layout(capacity)?, false)?;
+        unsafe {
+            raw.cast::<Header>().write(header);
+            Ok(DynVec {
+                raw: NonNull::new_unchecked(raw),
+                len: 0,
+                capacity,
+                _marker: PhantomData,
+            })
+        }
+    }
+
+    /// Returns the number of elements the tail can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Makes room for at least `additional` more elements, like [`Vec::reserve`](alloc::vec::Vec::reserve).
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        crate::__private::unwrap(self.try_reserve(additional))
+    }
+
+    /// Like [`DynVec::reserve`], but returns an error instead of panicking or aborting if the value
+    /// cannot be reallocated.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryNewError> {
+        let needed = self
+            .len
+            .checked_add(additional)
+            .ok_or(TryNewError::CapacityOverflow)?;
+        if needed <= self.capacity {
+            return Ok(());
+        }
+        // Grow geometrically, so that pushing many elements takes amortized constant time.
+        let capacity = needed.max(self.capacity.saturating_mul(2)).max(4);
+        self.reallocate(capacity)
+    }
+
+    /// Appends an element to the tail.
+    #[inline]
+    pub fn push(&mut self, value: Tail) {
+        if self.len == self.capacity {
+            self.reserve(1);
+        }
+        unsafe {
+            self.tail_ptr().add(self.len).write(value);
+        }
+        self.len += 1;
+    }
+
+    /// Removes the last element of the tail and returns it, or `None` if the tail is empty.
+    #[inline]
+    pub fn pop(&mut self) -> Option<Tail> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(unsafe { self.tail_ptr().add(self.len).read() })
+    }
+
+    /// Shortens the tail to `len` elements, dropping the rest, like [`Vec::truncate`](alloc::vec::Vec::truncate). The
+    /// capacity stays the same.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let dropped = self.len - len;
+        // Shortening the tail first keeps the elements from being dropped again if one of them
+        // panics.
+        self.len = len;
+        unsafe {
+            let dropped = core::ptr::slice_from_raw_parts_mut(self.tail_ptr().add(len), dropped);
+            core::ptr::drop_in_place(dropped);
+        }
+    }
+
+    /// Drops all elements of the tail, keeping the header and the capacity.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Turns the value into a `Box`, shrinking its allocation to the length of the tail first, and
+    /// reusing it as is if the tail is already at full capacity.
+    pub fn into_boxed(mut this: Self) -> Box<DynStruct<Header, Tail>> {
+        if this.len != this.capacity && size_of::<Tail>() != 0 {
+            // Shrinking the allocation may only fail if the allocator does not support it.
+            crate::__private::unwrap(this.reallocate(this.len));
+        }
+        let this = ManuallyDrop::new(this);
+        let ptr = this.as_ptr();
+        unsafe {
+            tracking::record(ptr.cast::<u8>(), Layout::for_value(&*ptr));
+            Box::from_raw(ptr)
+        }
+    }
+
+    /// Returns the capacity of an allocation with room for `capacity` elements. There is room for any
+    /// number of zero-sized elements.
+    #[inline]
+    fn room_for(capacity: usize) -> usize {
+        if size_of::<Tail>() == 0 {
+            usize::MAX
+        } else {
+            capacity
+        }
+    }
+
+    /// Returns the layout of the allocation with room for `capacity` elements.
+    fn layout(capacity: usize) -> Result<Layout, TryNewError> {
+        crate::layout::for_len::<Tail>(Layout::new::<Header>(), capacity).map(|(layout, _)| layout)
+    }
+
+    /// Moves the value into an allocation with room for `capacity` elements, which must be at least
+    /// the length of the tail.
+    fn reallocate(&mut self, capacity: usize) -> Result<(), TryNewError> {
+        // This cannot fail, since the same layout was allocated before.
+        let old = crate::__private::unwrap(Self::layout(self.capacity));
+        let (raw, _, _) = unsafe {
+            crate::reallocate(
+                self.raw.as_ptr(),
+                old,
+                Layout::new::<Header>(),
+                Layout::new::<Tail>(),
+                capacity,
+            )?
+        };
+        self.raw = unsafe { NonNull::new_unchecked(raw) };
+        self.capacity = capacity;
+        Ok(())
+    }
+
+    /// Returns a pointer to the first element of the tail.
+    #[inline]
+    fn tail_ptr(&mut self) -> *mut Tail {
+        let offset = DynStruct::<Header, Tail>::TAIL_OFFSET;
+        unsafe { self.raw.as_ptr().add(offset).cast::<Tail>() }
+    }
+
+    /// Returns a pointer to the value, with the current length of the tail as metadata.
+    #[inline]
+    fn as_ptr(&self) -> *mut DynStruct<Header, Tail> {
+        core::ptr::slice_from_raw_parts_mut(self.raw.as_ptr().cast::<()>(), self.len)
+            as *mut DynStruct<Header, Tail>
+    }
+}
+
+impl<Header, Tail> Drop for DynVec<Header, Tail> {
+    fn drop(&mut self) {
+        /// Frees the allocation, even if dropping the value panics.
+        struct Free {
+            raw: *mut u8,
+            layout: Layout,
+        }
+
+        impl Drop for Free {
+            fn drop(&mut self) {
+                unsafe { Global.deallocate(self.raw, self.layout) };
+            }
+        }
+
+        let _free = Free {
+            raw: self.raw.as_ptr(),
+            // This cannot fail, since the same layout was allocated before.
+            layout: crate::__private::unwrap(Self::layout(self.capacity)),
+        };
+        unsafe { core::ptr::drop_in_place(self.as_ptr()) };
+    }
+}
+
+impl<Header, Tail> Deref for DynVec<Header, Tail> {
+    type Target = DynStruct<Header, Tail>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.as_ptr() }
+    }
+}
+
+impl<Header, Tail> DerefMut for DynVec<Header, Tail> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut *self.as_ptr() }
+    }
+}
+
+impl<Header, Tail> Extend<Tail> for DynVec<Header, Tail> {
+    fn extend<I: IntoIterator<Item = Tail>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<'a, Header, Tail: Copy + 'a> Extend<&'a Tail> for DynVec<Header, Tail> {
+    fn extend<I: IntoIterator<Item = &'a Tail>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+impl<Header, Tail> From<Box<DynStruct<Header, Tail>>> for DynVec<Header, Tail> {
+    /// Takes over the allocation of the `Box`, with a capacity equal to the length of its tail.
+    fn from(value: Box<DynStruct<Header, Tail>>) -> Self {
+        let len = value.len();
+        let raw = Box::into_raw(value).cast::<u8>();
+        // The allocation is freed by `Drop` above, not through a `Box`.
+        tracking::forget(raw);
+        DynVec {
+            raw: unsafe { NonNull::new_unchecked(raw) },
+            len,
+            capacity: Self::room_for(len),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<Header, Tail> From<DynVec<Header, Tail>> for Box<DynStruct<Header, Tail>> {
+    #[inline]
+    fn from(value: DynVec<Header, Tail>) -> Self {
+        DynVec::into_boxed(value)
+    }
+}
+
+impl<Header: fmt::Debug, Tail: fmt::Debug> fmt::Debug for DynVec<Header, Tail> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use std::format;
+    use std::rc::Rc;
+
+    #[test]
+    fn grow_and_shrink() {
+        let mut values = DynVec::<u8, u64>::new(1);
+        assert!(values.is_empty());
+        assert_eq!(values.capacity(), 0);
+
+        let mut capacities = Vec::new();
+        for i in 0..100 {
+            values.push(i);
+            if capacities.last() != Some(&values.capacity()) {
+                capacities.push(values.capacity());
+            }
+        }
+        assert_eq!(capacities, [4, 8, 16, 32, 64, 128]);
+        assert_eq!(values.header, 1);
+        assert_eq!(&values.tail, &(0..100).collect::<Vec<_>>()[..]);
+        assert_eq!(
+            &*values as *const DynStruct<u8, u64> as *const u8 as usize % 8,
+            0
+        );
+
+        assert_eq!(values.pop(), Some(99));
+        values.truncate(10);
+        values.extend(&[20, 21]);
+        values.tail[0] = 7;
+        assert_eq!(format!("{:?}", values), format!("{:?}", &*values));
+
+        let boxed = DynVec::into_boxed(values);
+        let expected = [7, 1, 2, 3, 4, 5, 6, 7, 8, 9, 20, 21];
+        assert_eq!(*boxed, *DynStruct::new(1u8, expected));
+
+        let mut values = DynVec::from(boxed);
+        assert_eq!(values.capacity(), 12);
+        values.clear();
+        assert_eq!(values.pop(), None);
+        assert!(DynVec::into_boxed(values).is_empty());
+    }
+
+    #[test]
+    fn exact_capacity() {
+        let mut values = DynVec::with_capacity(0u16, 3);
+        values.extend([1u8, 2, 3]);
+        assert_eq!(values.capacity(), 3);
+        let start = &*values as *const DynStruct<u16, u8>;
+        // The allocation is reused as is.
+        let boxed = DynVec::into_boxed(values);
+        assert_eq!(&*boxed as *const DynStruct<u16, u8>, start);
+
+        let mut zero_sized = DynVec::new(());
+        zero_sized.extend([(); 1000]);
+        assert_eq!(zero_sized.capacity(), usize::MAX);
+        assert_eq!(DynVec::into_boxed(zero_sized).len(), 1000);
+    }
+
+    #[test]
+    fn drops() {
+        let counter = Rc::new(());
+        let mut values = DynVec::new((counter.clone(), String::from("header")));
+        values.extend(core::iter::repeat_n(counter.clone(), 10));
+        assert_eq!(Rc::strong_count(&counter), 12);
+        values.truncate(5);
+        assert_eq!(Rc::strong_count(&counter), 7);
+        drop(values.pop());
+        let boxed = DynVec::into_boxed(values);
+        assert_eq!(Rc::strong_count(&counter), 6);
+        drop(DynVec::from(boxed));
+        assert_eq!(Rc::strong_count(&counter), 1);
+
+        struct Bomb;
+        impl Drop for Bomb {
+            fn drop(&mut self) {
+                panic!("boom");
+            }
+        }
+        let mut values = DynVec::new(counter.clone());
+        values.push(Bomb);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(values)));
+        assert!(result.is_err());
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn overflow() {
+        let result = DynVec::<u8, u64>::try_with_capacity(0, usize::MAX / 4);
+        assert_eq!(result.unwrap_err(), TryNewError::CapacityOverflow);
+        let mut values = DynVec::<u8, u64>::new(0);
+        values.push(1);
+        assert_eq!(
+            values.try_reserve(usize::MAX).unwrap_err(),
+            TryNewError::CapacityOverflow
+        );
+    }
+}
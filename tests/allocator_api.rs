@@ -0,0 +1,173 @@
+#![feature(allocator_api)]
+
+use std::alloc::{AllocError, Allocator, Global, Layout};
+use std::cell::Cell;
+use std::ptr::NonNull;
+
+use dyn_struct::{DynStruct, TryNewError};
+
+/// Counts the allocations which are still alive, and fails once `limit` bytes are exceeded.
+#[derive(Default)]
+struct Counting {
+    allocations: Cell<usize>,
+    deallocations: Cell<usize>,
+    bytes: Cell<usize>,
+    limit: Option<usize>,
+}
+
+impl Counting {
+    fn live(&self) -> usize {
+        self.allocations.get() - self.deallocations.get()
+    }
+}
+
+unsafe impl Allocator for &Counting {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if self.limit.is_some_and(|limit| layout.size() > limit) {
+            return Err(AllocError);
+        }
+        self.allocations.set(self.allocations.get() + 1);
+        self.bytes.set(self.bytes.get() + layout.size());
+        Global.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.deallocations.set(self.deallocations.get() + 1);
+        self.bytes.set(self.bytes.get() - layout.size());
+        Global.deallocate(ptr, layout)
+    }
+}
+
+#[test]
+fn dyn_struct_new_in() {
+    let counting = Counting::default();
+
+    let value = DynStruct::new_in(1u32, vec![2u64, 3], &counting);
+    assert_eq!(value.header, 1);
+    assert_eq!(&value.tail, &[2, 3]);
+    assert_eq!(counting.live(), 1);
+    assert_eq!(counting.bytes.get(), std::mem::size_of_val(&*value));
+
+    drop(value);
+    assert_eq!(counting.live(), 0);
+    assert_eq!(counting.bytes.get(), 0);
+
+    // zero-sized values are not allocated
+    let empty = DynStruct::new_in((), Vec::<()>::new(), &counting);
+    drop(empty);
+    assert_eq!(counting.allocations.get(), 1);
+}
+
+#[test]
+fn dyn_struct_try_new_in() {
+    let counting = Counting {
+        limit: Some(64),
+        ..Counting::default()
+    };
+
+    let small = DynStruct::try_new_in(1u8, vec![2u8; 16], &counting).unwrap();
+    assert_eq!(small.tail.len(), 16);
+
+    let error = DynStruct::try_new_in(1u8, vec![2u8; 128], &counting).unwrap_err();
+    assert!(matches!(error, TryNewError::AllocError { .. }));
+
+    drop(small);
+    assert_eq!(counting.allocations.get(), 1);
+    assert_eq!(counting.live(), 0);
+}
+
+#[test]
+fn failed_allocation_releases_values() {
+    use std::rc::Rc;
+
+    let counting = Counting {
+        limit: Some(64),
+        ..Counting::default()
+    };
+
+    let header = Rc::new(());
+    let tail = Rc::new(());
+    let values = vec![tail.clone(); 16];
+    let error = DynStruct::try_new_in(header.clone(), values, &counting).unwrap_err();
+    assert!(matches!(error, TryNewError::AllocError { .. }));
+    assert_eq!(Rc::strong_count(&header), 1);
+    assert_eq!(Rc::strong_count(&tail), 1);
+    assert_eq!(counting.allocations.get(), 0);
+}
+
+#[test]
+fn panicking_iterator_deallocates() {
+    let counting = Counting::default();
+
+    struct Panicking(usize);
+    impl Iterator for Panicking {
+        type Item = String;
+        fn next(&mut self) -> Option<String> {
+            self.0 += 1;
+            if self.0 == 3 {
+                panic!("oops");
+            }
+            Some(self.0.to_string())
+        }
+    }
+    impl ExactSizeIterator for Panicking {
+        fn len(&self) -> usize {
+            4
+        }
+    }
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        DynStruct::new_in(String::from("header"), Panicking(0), &counting)
+    }));
+    assert!(result.is_err());
+    assert_eq!(counting.allocations.get(), 1);
+    assert_eq!(counting.live(), 0);
+}
+
+#[test]
+fn derived_new_in() {
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    #[dyn_struct(allocator_api, try_new)]
+    struct Record {
+        pub id: u64,
+        pub tags: Vec<String>,
+        pub values: [u16],
+    }
+
+    let counting = Counting::default();
+
+    let record = Record::new_in(1, vec!["a".to_string()], [2, 3, 4], &counting);
+    assert_eq!(record.id, 1);
+    assert_eq!(record.tags, ["a"]);
+    assert_eq!(&record.values, &[2, 3, 4]);
+    assert_eq!(counting.live(), 1);
+    assert_eq!(counting.bytes.get(), Record::layout_for_len(3).size());
+
+    let other = Record::try_new_in(5, Vec::new(), Vec::new(), &counting).unwrap();
+    assert_eq!(counting.live(), 2);
+
+    drop(record);
+    drop(other);
+    assert_eq!(counting.allocations.get(), 2);
+    assert_eq!(counting.live(), 0);
+}
+
+#[test]
+fn derived_str_new_in() {
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    #[dyn_struct(allocator_api)]
+    struct Name {
+        pub alloc: u8,
+        pub name: str,
+    }
+
+    let counting = Counting::default();
+    let name = Name::new_in(1, "name", &counting);
+    assert_eq!(name.alloc, 1);
+    assert_eq!(&name.name, "name");
+    drop(name);
+    assert_eq!(counting.allocations.get(), 1);
+    assert_eq!(counting.live(), 0);
+}
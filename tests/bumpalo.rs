@@ -0,0 +1,137 @@
+use std::cell::Cell;
+use std::fmt::Display;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use bumpalo::Bump;
+use dyn_struct::{DynStruct, TryNewError};
+
+/// Counts how often it is dropped, without owning any memory which a skipped drop would leak.
+struct Counted<'a>(&'a Cell<usize>, u32);
+
+impl Drop for Counted<'_> {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, DynStruct)]
+#[dyn_struct(bumpalo, try_new)]
+struct Node {
+    pub kind: u8,
+    pub children: [u32],
+}
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(bumpalo)]
+struct Name {
+    pub id: u16,
+    pub text: str,
+}
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(bumpalo)]
+struct Lazy<'a> {
+    pub id: u32,
+    pub compute: dyn Fn() -> u32 + 'a,
+}
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(bumpalo)]
+struct Shown {
+    pub id: u32,
+    pub value: dyn Display,
+}
+
+#[test]
+fn dyn_struct() {
+    let bump = Bump::new();
+    let first = DynStruct::new_in_bump(1u8, [2u64, 3], &bump);
+    let second = DynStruct::new_in_bump((), Vec::<u16>::new(), &bump);
+    let third = DynStruct::new_in_bump(4u16, (5..8u32).map(|i| i * 2), &bump);
+
+    // The values live side by side in the arena, and can all be used at once.
+    first.tail[1] = 9;
+    assert_eq!((first.header, &first.tail), (1, &[2, 9][..]));
+    assert!(second.tail.is_empty());
+    assert_eq!((third.header, &third.tail), (4, &[10, 12, 14][..]));
+    assert_eq!(
+        std::mem::size_of_val(first),
+        DynStruct::<u8, u64>::size_for_len(2)
+    );
+    assert_eq!(first as *mut _ as *mut u8 as usize % 8, 0);
+}
+
+#[test]
+fn never_dropped() {
+    let drops = Cell::new(0);
+    {
+        let bump = Bump::new();
+        let value = DynStruct::new_in_bump(
+            Counted(&drops, 0),
+            [1, 2].map(|i| Counted(&drops, i)),
+            &bump,
+        );
+        assert_eq!(value.tail[1].1, 2);
+    }
+    assert_eq!(drops.get(), 0);
+
+    // If construction fails, the elements written so far are dropped, and the memory is left to
+    // the arena.
+    let bump = Bump::new();
+    let tail = (0..3).map(|i| {
+        assert!(i < 2, "boom");
+        Counted(&drops, i)
+    });
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        DynStruct::new_in_bump(Counted(&drops, 0), tail, &bump);
+    }));
+    assert!(result.is_err());
+    assert_eq!(drops.get(), 3);
+}
+
+#[test]
+fn allocation_failure() {
+    let bump = Bump::new();
+    bump.set_allocation_limit(Some(0));
+    let error = DynStruct::try_new_in_bump(1u64, [2u64; 64], &bump).unwrap_err();
+    assert!(matches!(error, TryNewError::AllocError { layout } if layout.size() == 8 * 65));
+    assert!(matches!(
+        Node::try_new_in_bump(1, [2; 64], &bump),
+        Err(TryNewError::AllocError { .. })
+    ));
+
+    // Zero-sized values need no memory at all.
+    let empty = DynStruct::try_new_in_bump((), [(); 3], &bump).unwrap();
+    assert_eq!(empty.tail.len(), 3);
+}
+
+#[test]
+fn derived() {
+    let bump = Bump::new();
+    let node = Node::new_in_bump(1, vec![2, 3], &bump);
+    node.children[0] = 4;
+    assert_eq!((node.kind, &node.children), (1, &[4, 3][..]));
+    assert_eq!(std::mem::size_of_val(node), Node::layout_for_len(2).size());
+
+    let name = Name::new_in_bump(7, "seven", &bump);
+    assert_eq!((name.id, &name.text), (7, "seven"));
+
+    // Trait object tails without drop glue, borrowing from outside the arena.
+    let offset = 10;
+    let lazy = Lazy::new_in_bump(1, || offset + 1, &bump);
+    assert_eq!((lazy.compute)(), 11);
+    let shown = Shown::new_in_bump(2, 3.5, &bump);
+    assert_eq!(format!("{}: {}", shown.id, &shown.value), "2: 3.5");
+    assert_eq!(node.kind, 1);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/bumpalo/*.rs");
+}
@@ -0,0 +1,66 @@
+//! The crate is only reachable through a re-export here, as if it was a dependency of a facade
+//! crate. Any path in the generated code that does not go through `#[dyn_struct(crate = "...")]`
+//! fails to resolve.
+
+mod facade {
+    pub use ::dyn_struct as dst;
+}
+
+// Shadows the crate itself.
+#[allow(dead_code)]
+mod dyn_struct {}
+
+use ::dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(
+    crate = "crate::facade::dst",
+    try_new,
+    rc,
+    arc,
+    zeroable,
+    uninit,
+    pod,
+    clone,
+    default,
+    builder,
+    getters
+)]
+struct Message {
+    id: u32,
+    values: [u32],
+}
+
+#[test]
+fn renamed_crate() {
+    let message = Message::new(1, [2, 3]);
+    assert_eq!(message.id(), 1);
+    assert_eq!(message.values(), [2, 3]);
+    assert_eq!(message.as_bytes().len(), 12);
+    assert_eq!(&message.clone().values, [2, 3]);
+    assert!(Message::try_new(1, []).is_ok());
+    assert_eq!(&Message::new_zeroed(1, 2).values, [0, 0]);
+    assert!(Message::new_uninit(1, 1).values.len() == 1);
+    assert!(Box::<Message>::default().values.is_empty());
+    assert!(matches!(
+        Message::builder().build(),
+        Err(facade::dst::MissingField { field: "id" })
+    ));
+    let (id, values) = message.into_parts();
+    assert_eq!((id, values), (1, vec![2, 3]));
+}
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(crate = "crate::facade::dst")]
+struct Task {
+    priority: u8,
+    job: dyn Fn() -> u8,
+}
+
+#[test]
+fn renamed_crate_dyn() {
+    let task = Task::new(1, || 2);
+    assert_eq!((task.priority, (task.job)()), (1, 2));
+}
@@ -0,0 +1,96 @@
+// The feature only poisons memory in debug builds.
+#![cfg(debug_assertions)]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use dyn_struct::DynStruct;
+
+/// Records, for the current thread, how many blocks were freed and how many of them held nothing
+/// but `0xDD`. Only blocks freed inside `watch` are looked at, which must all be initialized.
+struct Inspecting;
+
+thread_local! {
+    static WATCHING: Cell<bool> = const { Cell::new(false) };
+    static FREED: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+}
+
+unsafe impl GlobalAlloc for Inspecting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if WATCHING.with(Cell::get) {
+            let bytes = std::slice::from_raw_parts(ptr, layout.size());
+            let poisoned = bytes.iter().all(|&byte| byte == 0xDD);
+            FREED.with(|freed| {
+                let (count, poisoned_count) = freed.get();
+                freed.set((count + 1, poisoned_count + usize::from(poisoned)));
+            });
+        }
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Inspecting = Inspecting;
+
+/// Runs `f` and returns its result, together with the number of blocks it freed and how many of
+/// them were poisoned.
+fn watch<T>(f: impl FnOnce() -> T) -> (T, (usize, usize)) {
+    FREED.with(|freed| freed.set((0, 0)));
+    WATCHING.with(|watching| watching.set(true));
+    let result = f();
+    WATCHING.with(|watching| watching.set(false));
+    (result, FREED.with(Cell::get))
+}
+
+#[test]
+fn into_parts() {
+    let value = DynStruct::new(1u64, [2u64, 3]);
+    let ((header, tail), freed) = watch(|| DynStruct::into_parts(value));
+    assert_eq!(freed, (1, 1));
+    assert_eq!(header, 1);
+    assert_eq!(tail, [2, 3]);
+}
+
+#[test]
+fn from_vec() {
+    let values = vec![5u32, 6, 7];
+    let (value, freed) = watch(|| DynStruct::from_vec(4u32, values));
+    assert_eq!(freed, (1, 1));
+    assert_eq!(value.header, 4);
+    assert_eq!(&value.tail, &[5, 6, 7]);
+}
+
+#[test]
+fn derived() {
+    #[repr(C)]
+    #[derive(dyn_struct::DynStruct)]
+    struct Wrapper {
+        pub count: u64,
+        pub inner: DynStruct<u64, u64>,
+    }
+
+    #[repr(C)]
+    #[derive(dyn_struct::DynStruct)]
+    struct Values {
+        pub count: u64,
+        pub values: [u64],
+    }
+
+    // The boxed tail is moved into the new allocation.
+    let inner = DynStruct::new(5u64, [6u64, 7]);
+    let (wrapper, freed) = watch(|| Wrapper::new(2, inner));
+    assert_eq!(freed, (1, 1));
+    assert_eq!(wrapper.count, 2);
+    assert_eq!(wrapper.inner.header, 5);
+    assert_eq!(&wrapper.inner.tail, &[6, 7]);
+
+    let values = Values::new(1, vec![2, 3]);
+    let ((count, values), freed) = watch(|| values.into_parts());
+    assert_eq!(freed, (1, 1));
+    assert_eq!(count, 1);
+    assert_eq!(values, [2, 3]);
+}
@@ -25,7 +25,7 @@ fn generic() {
     }
 
     let foo = Foo::new(true, "hello", [1, 2, 3, 4]);
-    assert_eq!(foo.inner, true);
+    assert!(foo.inner);
     assert_eq!(foo.text, "hello");
     assert_eq!(&foo.values, [1, 2, 3, 4]);
 }
@@ -41,7 +41,7 @@ fn readme() {
     }
 
     let foo: Box<MyDynamicType> = MyDynamicType::new(true, 123, 4..8);
-    assert_eq!(foo.awesome, true);
+    assert!(foo.awesome);
     assert_eq!(foo.number, 123);
     assert_eq!(&foo.dynamic, &[4, 5, 6, 7]);
 }
@@ -88,3 +88,2287 @@ fn non_copy_with_drop() {
         "dropping DynStruct should result in drop"
     );
 }
+
+#[test]
+fn str_tail() {
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    struct Symbol {
+        pub id: u32,
+        pub name: str,
+    }
+
+    let symbol = Symbol::new(7, "hello");
+    assert_eq!(symbol.id, 7);
+    assert_eq!(&symbol.name, "hello");
+
+    let empty = Symbol::new(0, "");
+    assert_eq!(empty.id, 0);
+    assert_eq!(&empty.name, "");
+
+    let unicode = Symbol::new(1, "grüße, 世界");
+    assert_eq!(&unicode.name, "grüße, 世界");
+    assert_eq!(unicode.name.chars().count(), 9);
+}
+
+#[test]
+fn str_tail_generic() {
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    struct Named<'a, T> {
+        pub value: T,
+        pub label: &'a str,
+        pub name: str,
+    }
+
+    let named = Named::new((1u8, 2u64), "label", "ñame");
+    assert_eq!(named.value, (1, 2));
+    assert_eq!(named.label, "label");
+    assert_eq!(&named.name, "ñame");
+}
+
+#[test]
+fn dyn_tail() {
+    use std::fmt::Display;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Labeled {
+        pub id: u8,
+        pub label: dyn Display,
+    }
+
+    let number = Labeled::new(1, 1234u64);
+    assert_eq!(number.id, 1);
+    assert_eq!(number.label.to_string(), "1234");
+
+    let text = Labeled::new(2, String::from("text"));
+    assert_eq!(text.id, 2);
+    assert_eq!(text.label.to_string(), "text");
+}
+
+#[test]
+fn dyn_tail_auto_traits() {
+    use std::fmt::Debug;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Task {
+        pub priority: u8,
+        pub job: dyn Fn() -> u32 + Send,
+    }
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Shared<'a> {
+        pub id: u16,
+        pub value: dyn Debug + Send + Sync + 'a,
+    }
+
+    let offset = 10;
+    let task = Task::new(3, move || offset + 2);
+    assert_eq!(task.priority, 3);
+    assert_eq!((task.job)(), 12);
+    let task = std::thread::spawn(move || (task.job)()).join().unwrap();
+    assert_eq!(task, 12);
+
+    let local = String::from("borrowed");
+    let shared = Shared::new(4, &local);
+    assert_eq!(shared.id, 4);
+    assert_eq!(format!("{:?}", &shared.value), "\"borrowed\"");
+}
+
+#[test]
+fn dyn_tail_zero_sized() {
+    use std::any::Any;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Holder {
+        pub unit: (),
+        pub value: dyn Any,
+    }
+
+    let holder = Holder::new((), ());
+    assert_eq!(std::mem::size_of_val(&*holder), 0);
+    assert!(holder.value.is::<()>());
+    drop(holder);
+
+    let holder = Holder::new((), [0u64; 0]);
+    assert_eq!(std::mem::size_of_val(&*holder), 0);
+    assert!(is_aligned(&*holder, 8));
+    drop(holder);
+
+    // The tail is dropped even if it has no size.
+    static DROPPED: AtomicBool = AtomicBool::new(false);
+    struct Flag;
+    impl Drop for Flag {
+        fn drop(&mut self) {
+            DROPPED.store(true, Ordering::Relaxed);
+        }
+    }
+    drop(Holder::new((), Flag));
+    assert!(DROPPED.load(Ordering::Relaxed));
+}
+
+#[test]
+fn dyn_tail_drop() {
+    use std::rc::Rc;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Holder {
+        pub header: Rc<()>,
+        pub value: dyn std::any::Any,
+    }
+
+    let header = Rc::new(());
+    let tail = Rc::new(());
+    let holder = Holder::new(header.clone(), (1u8, tail.clone()));
+    assert_eq!(Rc::strong_count(&header), 2);
+    assert_eq!(Rc::strong_count(&tail), 2);
+    assert!(holder.value.is::<(u8, Rc<()>)>());
+
+    drop(holder);
+    assert_eq!(Rc::strong_count(&header), 1);
+    assert_eq!(Rc::strong_count(&tail), 1);
+}
+
+#[test]
+fn nested_tail() {
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    struct Inner {
+        pub id: u32,
+        pub values: [u32],
+    }
+
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    struct Outer {
+        pub flag: bool,
+        pub inner: Inner,
+    }
+
+    let outer = Outer::new(true, Inner::new(7, [1, 2, 3]));
+    assert!(outer.flag);
+    assert_eq!(outer.inner.id, 7);
+    assert_eq!(&outer.inner.values, [1, 2, 3]);
+
+    let empty = Outer::new(false, Inner::new(8, []));
+    assert_eq!(empty.inner.id, 8);
+    assert!(empty.inner.values.is_empty());
+}
+
+#[test]
+fn nested_library_tail() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Wrapper {
+        pub count: u8,
+        pub inner: dyn_struct::DynStruct<u64, String>,
+    }
+
+    let inner = dyn_struct::DynStruct::new(5u64, vec![String::from("a"), String::from("b")]);
+    let wrapper = Wrapper::new(2, inner);
+    assert_eq!(wrapper.count, 2);
+    assert_eq!(wrapper.inner.header, 5);
+    assert_eq!(&wrapper.inner.tail, &["a", "b"]);
+}
+
+// The padding inside the tail is moved along with it, without being read as initialized bytes,
+// which Miri would reject.
+#[test]
+fn padded_unsized_tail() {
+    use std::fmt::Debug;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Inner {
+        pub flag: u8,
+        pub id: u64,
+        pub values: [(u8, u64)],
+    }
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Outer {
+        pub tag: (u8, u32),
+        pub inner: Inner,
+    }
+
+    let outer = Outer::new((1, 2), Inner::new(3, 4, vec![(5, 6), (7, 8)]));
+    assert_eq!(outer.tag, (1, 2));
+    assert_eq!((outer.inner.flag, outer.inner.id), (3, 4));
+    assert_eq!(&outer.inner.values, &[(5, 6), (7, 8)]);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Holder {
+        pub tag: (u8, u16),
+        pub value: dyn Debug,
+    }
+
+    let holder = Holder::new((1, 2), [(3u8, 4u64), (5, 6)]);
+    assert_eq!(holder.tag, (1, 2));
+    assert_eq!(format!("{:?}", &holder.value), "[(3, 4), (5, 6)]");
+}
+
+#[test]
+fn generic_non_copy() {
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    struct Foo<T, U> {
+        pub inner: T,
+        pub values: [U],
+    }
+
+    let foo = Foo::new(String::from("header"), vec![vec![1u8], vec![2, 3]]);
+    assert_eq!(foo.inner, "header");
+    assert_eq!(&foo.values, [vec![1], vec![2, 3]]);
+}
+
+#[test]
+fn generic_only_in_tail() {
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    struct Foo<T> {
+        pub count: usize,
+        pub values: [T],
+    }
+
+    let foo = Foo::new(2, vec![String::from("a"), String::from("b")]);
+    assert_eq!(foo.count, 2);
+    assert_eq!(&foo.values, ["a", "b"]);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Bar<T> {
+        pub values: [T],
+    }
+
+    let bar = Bar::new(vec![Box::new(1), Box::new(2)]);
+    assert_eq!(&bar.values, [Box::new(1), Box::new(2)]);
+}
+
+#[test]
+fn where_clause() {
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    struct Foo<T>
+    where
+        T: Ord,
+    {
+        pub head: T,
+        pub tail: [u8],
+    }
+
+    let foo = Foo::new(3, [1, 2]);
+    assert_eq!(foo.head, 3);
+    assert_eq!(&foo.tail, [1, 2]);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Assoc<I>
+    where
+        I: Iterator,
+        I::Item: Clone,
+    {
+        pub first: I::Item,
+        pub rest: [<I as Iterator>::Item],
+    }
+
+    let assoc =
+        Assoc::<std::vec::IntoIter<String>>::new(String::from("a"), vec![String::from("b")]);
+    assert_eq!(assoc.first, "a");
+    assert_eq!(&assoc.rest, ["b"]);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Borrows<'a, 'b, T: ?Sized>
+    where
+        'a: 'b,
+        T: std::fmt::Display + 'a,
+    {
+        pub long: &'a T,
+        pub short: &'b str,
+        pub label: str,
+    }
+
+    let text = String::from("long");
+    let borrows: Box<Borrows<str>> = Borrows::new(text.as_str(), "short", "label");
+    assert_eq!(borrows.long, "long");
+    assert_eq!(borrows.short, "short");
+    assert_eq!(&borrows.label, "label");
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Unsized<T, U>
+    where
+        T: ?Sized + std::fmt::Debug,
+    {
+        pub boxed: Box<T>,
+        pub values: [U],
+    }
+
+    let boxed: Box<[u8]> = Box::new([1, 2]);
+    let value = Unsized::new(boxed, [3u8]);
+    assert_eq!(*value.boxed, [1, 2]);
+    assert_eq!(&value.values, [3]);
+}
+
+#[test]
+fn const_generic() {
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    struct Block<const N: usize> {
+        pub header: [u8; N],
+        pub data: [u8],
+    }
+
+    let block = Block::new([1, 2, 3], [4, 5]);
+    assert_eq!(block.header, [1, 2, 3]);
+    assert_eq!(&block.data, [4, 5]);
+
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    struct Defaulted<T, const N: usize = 2> {
+        pub header: [T; N],
+        pub data: [T],
+    }
+
+    let defaulted: Box<Defaulted<u16>> = Defaulted::new([1, 2], [3]);
+    assert_eq!(defaulted.header, [1, 2]);
+    assert_eq!(&defaulted.data, [3]);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct OnlyTail<const N: usize> {
+        pub data: [[u8; N]],
+    }
+
+    let only = OnlyTail::new([[1, 2], [3, 4]]);
+    assert_eq!(&only.data, [[1, 2], [3, 4]]);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Traited<const N: usize = 1> {
+        pub header: [u8; N],
+        pub value: dyn std::fmt::Debug,
+    }
+
+    let traited: Box<Traited> = Traited::new([9], "text");
+    assert_eq!(traited.header, [9]);
+    assert_eq!(format!("{:?}", &traited.value), "\"text\"");
+}
+
+#[test]
+fn default_parameters() {
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    struct Foo<T = u32> {
+        pub head: T,
+        pub tail: [T],
+    }
+
+    let foo: Box<Foo> = Foo::new(1, [2, 3]);
+    assert_eq!(foo.head, 1u32);
+    assert_eq!(&foo.tail, [2, 3]);
+
+    let bar: Box<Foo<u8>> = Foo::new(1, [2]);
+    assert_eq!(bar.head, 1u8);
+
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    struct Both<T = u8, const N: usize = 2>
+    where
+        T: Copy + Default,
+    {
+        pub head: [T; N],
+        pub tail: [T],
+    }
+
+    let both: Box<Both> = Both::new([1, 2], [3]);
+    assert_eq!(both.head, [1u8, 2]);
+    assert_eq!(&both.tail, [3]);
+
+    let other: Box<Both<i64, 1>> = Both::new([-1], []);
+    assert_eq!(other.head, [-1]);
+    assert!(other.tail.is_empty());
+}
+
+fn is_aligned<T: ?Sized>(value: &T, align: usize) -> bool {
+    ((value as *const T).cast::<u8>() as usize) % align == 0
+}
+
+#[test]
+fn repr_align() {
+    #[repr(C, align(64))]
+    #[derive(DynStruct)]
+    struct Slice {
+        pub inner: u8,
+        pub values: [u32],
+    }
+
+    #[repr(align(64), C)]
+    #[derive(DynStruct)]
+    struct Str {
+        pub inner: u8,
+        pub name: str,
+    }
+
+    #[repr(C, align(64))]
+    #[derive(DynStruct)]
+    struct Dyn {
+        pub inner: u8,
+        pub value: dyn std::fmt::Debug,
+    }
+
+    #[repr(C, align(64))]
+    #[derive(DynStruct)]
+    struct Nested {
+        pub inner: u8,
+        pub slice: Slice,
+    }
+
+    let slice = Slice::new(1, vec![2, 3, 4]);
+    assert!(is_aligned(&*slice, 64));
+    assert_eq!(std::mem::size_of_val(&*slice), 64);
+    assert_eq!(slice.inner, 1);
+    assert_eq!(&slice.values, &[2, 3, 4]);
+
+    let empty = Slice::new(1, vec![]);
+    assert!(is_aligned(&*empty, 64));
+    assert!(empty.values.is_empty());
+
+    let long = Slice::new(1, 0..100);
+    assert!(is_aligned(&*long, 64));
+    assert!(long.values.iter().copied().eq(0..100));
+
+    let name = Str::new(1, "aligned");
+    assert!(is_aligned(&*name, 64));
+    assert_eq!(name.inner, 1);
+    assert_eq!(&name.name, "aligned");
+
+    let value = Dyn::new(1, 2u16);
+    assert!(is_aligned(&*value, 64));
+    assert_eq!(value.inner, 1);
+    assert_eq!(format!("{:?}", &value.value), "2");
+
+    let nested = Nested::new(1, Slice::new(2, vec![3]));
+    assert!(is_aligned(&*nested, 64));
+    assert!(is_aligned(&nested.slice, 64));
+    assert_eq!(nested.inner, 1);
+    assert_eq!(nested.slice.inner, 2);
+    assert_eq!(&nested.slice.values, &[3]);
+}
+
+#[test]
+fn padded_header() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Padded {
+        pub wide: u64,
+        pub narrow: u8,
+        pub values: [u8],
+    }
+
+    // the tail starts directly after `narrow`, inside what would be the padding of the header
+    let padded = Padded::new(1, 2, vec![3, 4, 5]);
+    assert_eq!(std::mem::size_of_val(&*padded), 16);
+    assert_eq!(padded.wide, 1);
+    assert_eq!(padded.narrow, 2);
+    assert_eq!(&padded.values, &[3, 4, 5]);
+    assert_eq!(
+        padded.values.as_ptr() as usize - (&*padded as *const Padded).cast::<u8>() as usize,
+        9
+    );
+}
+
+#[test]
+fn tuple_struct() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Payload(pub u32, pub [u8]);
+
+    let payload = Payload::new(7, vec![1, 2, 3]);
+    assert_eq!(payload.0, 7);
+    assert_eq!(&payload.1, &[1, 2, 3]);
+
+    let Payload(number, bytes) = &*payload;
+    assert_eq!(*number, 7);
+    assert_eq!(bytes, &[1, 2, 3]);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Padded<T>(pub u64, pub T, pub str);
+
+    let padded = Padded::new(1, 2u8, "tuple");
+    let Padded(wide, narrow, name) = &*padded;
+    assert_eq!(*wide, 1);
+    assert_eq!(*narrow, 2);
+    assert_eq!(name, "tuple");
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Job(pub u8, pub dyn Fn() -> u8);
+
+    let job = Job::new(3, || 4);
+    let Job(priority, run) = &*job;
+    assert_eq!(*priority, 3);
+    assert_eq!(run(), 4);
+}
+
+#[test]
+fn tuple_struct_only_tail() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Bytes(pub [u8]);
+
+    let bytes = Bytes::new(vec![1, 2, 3]);
+    let Bytes(inner) = &*bytes;
+    assert_eq!(inner, &[1, 2, 3]);
+
+    let empty = Bytes::new(vec![]);
+    assert!(empty.0.is_empty());
+}
+
+#[test]
+fn only_tail() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Bytes {
+        pub data: [u8],
+    }
+
+    let bytes = Bytes::new(vec![1, 2, 3]);
+    assert_eq!(&bytes.data, &[1, 2, 3]);
+    assert_eq!(std::mem::size_of_val(&*bytes), 3);
+
+    // nothing is allocated at all
+    let empty = Bytes::new(vec![]);
+    assert!(empty.data.is_empty());
+    assert_eq!(std::mem::size_of_val(&*empty), 0);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Words {
+        pub data: [u64],
+    }
+
+    let empty = Words::new(vec![]);
+    assert!(empty.data.is_empty());
+    assert!(is_aligned(&*empty, std::mem::align_of::<u64>()));
+
+    let words = Words::new(vec![1, 2]);
+    assert!(is_aligned(&*words, std::mem::align_of::<u64>()));
+    assert_eq!(&words.data, &[1, 2]);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Name {
+        pub name: str,
+    }
+
+    let name = Name::new("name");
+    assert_eq!(&name.name, "name");
+    assert_eq!(&Name::new("").name, "");
+}
+
+#[test]
+fn renamed_constructor() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(constructor = "new_unchecked")]
+    struct Sorted {
+        pub min: u32,
+        pub values: [u32],
+    }
+
+    impl Sorted {
+        fn new(values: Vec<u32>) -> Option<Box<Sorted>> {
+            if values.windows(2).all(|pair| pair[0] <= pair[1]) {
+                Some(Sorted::new_unchecked(
+                    values.first().copied().unwrap_or(0),
+                    values,
+                ))
+            } else {
+                None
+            }
+        }
+    }
+
+    let sorted = Sorted::new(vec![1, 2, 3]).unwrap();
+    assert_eq!(sorted.min, 1);
+    assert_eq!(&sorted.values, &[1, 2, 3]);
+    assert!(Sorted::new(vec![3, 2, 1]).is_none());
+
+    let unchecked = Sorted::new_unchecked(0, vec![3, 2, 1]);
+    assert_eq!(&unchecked.values, &[3, 2, 1]);
+}
+
+mod restricted {
+    pub mod inner {
+        use dyn_struct::DynStruct;
+
+        #[repr(C)]
+        #[derive(DynStruct)]
+        #[dyn_struct(vis = "pub(crate)")]
+        pub struct Checked {
+            pub values: [u32],
+        }
+
+        #[repr(C)]
+        #[derive(DynStruct)]
+        pub(super) struct Inherited {
+            pub values: [u32],
+        }
+    }
+
+    #[test]
+    fn visibility() {
+        let checked = inner::Checked::new(vec![1, 2]);
+        assert_eq!(&checked.values, &[1, 2]);
+
+        let inherited = inner::Inherited::new(vec![3]);
+        assert_eq!(&inherited.values, &[3]);
+    }
+}
+
+#[test]
+fn skip_new() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(skip_new)]
+    struct Counted {
+        pub count: usize,
+        pub values: [u8],
+    }
+
+    impl Counted {
+        fn new(values: &[u8]) -> Box<Counted> {
+            let dyn_struct = dyn_struct::DynStruct::from_slice(values.len(), values);
+            let ptr = Box::into_raw(dyn_struct);
+            unsafe { Box::from_raw(ptr as *mut Counted) }
+        }
+    }
+
+    let counted = Counted::new(&[1, 2, 3]);
+    assert_eq!(counted.count, 3);
+    assert_eq!(&counted.values, &[1, 2, 3]);
+}
+
+#[test]
+fn try_new() {
+    use std::rc::Rc;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(try_new)]
+    struct Values {
+        pub owner: Rc<()>,
+        pub values: [u32],
+    }
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(try_new, constructor = "make")]
+    struct Named {
+        pub id: u32,
+        pub name: str,
+    }
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(try_new, skip_new)]
+    struct Job {
+        pub id: u32,
+        pub run: dyn Fn() -> u32,
+    }
+
+    let owner = Rc::new(());
+
+    let values = Values::try_new(owner.clone(), vec![1, 2, 3]).unwrap();
+    assert_eq!(&values.values, &[1, 2, 3]);
+    drop(values);
+
+    // the layout computation overflows long before anything is allocated
+    let result = Values::try_new(owner.clone(), (0..usize::MAX).map(|i| i as u32));
+    assert!(matches!(
+        result,
+        Err(dyn_struct::TryNewError::CapacityOverflow)
+    ));
+    assert_eq!(Rc::strong_count(&owner), 1);
+
+    let named = Named::try_make(1, "name").unwrap();
+    assert_eq!(&named.name, "name");
+    assert_eq!(&Named::make(2, "other").name, "other");
+
+    let job = Job::try_new(1, || 2).unwrap();
+    assert_eq!(job.id, 1);
+    assert_eq!((job.run)(), 2);
+}
+
+#[test]
+fn from_slice() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Names {
+        pub count: u8,
+        pub names: [String],
+    }
+
+    let names = [String::from("a"), String::from("b")];
+    let cloned = Names::new_from_slice(2, &names);
+    assert_eq!(cloned.count, 2);
+    assert_eq!(&cloned.names, &names);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Generic<T> {
+        pub values: [T],
+    }
+
+    let generic = Generic::new_from_slice(&[1, 2, 3]);
+    assert_eq!(&generic.values, &[1, 2, 3]);
+
+    // elements that are not `Clone` can still use `new`
+    struct Unique(u8);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Uniques {
+        pub values: [Unique],
+    }
+
+    let uniques = Uniques::new(vec![Unique(1)]);
+    assert_eq!(uniques.values[0].0, 1);
+}
+
+#[test]
+fn from_iter() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::rc::Rc;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Values<T> {
+        pub owner: Rc<()>,
+        pub values: [T],
+    }
+
+    let owner = Rc::new(());
+
+    // elements are written straight into the allocation as they are produced
+    let doubled = Values::new(owner.clone(), (0..100u32).map(|i| i * 2));
+    assert!(doubled.values.iter().copied().eq((0..100).map(|i| i * 2)));
+    drop(doubled);
+
+    let tail = Rc::new(());
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        Values::new(
+            owner.clone(),
+            (0..4).map(|i| if i == 2 { panic!("boom") } else { tail.clone() }),
+        )
+    }));
+    assert!(result.is_err());
+    assert_eq!(Rc::strong_count(&owner), 1);
+    assert_eq!(Rc::strong_count(&tail), 1);
+}
+
+#[test]
+fn shared() {
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(rc, arc)]
+    struct Shared {
+        pub id: u32,
+        pub values: [u32],
+    }
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(arc)]
+    struct Task {
+        pub id: u32,
+        pub run: dyn Fn() -> u32 + Send + Sync,
+    }
+
+    let rc: Rc<Shared> = Shared::new_rc(1, vec![2, 3]);
+    let clone = rc.clone();
+    assert_eq!(clone.id, 1);
+    assert_eq!(&clone.values, &[2, 3]);
+
+    let arc: Arc<Shared> = Shared::new_arc(4, vec![5, 6, 7]);
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let arc = arc.clone();
+            std::thread::spawn(move || arc.values.iter().sum::<u32>())
+        })
+        .collect();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), 18);
+    }
+    assert_eq!(arc.id, 4);
+
+    let task = Task::new_arc(8, || 9);
+    let other = task.clone();
+    assert_eq!(std::thread::spawn(move || (other.run)()).join().unwrap(), 9);
+    assert_eq!(task.id, 8);
+}
+
+#[test]
+fn zeroed() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(zeroable)]
+    struct Buffer {
+        pub id: u64,
+        pub flags: u8,
+        pub bytes: [u8],
+    }
+
+    // the tail starts inside the trailing padding of the header, which must still be zero
+    let buffer = Buffer::new_zeroed(1, 2, 4096);
+    assert_eq!(buffer.id, 1);
+    assert_eq!(buffer.flags, 2);
+    assert_eq!(buffer.bytes.len(), 4096);
+    assert!(buffer.bytes.iter().all(|&byte| byte == 0));
+
+    let short = Buffer::new_zeroed(1, 2, 3);
+    assert_eq!(&short.bytes, &[0, 0, 0]);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(zeroable)]
+    struct Generic<T> {
+        pub len: usize,
+        pub values: [T],
+    }
+
+    let generic = Generic::<[u16; 2]>::new_zeroed(5, 10);
+    assert_eq!(generic.len, 5);
+    assert_eq!(&generic.values, &[[0, 0]; 10]);
+}
+
+#[test]
+fn uninit() {
+    use std::mem::MaybeUninit;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(uninit)]
+    pub struct Decoded {
+        pub kind: u8,
+        pub values: [String],
+    }
+
+    let mut decoded: Box<DecodedUninit> = Decoded::new_uninit(3, 2);
+    assert_eq!(decoded.kind, 3);
+    assert_eq!(decoded.values.len(), 2);
+    for (i, value) in decoded.tail_uninit_mut().iter_mut().enumerate() {
+        value.write(i.to_string());
+    }
+    let decoded: Box<Decoded> = unsafe { decoded.assume_init() };
+    assert_eq!(decoded.kind, 3);
+    assert_eq!(&decoded.values, &["0", "1"]);
+
+    // dropping an uninitialized value only drops the header
+    let partial = Decoded::new_uninit(1, 100);
+    drop(partial);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(uninit)]
+    struct Pair<T>(pub T, pub [T]);
+
+    let mut pair = Pair::new_uninit(String::from("a"), 1);
+    pair.tail_uninit_mut()[0] = MaybeUninit::new(String::from("b"));
+    let pair = unsafe { pair.assume_init() };
+    assert_eq!(pair.0, "a");
+    assert_eq!(&pair.1, &["b"]);
+}
+
+#[test]
+fn from_vec() {
+    use std::rc::Rc;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Names {
+        pub id: u32,
+        pub names: [String],
+    }
+
+    let names = Names::new_from_vec(1, vec![String::from("a"), String::from("b")]);
+    assert_eq!(names.id, 1);
+    assert_eq!(&names.names, &["a", "b"]);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Owners<T> {
+        pub owner: T,
+        pub values: [T],
+    }
+
+    let counter = Rc::new(());
+    let owners = Owners::new_from_vec(counter.clone(), vec![counter.clone(), counter.clone()]);
+    assert_eq!(owners.values.len(), 2);
+    assert_eq!(Rc::strong_count(&counter), 4);
+    drop(owners);
+    assert_eq!(Rc::strong_count(&counter), 1);
+
+    let empty = Owners::new_from_vec(counter.clone(), Vec::new());
+    assert!(empty.values.is_empty());
+    drop(empty);
+    assert_eq!(Rc::strong_count(&counter), 1);
+}
+
+#[test]
+fn into_parts() {
+    use std::rc::Rc;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Message {
+        pub id: u32,
+        pub owner: Rc<()>,
+        pub values: [Rc<()>],
+    }
+
+    let counter = Rc::new(());
+    let message = Message::new(1, counter.clone(), vec![counter.clone(), counter.clone()]);
+    let (id, owner, values) = message.into_parts();
+    assert_eq!(id, 1);
+    assert_eq!(values.len(), 2);
+    assert_eq!(Rc::strong_count(&counter), 4);
+    drop((owner, values));
+    assert_eq!(Rc::strong_count(&counter), 1);
+
+    let message = Message::new(2, counter.clone(), vec![counter.clone()]);
+    let values: Vec<Rc<()>> = message.into_vec();
+    assert_eq!(Rc::strong_count(&counter), 2);
+    drop(values);
+    assert_eq!(Rc::strong_count(&counter), 1);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Named(pub String, pub str);
+
+    let named = Named::new(String::from("owner"), "name");
+    let (owner, name) = named.into_parts();
+    assert_eq!(owner, "owner");
+    assert_eq!(name, "name");
+    assert_eq!(Named::new(String::new(), "other").into_string(), "other");
+}
+
+#[test]
+fn len() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Values {
+        pub id: u32,
+        pub values: [u64],
+    }
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Name {
+        pub name: str,
+    }
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(no_len)]
+    struct Custom {
+        pub values: [u8],
+    }
+
+    impl Custom {
+        fn len(&self) -> u8 {
+            self.values.len() as u8
+        }
+    }
+
+    // called through a `&Box<_>`
+    let values = &Values::new(1, vec![2, 3, 4]);
+    assert_eq!(values.len(), 3);
+    assert!(!values.is_empty());
+    assert!(Values::new(1, vec![]).is_empty());
+
+    let name = &Name::new("ß");
+    assert_eq!(name.len(), 2);
+    assert!(Name::new("").is_empty());
+
+    assert_eq!(Custom::new(vec![1, 2]).len(), 2u8);
+}
+
+#[test]
+fn as_bytes() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(pod)]
+    struct Packet {
+        pub id: [u8; 2],
+        pub kind: u8,
+        pub payload: [u8],
+    }
+
+    let mut packet = Packet::new([1, 2], 3, vec![4, 5]);
+    assert_eq!(packet.as_bytes(), &[1, 2, 3, 4, 5]);
+
+    packet.as_bytes_mut()[2] = 6;
+    assert_eq!(packet.kind, 6);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(pod)]
+    struct Words<T> {
+        pub count: u32,
+        pub words: [T],
+    }
+
+    let words = Words::new(2, vec![3u32, 4]);
+    let expected: Vec<u8> = [2u32, 3, 4]
+        .iter()
+        .flat_map(|word| word.to_ne_bytes())
+        .collect();
+    assert_eq!(words.as_bytes(), &expected[..]);
+    assert_eq!(Words::<u32>::new(0, vec![]).as_bytes(), &[0; 4]);
+}
+
+#[test]
+fn clone() {
+    use std::rc::Rc;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(clone)]
+    struct Message {
+        pub owner: Rc<()>,
+        pub values: [Rc<()>],
+    }
+
+    let counter = Rc::new(());
+    let message = Message::new(counter.clone(), vec![counter.clone(), counter.clone()]);
+    let copy = message.clone();
+    assert_eq!(copy.values.len(), 2);
+    assert_eq!(Rc::strong_count(&counter), 7);
+    drop(message);
+    assert_eq!(Rc::strong_count(&counter), 4);
+    drop(copy.clone_box());
+    drop(copy);
+    assert_eq!(Rc::strong_count(&counter), 1);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(clone)]
+    struct Named<T>(pub T, pub str);
+
+    let named = Named::new(vec![1u8], "name");
+    let copy = named.clone();
+    assert_eq!(copy.0, [1]);
+    assert_eq!(&copy.1, "name");
+}
+
+#[test]
+fn eq_hash() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashMap;
+    use std::hash::{Hash, Hasher};
+    use std::marker::PhantomData;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(eq, hash)]
+    struct Path<Unit> {
+        pub unit: PhantomData<Unit>,
+        pub id: u32,
+        pub points: [(i32, i32)],
+    }
+
+    // `Unit` itself has no bounds, only the fields do.
+    struct Meters;
+
+    let mut lengths = HashMap::new();
+    lengths.insert(Path::<Meters>::new(PhantomData, 1, vec![(0, 0), (3, 4)]), 5);
+    lengths.insert(Path::new(PhantomData, 1, vec![(0, 0)]), 0);
+    lengths.insert(Path::new(PhantomData, 2, vec![(0, 0), (3, 4)]), 5);
+    assert_eq!(lengths.len(), 3);
+    let key = Path::new(PhantomData, 1, vec![(0, 0), (3, 4)]);
+    assert_eq!(lengths.get(&key), Some(&5));
+    assert!(key != Path::new(PhantomData, 1, vec![(0, 0), (3, 4), (0, 0)]));
+    assert!(key != Path::new(PhantomData, 1, vec![]));
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(partial_eq, hash)]
+    struct Labeled {
+        pub prefix: String,
+        pub rest: str,
+    }
+
+    fn hash(value: &impl Hash) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    let label = Labeled::new("ab".into(), "c");
+    assert!(*label == *Labeled::new("ab".into(), "c"));
+    assert_eq!(hash(&label), hash(&Labeled::new("ab".into(), "c")));
+    // Moving bytes from the header into the tail gives a different value, and a different hash.
+    assert!(*label != *Labeled::new("a".into(), "bc"));
+    assert_ne!(hash(&label), hash(&Labeled::new("a".into(), "bc")));
+    assert_ne!(hash(&label), hash(&Labeled::new("ab".into(), "")));
+}
+
+#[test]
+fn empty() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(default)]
+    struct Values {
+        pub id: u32,
+        pub names: Vec<String>,
+        pub values: [u64],
+    }
+
+    let values = Values::empty(3, vec![String::from("a")]);
+    assert_eq!(values.id, 3);
+    assert_eq!(values.names, ["a"]);
+    assert!(values.values.is_empty());
+    assert!(is_aligned(&values.values, 8));
+
+    #[derive(Default)]
+    struct Outer {
+        values: Box<Values>,
+    }
+
+    let outer = Outer::default();
+    assert_eq!(outer.values.id, 0);
+    assert!(outer.values.names.is_empty());
+    assert!(outer.values.values.is_empty());
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(default)]
+    struct Name(pub str);
+
+    let name = Box::<Name>::default();
+    assert_eq!(&name.0, "");
+    assert_eq!(std::mem::size_of_val(&*name), 0);
+}
+
+#[test]
+fn empty_zero_sized() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(default)]
+    struct Unit {
+        pub unit: (),
+        pub align: [u64; 0],
+        pub values: [()],
+    }
+
+    let unit = Box::<Unit>::default();
+    assert!(unit.values.is_empty());
+    assert_eq!(std::mem::size_of_val(&*unit), 0);
+    assert!(is_aligned(&*unit, 8));
+
+    #[repr(C, align(32))]
+    #[derive(DynStruct)]
+    #[dyn_struct(default)]
+    struct Aligned {
+        pub values: [u8],
+    }
+
+    let aligned = Box::<Aligned>::default();
+    assert!(aligned.values.is_empty());
+    assert_eq!(std::mem::size_of_val(&*aligned), 0);
+    assert!(is_aligned(&*aligned, 32));
+}
+
+#[test]
+fn builder() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(builder)]
+    struct Message {
+        pub id: u32,
+        pub sender: String,
+        pub values: [u16],
+    }
+
+    let message = Message::builder()
+        .sender(String::from("me"))
+        .id(3)
+        .values([1, 2])
+        .extend_values(3..5)
+        .build()
+        .unwrap();
+    assert_eq!(message.id, 3);
+    assert_eq!(message.sender, "me");
+    assert_eq!(&message.values, [1, 2, 3, 4]);
+
+    let empty = Message::builder()
+        .id(1)
+        .sender(String::new())
+        .build()
+        .unwrap();
+    assert!(empty.values.is_empty());
+
+    let missing = Message::builder().id(1).values(vec![1]).build();
+    assert!(matches!(
+        missing,
+        Err(dyn_struct::MissingField { field: "sender" })
+    ));
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(builder)]
+    struct Name {
+        pub name: str,
+    }
+
+    let name = Name::builder()
+        .name("dyn")
+        .extend_name("_struct")
+        .build()
+        .unwrap();
+    assert_eq!(&name.name, "dyn_struct");
+    assert_eq!(&Name::builder().build().unwrap().name, "");
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(builder)]
+    struct Tagged<T: Copy> {
+        pub tag: T,
+        pub values: [T],
+    }
+
+    let tagged = Tagged::builder().tag('a').values(['b']).build().unwrap();
+    assert_eq!(tagged.tag, 'a');
+    assert_eq!(&tagged.values, ['b']);
+}
+
+#[test]
+fn into() {
+    #[derive(Debug, PartialEq)]
+    struct SymbolId(u32);
+
+    impl From<u32> for SymbolId {
+        fn from(id: u32) -> Self {
+            SymbolId(id)
+        }
+    }
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(try_new)]
+    struct Symbol {
+        #[dyn_struct(into)]
+        pub id: SymbolId,
+        pub flags: u8,
+        #[dyn_struct(into)]
+        pub owner: String,
+        pub name: str,
+    }
+
+    let symbol = Symbol::new(3u32, 1, "owner", "name");
+    assert_eq!(symbol.id, SymbolId(3));
+    assert_eq!(symbol.flags, 1);
+    assert_eq!(symbol.owner, "owner");
+    assert_eq!(&symbol.name, "name");
+
+    let symbol = Symbol::try_new(SymbolId(4), 2, String::from("other"), "").unwrap();
+    assert_eq!(symbol.id, SymbolId(4));
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Values(#[dyn_struct(into)] pub u64, pub [u8]);
+
+    let values = Values::new(7u8, vec![1, 2]);
+    assert_eq!(values.0, 7);
+    assert_eq!(&values.1, [1, 2]);
+}
+
+mod getters {
+    use dyn_struct::DynStruct;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct Id(u32);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(getters)]
+    pub struct Symbol {
+        #[dyn_struct(copy)]
+        id: Id,
+        flags: (u8, bool),
+        owner: String,
+        #[dyn_struct(vis = "pub(crate)")]
+        hidden: u16,
+        name: str,
+    }
+
+    #[test]
+    fn getters() {
+        let symbol = Symbol::new(Id(1), (2, true), String::from("owner"), 4, "name");
+        let id: Id = symbol.id();
+        let flags: (u8, bool) = symbol.flags();
+        let owner: &String = symbol.owner();
+        let hidden: u16 = symbol.hidden();
+        let name: &str = symbol.name();
+        assert_eq!(id, Id(1));
+        assert_eq!(flags, (2, true));
+        assert_eq!(owner, "owner");
+        assert_eq!(hidden, 4);
+        assert_eq!(name, "name");
+    }
+}
+
+#[test]
+fn raw_identifiers() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(try_new, zeroable, uninit, builder, clone, default)]
+    struct r#Token {
+        r#type: u8,
+        r#loop: (),
+        #[dyn_struct(into)]
+        new: u32,
+        header: u16,
+        header_end: u16,
+        ptr: u16,
+        r#len: usize,
+        r#in: [u8],
+    }
+
+    let token = Token::new(1, (), 2u8, 3, 4, 5, 6, vec![7, 8]);
+    assert_eq!((token.r#type, token.new), (1, 2));
+    assert_eq!((token.header, token.header_end, token.ptr), (3, 4, 5));
+    assert_eq!(token.r#len, 6);
+    assert_eq!(&token.r#in, [7, 8]);
+    assert_eq!(&token.clone().r#in, [7, 8]);
+
+    let token = Token::builder()
+        .r#type(1)
+        .r#loop(())
+        .new(2)
+        .header(3)
+        .header_end(4)
+        .ptr(5)
+        .r#len(6)
+        .extend_in([7])
+        .build()
+        .unwrap();
+    assert_eq!(&token.r#in, [7]);
+    assert!(matches!(
+        Token::builder().build(),
+        Err(dyn_struct::MissingField { field: "type" })
+    ));
+
+    assert_eq!(&Token::new_zeroed(1, (), 2, 3, 4, 5, 6, 2).r#in, [0, 0]);
+    assert_eq!(Token::new_uninit(1, (), 2, 3, 4, 5, 6, 2).r#in.len(), 2);
+    assert!(Box::<Token>::default().r#in.is_empty());
+    assert!(Token::try_new(1, (), 2u8, 3, 4, 5, 6, vec![]).is_ok());
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(getters)]
+    struct Keyword {
+        r#type: u8,
+        r#in: str,
+    }
+
+    let keyword = Keyword::new(1, "in");
+    assert_eq!(keyword.r#type(), 1);
+    assert_eq!(keyword.r#in(), "in");
+}
+
+#[test]
+fn phantom_fields() {
+    use std::marker::PhantomData;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(zeroable, uninit, builder, default)]
+    struct Borrowed<'a, T: Copy> {
+        pub id: u32,
+        #[dyn_struct(skip)]
+        pub lifetime: PhantomData<&'a ()>,
+        #[dyn_struct(skip)]
+        pub element: PhantomData<fn() -> T>,
+        pub values: [T],
+    }
+
+    let borrowed = Borrowed::new(1, [2u8, 3]);
+    assert_eq!(borrowed.id, 1);
+    assert_eq!(&borrowed.values, [2, 3]);
+    assert_eq!(&Borrowed::new_from_vec(1, vec![4u8]).values, [4]);
+    assert!(Borrowed::<u8>::empty(1).values.is_empty());
+    assert_eq!(&Borrowed::<u8>::new_zeroed(1, 2).values, [0, 0]);
+    assert_eq!(Borrowed::<u8>::new_uninit(1, 2).values.len(), 2);
+    assert_eq!(Box::<Borrowed<u8>>::default().id, 0);
+    let built = Borrowed::builder().id(5).values([6u16]).build().unwrap();
+    assert_eq!((built.id, &built.values), (5, &[6][..]));
+
+    // Without `skip`, the marker is an ordinary parameter.
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Explicit<T> {
+        pub marker: PhantomData<T>,
+        pub values: [u8],
+    }
+
+    let explicit = Explicit::<String>::new(PhantomData, [1]);
+    assert_eq!(&explicit.values, [1]);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Task<'a> {
+        pub priority: u8,
+        #[dyn_struct(skip)]
+        pub lifetime: PhantomData<&'a mut ()>,
+        pub job: dyn Fn() -> u8 + 'a,
+    }
+
+    let value = 3;
+    let task = Task::new(1, || value);
+    assert_eq!((task.job)(), 3);
+}
+
+fn tail_offset<T: ?Sized, U: ?Sized>(value: &T, tail: &U) -> usize {
+    (tail as *const U).cast::<u8>() as usize - (value as *const T).cast::<u8>() as usize
+}
+
+#[test]
+fn layout_constants() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Padded {
+        pub wide: u64,
+        pub narrow: u8,
+        pub values: [u16],
+    }
+
+    assert_eq!(Padded::HEADER_LAYOUT.size(), 9);
+    assert_eq!(Padded::HEADER_LAYOUT.align(), 8);
+    assert_eq!(Padded::TAIL_OFFSET, 10);
+
+    // The layout of a tail with a known length is a constant, which can size a buffer.
+    const LAYOUT: std::alloc::Layout = Padded::layout_for_len(3);
+    let buffer = [0u8; Padded::layout_for_len(3).size()];
+    assert_eq!((LAYOUT.size(), LAYOUT.align(), buffer.len()), (16, 8, 16));
+    for len in [0, 1, 3, 4, 11] {
+        let padded = Padded::new(1, 2, vec![3; len]);
+        assert_eq!(tail_offset(&*padded, &padded.values), Padded::TAIL_OFFSET);
+        assert_eq!(
+            std::mem::size_of_val(&*padded),
+            Padded::layout_for_len(len).size()
+        );
+        assert_eq!(
+            std::mem::align_of_val(&*padded),
+            Padded::layout_for_len(len).align()
+        );
+    }
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Name<T> {
+        pub id: T,
+        pub name: str,
+    }
+
+    let name = Name::new(1u32, "abc");
+    assert_eq!(tail_offset(&*name, &name.name), Name::<u32>::TAIL_OFFSET);
+    assert_eq!(Name::<u32>::TAIL_OFFSET, 4);
+    assert_eq!(
+        std::mem::size_of_val(&*name),
+        Name::<u32>::layout_for_len(3).size()
+    );
+    assert_eq!(Name::<u16>::TAIL_OFFSET, 2);
+
+    #[repr(C, align(64))]
+    #[derive(DynStruct)]
+    struct Aligned {
+        pub inner: u8,
+        pub values: [u8],
+    }
+
+    let aligned = Aligned::new(1, vec![2; 70]);
+    assert_eq!(
+        tail_offset(&*aligned, &aligned.values),
+        Aligned::TAIL_OFFSET
+    );
+    assert_eq!(Aligned::TAIL_OFFSET, 1);
+    assert_eq!(Aligned::HEADER_LAYOUT.align(), 64);
+    assert_eq!(Aligned::layout_for_len(70).size(), 128);
+    assert_eq!(std::mem::size_of_val(&*aligned), 128);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Job {
+        pub priority: u8,
+        pub run: dyn Fn() -> u8,
+    }
+
+    assert_eq!(Job::HEADER_LAYOUT.size(), 1);
+    assert_eq!(Job::HEADER_LAYOUT.align(), 1);
+}
+
+#[test]
+#[should_panic]
+fn layout_for_len_overflow() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Words {
+        pub inner: u8,
+        pub values: [u64],
+    }
+
+    Words::layout_for_len(usize::MAX / 4);
+}
+
+#[test]
+fn raw_parts() {
+    use std::ffi::c_void;
+
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    struct Packet {
+        pub id: u32,
+        pub payload: [String],
+    }
+
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    struct Name {
+        pub id: u8,
+        pub name: str,
+    }
+
+    // Receives the parts across an FFI boundary, and takes ownership again.
+    extern "C" fn shim(ptr: *mut c_void, len: usize) -> u32 {
+        let packet = unsafe { Packet::from_raw_parts(ptr.cast(), len) };
+        assert_eq!(&packet.payload, &["a", "bc"]);
+        packet.id
+    }
+
+    let packet = Packet::new(1, vec!["a".to_string(), "bc".to_string()]);
+    let (ptr, len) = packet.into_raw_parts();
+    assert_eq!(len, 2);
+    assert_eq!(shim(ptr.cast(), len), 1);
+
+    let name = Name::new(2, "name");
+    let (ptr, len) = name.into_raw_parts();
+    assert_eq!(len, 4);
+    let name = unsafe { Name::from_raw_parts(ptr, len) };
+    assert_eq!(name.id, 2);
+    assert_eq!(&name.name, "name");
+}
+
+#[test]
+fn dyn_struct_header() {
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    #[dyn_struct(header = "PacketHeader")]
+    struct Packet {
+        pub id: u32,
+        pub kind: u32,
+        pub payload: [u16],
+    }
+
+    let mut packet = Packet::new(1, 2, vec![3, 4]);
+    assert_eq!(packet.as_dyn_struct().header.id, 1);
+    assert_eq!(packet.as_dyn_struct().header.kind, 2);
+    assert_eq!(&packet.as_dyn_struct().tail, &[3, 4]);
+
+    packet.as_dyn_struct_mut().header.kind = 5;
+    packet.as_dyn_struct_mut().tail[1] = 6;
+    assert_eq!(packet.kind, 5);
+    assert_eq!(&packet.payload, &[3, 6]);
+
+    let (header, mut payload) = DynStruct::into_parts(packet.into_dyn_struct());
+    payload.push(8);
+    let extended = DynStruct::from_vec(PacketHeader { id: 7, ..header }, payload);
+    let packet = Packet::from_dyn_struct(extended);
+    assert_eq!(packet.id, 7);
+    assert_eq!(packet.kind, 5);
+    assert_eq!(&packet.payload, &[3, 6, 8]);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(header = "PairHeader")]
+    struct Pair<T>(pub T, pub [String]);
+
+    let pair = Pair::from_dyn_struct(DynStruct::new(PairHeader(1u64), vec!["a".to_string()]));
+    assert_eq!(pair.0, 1);
+    assert_eq!(&pair.1, &["a"]);
+    assert_eq!(pair.into_dyn_struct().header.0, 1);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(header = "ListHeader")]
+    struct List<'a, T> {
+        pub len: u32,
+        pub items: [&'a T],
+    }
+
+    let (a, b) = (1, 2);
+    let list = List::new(2, vec![&a, &b]);
+    assert_eq!(list.as_dyn_struct().header.len, 2);
+    assert_eq!(*list.as_dyn_struct().tail[1], 2);
+}
+
+#[test]
+fn same_repr() {
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    struct Packet {
+        pub id: u32,
+        pub kind: u32,
+        pub payload: [u16],
+    }
+
+    // Both start with two `u32`s, which are followed by the payload.
+    unsafe impl dyn_struct::SameRepr<Packet> for DynStruct<[u32; 2], u16> {}
+
+    let packet = DynStruct::into_repr::<Packet>(DynStruct::new([1, 2], [3u16, 4, 5]));
+    assert_eq!((packet.id, packet.kind), (1, 2));
+    assert_eq!(&packet.payload, &[3, 4, 5]);
+    assert_eq!(
+        std::alloc::Layout::for_value(&*packet),
+        Packet::layout_for_len(3)
+    );
+
+    let value = DynStruct::<[u32; 2], u16>::from_repr(packet);
+    assert_eq!(value.header, [1, 2]);
+    assert_eq!(&value.tail, &[3, 4, 5]);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Named<T> {
+        pub id: u64,
+        pub names: [T],
+    }
+
+    unsafe impl<T> dyn_struct::SameRepr<Named<T>> for DynStruct<u64, T> {}
+
+    let names = vec![String::from("a"), String::from("b")];
+    let named = DynStruct::into_repr::<Named<String>>(DynStruct::from_vec(7, names));
+    assert_eq!(named.id, 7);
+    assert_eq!(&named.names, &["a", "b"]);
+    let empty = DynStruct::<u64, String>::from_repr(Named::new(8, Vec::new()));
+    assert_eq!(empty.header, 8);
+    assert!(empty.tail.is_empty());
+}
+
+#[test]
+fn into_repr_checked() {
+    use dyn_struct::LayoutMismatch;
+    use std::alloc::Layout;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Packet {
+        pub id: u32,
+        pub kind: u32,
+        pub payload: [u16],
+    }
+
+    let value = DynStruct::new([1u32, 2], [3u16, 4]);
+    let packet = unsafe { DynStruct::into_repr_checked::<Packet>(value) }.unwrap();
+    assert_eq!((packet.id, packet.kind), (1, 2));
+    assert_eq!(&packet.payload, &[3, 4]);
+
+    // The header is missing a field, which moves the tail.
+    let value = DynStruct::new([1u32, 2, 3], [4u16]);
+    let mismatch = unsafe { DynStruct::into_repr_checked::<Packet>(value) }
+        .err()
+        .unwrap();
+    assert_eq!(
+        mismatch,
+        LayoutMismatch {
+            len: 1,
+            source: Layout::from_size_align(16, 4).unwrap(),
+            source_tail_offset: 12,
+            source_element: Layout::new::<u16>(),
+            target: Some(Layout::from_size_align(12, 4).unwrap()),
+            target_tail_offset: 8,
+            target_element: Layout::new::<u16>(),
+        }
+    );
+    assert_eq!(
+        mismatch.to_string(),
+        "the types are laid out differently with 1 elements in the tail: the source has size 16 \
+         and alignment 4, its tail at offset 12 with elements of size 2 and alignment 2; the \
+         target has size 12 and alignment 4, its tail at offset 8 with elements of size 2 and \
+         alignment 2"
+    );
+
+    // The elements differ, which an empty tail does not show in the size.
+    let value = DynStruct::new([1u32, 2], Vec::<u8>::new());
+    let mismatch = unsafe { DynStruct::into_repr_checked::<Packet>(value) }
+        .err()
+        .unwrap();
+    assert_eq!(mismatch.source, mismatch.target.unwrap());
+    assert_eq!(mismatch.source_element, Layout::new::<u8>());
+    assert_eq!(mismatch.target_element, Layout::new::<u16>());
+}
+
+#[test]
+fn field_attributes() {
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    #[dyn_struct(builder, getters, clone, header = "ConfigHeader")]
+    struct Config {
+        #[allow(dead_code)]
+        #[cfg_attr(test, dyn_struct(into))]
+        id: u32,
+        #[cfg(test)]
+        enabled: u32,
+        #[cfg(not(test))]
+        disabled: String,
+        #[cfg_attr(not(test), dyn_struct(into))]
+        #[deny(missing_docs)]
+        flags: u32,
+        values: [u8],
+    }
+
+    let config = Config::new(1u8, 2, 3, vec![4]);
+    assert_eq!(config.id(), 1);
+    assert_eq!(config.enabled(), 2);
+    assert_eq!(config.flags(), 3);
+    assert_eq!(config.values(), &[4]);
+    assert_eq!(config.clone_box().as_dyn_struct().header.enabled, 2);
+
+    let built = Config::builder()
+        .id(5)
+        .enabled(6)
+        .flags(7)
+        .values(vec![8])
+        .build()
+        .unwrap();
+    assert_eq!(built.into_parts(), (5, 6, 7, vec![8]));
+
+    // compiled both with and without the feature by the test suite
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(getters)]
+    struct Feature {
+        id: u8,
+        #[cfg(feature = "serde")]
+        serde: u64,
+        values: [u16],
+    }
+
+    #[cfg(feature = "serde")]
+    let feature = Feature::new(1, 2, vec![3]);
+    #[cfg(not(feature = "serde"))]
+    let feature = Feature::new(1, vec![3]);
+    assert_eq!(feature.id(), 1);
+    #[cfg(feature = "serde")]
+    assert_eq!(feature.serde(), 2);
+    assert_eq!(feature.values(), &[3]);
+    assert_eq!(
+        Feature::TAIL_OFFSET,
+        if cfg!(feature = "serde") { 16 } else { 2 }
+    );
+}
+
+#[test]
+fn lifetime_in_tail() {
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    #[dyn_struct(try_new, rc, clone, builder, getters)]
+    struct Spans<'a> {
+        count: u32,
+        spans: [&'a str],
+    }
+
+    // The spans borrow from a local arena, which outlives every value built from it.
+    let arena = String::from("one two three");
+    let words: Vec<&str> = arena.split(' ').collect();
+
+    let spans = Spans::new(3, words.iter().copied());
+    assert_eq!(spans.count(), 3);
+    assert_eq!(spans.spans(), &["one", "two", "three"]);
+
+    let shared = Spans::new_rc(2, words[1..].iter().copied());
+    assert_eq!(shared.spans(), &["two", "three"]);
+
+    let built = Spans::builder()
+        .count(1)
+        .spans(vec![&arena[4..7]])
+        .build()
+        .unwrap();
+    assert_eq!(built.clone_box().spans(), &["two"]);
+    assert_eq!(Spans::into_vec(built), vec!["two"]);
+
+    // The spans themselves may outlive the box that holds them.
+    fn first<'a>(spans: &Spans<'a>) -> &'a str {
+        spans.spans[0]
+    }
+    let first = {
+        let spans = Spans::try_new(1, vec![&arena[..3]]).unwrap();
+        first(&spans)
+    };
+    assert_eq!(first, "one");
+}
+
+#[test]
+fn associated_types() {
+    trait Codec {
+        type Meta;
+        type Item;
+    }
+
+    struct Bytes;
+    impl Codec for Bytes {
+        type Meta = u32;
+        type Item = u8;
+    }
+
+    struct Wrapped<C>(C);
+    impl<C: Codec> Codec for Wrapped<C> {
+        type Meta = (C::Meta, bool);
+        type Item = Option<C::Item>;
+    }
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(try_new, builder, getters, clone)]
+    struct Batch<C: Codec>
+    where
+        C::Meta: Clone,
+        C::Item: Clone,
+    {
+        meta: C::Meta,
+        items: [C::Item],
+    }
+
+    let batch = Batch::<Bytes>::new(1, vec![2, 3]);
+    assert_eq!(*batch.meta(), 1);
+    assert_eq!(batch.items(), &[2, 3]);
+    assert_eq!(batch.clone_box().items(), &[2, 3]);
+
+    let wrapped = Batch::<Wrapped<Bytes>>::builder()
+        .meta((4, true))
+        .items(vec![Some(5), None])
+        .build()
+        .unwrap();
+    assert_eq!(*wrapped.meta(), (4, true));
+    assert_eq!(wrapped.items(), &[Some(5), None]);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(try_new, header = "QualifiedHeader")]
+    struct Qualified<C: Codec> {
+        meta: <C as Codec>::Meta,
+        items: [<C as Codec>::Item],
+    }
+
+    let qualified = Qualified::<Wrapped<Bytes>>::try_new((6, false), vec![None]).unwrap();
+    assert_eq!(qualified.meta, (6, false));
+    assert_eq!(&qualified.items, &[None]);
+    assert_eq!(qualified.as_dyn_struct().header.meta, (6, false));
+
+    let qualified = Qualified::<Bytes>::new_from_slice(7, &[8, 9]);
+    assert_eq!(qualified.into_parts(), (7, vec![8, 9]));
+
+    trait Family {
+        type Member<T>;
+    }
+
+    struct Boxed;
+    impl Family for Boxed {
+        type Member<T> = Box<T>;
+    }
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(getters)]
+    struct Members<F: Family, T> {
+        first: <F as Family>::Member<T>,
+        rest: [F::Member<T>],
+    }
+
+    let members = Members::<Boxed, u8>::new(Box::new(1), vec![Box::new(2)]);
+    assert_eq!(**members.first(), 1);
+    assert_eq!(*members.rest()[0], 2);
+}
+
+#[test]
+fn resize_tail() {
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    struct Record {
+        pub sequence: u64,
+        pub level: u8,
+        pub entries: [u8],
+    }
+
+    // the tail starts inside what would be the padding of the header
+    let record = Record::new(1, 2, vec![3]);
+    let record = record.with_extended_tail(&[4, 5]);
+    assert_eq!(record.sequence, 1);
+    assert_eq!(record.level, 2);
+    assert_eq!(&record.entries, &[3, 4, 5]);
+    assert_eq!(
+        std::mem::size_of_val(&*record),
+        Record::layout_for_len(3).size()
+    );
+
+    let record = record.with_truncated_tail(1);
+    assert_eq!(record.level, 2);
+    assert_eq!(&record.entries, &[3]);
+    assert_eq!(std::mem::size_of_val(&*record), 16);
+
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    struct Log<T> {
+        pub name: String,
+        pub lines: [T],
+    }
+
+    let mut log = Log::new("log".to_string(), Vec::<String>::new());
+    for line in ["a", "b", "c"] {
+        log = log.with_extended_tail(&[line.to_string()]);
+    }
+    assert_eq!(log.name, "log");
+    assert_eq!(&log.lines, &["a", "b", "c"]);
+    let log = log.with_truncated_tail(2);
+    assert_eq!(&log.lines, &["a", "b"]);
+    let log = log.with_extended_tail_iter(["d", "e"].iter().map(|line| line.to_string()));
+    assert_eq!(&log.lines, &["a", "b", "d", "e"]);
+}
+
+#[test]
+fn owned() {
+    #[repr(C)]
+    #[derive(Debug, DynStruct)]
+    #[dyn_struct(owned(Debug, Clone, PartialEq))]
+    struct Log<'a, T> {
+        /// The name of the log.
+        pub name: &'a str,
+        #[dyn_struct(skip)]
+        pub flushed: bool,
+        pub lines: [T],
+    }
+
+    let log = Log::new("log", vec![1, 2]);
+    let mut owned = LogOwned::from(log);
+    assert_eq!(
+        owned,
+        LogOwned {
+            name: "log",
+            flushed: false,
+            lines: vec![1, 2]
+        }
+    );
+    owned.flushed = true;
+    owned.lines.push(3);
+    let log = owned.clone().into_dyn();
+    assert_eq!(log.name, "log");
+    assert!(log.flushed);
+    assert_eq!(&log.lines, [1, 2, 3]);
+    assert_eq!(LogOwned::from(log), owned);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(owned)]
+    struct Named(pub u32, pub str);
+
+    let mut named = NamedOwned::from(Named::new(1, "a"));
+    named.1.push_str("bc");
+    let named = named.into_dyn();
+    assert_eq!(named.0, 1);
+    assert_eq!(&named.1, "abc");
+}
+
+#[test]
+fn view() {
+    use std::fmt::Display;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(view)]
+    struct Entry<'a, T> {
+        /// The key of the entry.
+        pub key: &'a str,
+        pub values: [T],
+    }
+
+    fn total(entry: EntryRef<'_, '_, u32>) -> u32 {
+        entry.values.iter().sum()
+    }
+
+    let entries: Vec<Box<Entry<u32>>> =
+        IntoIterator::into_iter([("a", vec![1, 2]), ("b", vec![3])])
+            .map(|(key, values)| Entry::new(key, values))
+            .collect();
+    let index: Vec<EntryRef<'_, '_, u32>> = entries.iter().map(|entry| entry.as_view()).collect();
+    let view = index[0];
+    assert_eq!(*view.key, "a");
+    assert_eq!(total(view), 3);
+    assert_eq!(*index[1].key, "b");
+    assert_eq!(index.iter().map(|view| total(*view)).sum::<u32>(), 6);
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(view)]
+    struct Labeled<'view>(pub &'view str, pub dyn Display + 'view);
+
+    let labeled: Box<Labeled> = Labeled::new("count", 7);
+    let view = labeled.as_view();
+    assert_eq!(format!("{}: {}", view.0, view.1), "count: 7");
+}
+
+#[test]
+fn panicking_clone() {
+    use std::cell::Cell;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    thread_local! {
+        static CLONES: Cell<usize> = const { Cell::new(0) };
+        static DROPS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    // Panics on the third clone.
+    struct Bomb;
+    impl Clone for Bomb {
+        fn clone(&self) -> Self {
+            let clones = CLONES.with(|clones| clones.replace(clones.get() + 1));
+            assert!(clones < 2, "boom");
+            Bomb
+        }
+    }
+    impl Drop for Bomb {
+        fn drop(&mut self) {
+            DROPS.with(|drops| drops.set(drops.get() + 1));
+        }
+    }
+    fn reset() {
+        CLONES.with(|clones| clones.set(0));
+        DROPS.with(|drops| drops.set(0));
+    }
+    fn drops() -> usize {
+        DROPS.with(Cell::get)
+    }
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(clone)]
+    struct Bombs {
+        pub header: Bomb,
+        pub values: [Bomb],
+    }
+
+    let originals = [Bomb, Bomb, Bomb, Bomb];
+    reset();
+    let result = catch_unwind(AssertUnwindSafe(|| Bombs::new_from_slice(Bomb, &originals)));
+    assert!(result.is_err());
+    // The two clones written before the panic, and the header.
+    assert_eq!(drops(), 3);
+
+    let bombs = Bombs::new(Bomb, vec![Bomb, Bomb, Bomb]);
+    reset();
+    let result = catch_unwind(AssertUnwindSafe(|| bombs.clone_box()));
+    assert!(result.is_err());
+    // The header is cloned first, then one element before the panic.
+    assert_eq!(drops(), 2);
+
+    drop(bombs);
+    assert_eq!(drops(), 6);
+    drop(originals);
+}
+
+#[test]
+fn layout_failure_releases_values() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::rc::{Rc, Weak};
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Values {
+        pub owner: Rc<()>,
+        pub values: [Rc<()>],
+    }
+
+    // Only weak references are kept, so they dangle once the values are dropped.
+    let owner = Rc::new(());
+    let weak_owner: Weak<()> = Rc::downgrade(&owner);
+    let tail = Rc::new(());
+    let weak_tail: Weak<()> = Rc::downgrade(&tail);
+    let iter = (0..usize::MAX).map(move |_| tail.clone());
+
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let result = catch_unwind(AssertUnwindSafe(|| Values::new(owner, iter)));
+    std::panic::set_hook(hook);
+
+    assert!(result.is_err());
+    assert_eq!(weak_owner.strong_count(), 0);
+    assert_eq!(weak_tail.strong_count(), 0);
+}
+
+#[test]
+fn over_aligned_tails() {
+    use std::alloc::Layout;
+
+    macro_rules! aligned {
+        ($($name:ident = $align:literal),*) => {
+            $(
+                #[repr(C, align($align))]
+                #[derive(Clone, Copy, Default, Debug, PartialEq)]
+                struct $name(u8);
+            )*
+        };
+    }
+    aligned!(A1 = 1, A2 = 2, A4 = 4, A8 = 8, A16 = 16, A32 = 32, A64 = 64);
+
+    fn check<T: ?Sized>(value: &T, tail: *const u8, tail_offset: usize, tail_align: usize) {
+        let start = (value as *const T).cast::<u8>();
+        assert_eq!(tail as usize - start as usize, tail_offset);
+        assert_eq!(tail as usize % tail_align, 0);
+        assert_eq!(start as usize % std::mem::align_of_val(value), 0);
+    }
+
+    macro_rules! matrix {
+        ($($header:ty),* ; $elements:tt) => {
+            $(matrix!(@row $header ; $elements);)*
+        };
+        (@row $header:ty ; [$($element:ty),*]) => {$({
+            #[repr(C)]
+            #[derive(DynStruct)]
+            struct Value {
+                pub header: $header,
+                pub values: [$element],
+            }
+
+            for len in [0, 1, 3] {
+                let value = Value::new(Default::default(), vec![<$element>::default(); len]);
+                let tail = std::ptr::addr_of!(value.values).cast::<u8>();
+                let offset = Layout::new::<$header>()
+                    .extend(Layout::new::<$element>())
+                    .unwrap()
+                    .1;
+                assert_eq!(Value::TAIL_OFFSET, offset);
+                check(&*value, tail, offset, std::mem::align_of::<$element>());
+                assert_eq!(Layout::for_value(&*value), Value::layout_for_len(len));
+                assert!(value.values.iter().all(|element| *element == <$element>::default()));
+            }
+        })*};
+    }
+
+    matrix!(
+        (), A1, A2, A8, A64, (u8, A32), [A16; 0];
+        [A1, A2, A4, A8, A16, A32, A64, u128, (u8, A64), [A64; 0]]
+    );
+}
+
+#[test]
+fn auto_traits() {
+    use std::panic::{RefUnwindSafe, UnwindSafe};
+
+    use dyn_struct::{LayoutMismatch, MissingField, TryNewError};
+
+    // None of the types hold raw pointers or cells, so they are `Send`, `Sync`, `UnwindSafe` and
+    // `RefUnwindSafe` whenever their fields are. A new field which loses one of these properties
+    // fails to compile here.
+    fn check<T: ?Sized + Send + Sync + UnwindSafe + RefUnwindSafe>() {}
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(header = "RecordHeader")]
+    #[dyn_struct(owned)]
+    #[dyn_struct(view)]
+    struct Record<'a, T> {
+        pub name: &'a str,
+        pub values: [T],
+    }
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Job {
+        pub id: u32,
+        pub run: dyn Fn() -> u32 + Send + Sync + UnwindSafe + RefUnwindSafe,
+    }
+
+    check::<DynStruct<(u32, String), u8>>();
+    check::<Box<DynStruct<(u32, String), u8>>>();
+    check::<&DynStruct<(u32, String), u8>>();
+    check::<dyn_struct::DynSlab<(u32, String), u8>>();
+    check::<dyn_struct::SlabHandle>();
+    check::<dyn_struct::ThinDynStruct<(u32, String), u8>>();
+    check::<dyn_struct::ArcDynStruct<(u32, String), u8>>();
+    check::<dyn_struct::WeakDynStruct<(u32, String), u8>>();
+    check::<dyn_struct::AlignedDynStruct<(u32, String), u8>>();
+    check::<dyn_struct::SmallDynStruct<(u32, String), u8, 16>>();
+    check::<dyn_struct::DynVec<(u32, String), u8>>();
+    check::<TryNewError>();
+    check::<MissingField>();
+    check::<LayoutMismatch>();
+
+    check::<Record<'static, u64>>();
+    check::<Box<Record<'static, u64>>>();
+    check::<RecordHeader<'static, u64>>();
+    check::<RecordOwned<'static, u64>>();
+    check::<RecordRef<'static, 'static, u64>>();
+    check::<Box<Job>>();
+
+    // The values can be moved out of `catch_unwind` without `AssertUnwindSafe`.
+    let record = Record::new("a", vec![1u64, 2]);
+    let view = record.as_view();
+    assert_eq!((*view.name, view.values), ("a", &[1, 2][..]));
+    let record = std::panic::catch_unwind(move || RecordOwned::from(record)).unwrap();
+    assert_eq!((record.name, &record.values[..]), ("a", &[1, 2][..]));
+    let job: Box<Job> = Job::new(1, || 7);
+    assert_eq!(std::panic::catch_unwind(|| (job.run)()).unwrap(), 7);
+}
+
+#[test]
+fn variance() {
+    use std::fmt::Debug;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Entry<'a, T> {
+        pub key: &'a str,
+        pub values: [T],
+    }
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    struct Shown<'a> {
+        pub id: u32,
+        pub value: dyn Debug + 'a,
+    }
+
+    // Boxes own their contents, so they are covariant like any struct holding the values.
+    fn shorten_value<'a>(
+        value: Box<DynStruct<&'static str, &'static str>>,
+    ) -> Box<DynStruct<&'a str, &'a str>> {
+        value
+    }
+    fn shorten_entry<'a>(entry: Box<Entry<'static, &'static str>>) -> Box<Entry<'a, &'a str>> {
+        entry
+    }
+    fn shorten_shown<'a>(shown: Box<Shown<'static>>) -> Box<Shown<'a>> {
+        shown
+    }
+
+    let value = shorten_value(DynStruct::new("a", ["b"]));
+    assert_eq!((value.header, &value.tail), ("a", &["b"][..]));
+    let entry = shorten_entry(Entry::new("c", vec!["d"]));
+    assert_eq!((entry.key, &entry.values), ("c", &["d"][..]));
+    let shown = shorten_shown(Shown::new(1, "e"));
+    assert_eq!(shown.id, 1);
+    assert_eq!(format!("{:?}", &shown.value), "\"e\"");
+}
@@ -0,0 +1,98 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use dyn_struct::{DynStruct, SameRepr};
+
+#[repr(C)]
+#[derive(Debug, DynStruct)]
+struct Packet {
+    pub id: u32,
+    pub kind: u32,
+    pub payload: [u16],
+}
+
+unsafe impl SameRepr<Packet> for DynStruct<[u32; 2], u16> {}
+
+// Wrong on purpose: the header has a third `u32`, so the tail of a `Packet` would start earlier
+// and the value would be freed with a smaller size.
+unsafe impl SameRepr<Packet> for DynStruct<[u32; 3], u16> {}
+
+fn panic_message<T>(f: impl FnOnce() -> T) -> String {
+    let payload = catch_unwind(AssertUnwindSafe(f)).err().unwrap();
+    *payload.downcast::<String>().unwrap()
+}
+
+#[test]
+fn matching_layouts() {
+    let value = DynStruct::new([1u32, 2], [3u16, 4]);
+    let value = DynStruct::with_extended_tail(value, &[5, 6, 7]);
+    let value = DynStruct::with_truncated_tail(value, 4);
+
+    let (ptr, len) = DynStruct::into_raw_parts(value);
+    let value = unsafe { DynStruct::<[u32; 2], u16>::from_raw_parts(ptr, len) };
+
+    let packet = DynStruct::into_repr::<Packet>(value);
+    let (ptr, len) = Packet::into_raw_parts(packet);
+    let packet = unsafe { Packet::from_raw_parts(ptr, len) };
+    let packet = packet.with_extended_tail(&[8]);
+    let value = DynStruct::<[u32; 2], u16>::from_repr(packet);
+    assert_eq!(value.header, [1, 2]);
+    assert_eq!(&value.tail, &[3, 4, 5, 6, 8]);
+
+    let (header, tail) = DynStruct::into_parts(value);
+    let value = DynStruct::from_vec(header, tail);
+    assert_eq!(value.tail.len(), 5);
+}
+
+#[test]
+fn wrong_repr() {
+    let value = DynStruct::new([1u32, 2, 3], [4u16, 5]);
+    let message = panic_message(|| DynStruct::into_repr::<Packet>(value));
+    assert!(
+        message.ends_with(
+            "was allocated with size 16 and alignment 4, but would be freed with size 12 and \
+             alignment 4"
+        ),
+        "{}",
+        message
+    );
+
+    let packet = Packet::new(1, 2, vec![3, 4]);
+    let message = panic_message(|| DynStruct::<[u32; 3], u16>::from_repr(packet));
+    assert!(
+        message.ends_with(
+            "was allocated with size 12 and alignment 4, but would be freed with size 16 and \
+             alignment 4"
+        ),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn wrong_raw_parts() {
+    let (ptr, len) = DynStruct::into_raw_parts(DynStruct::new(1u32, [2u32, 3, 4]));
+    let message = panic_message(|| unsafe { DynStruct::<u64, u32>::from_raw_parts(ptr, len) });
+    assert!(
+        message.ends_with(
+            "was allocated with size 16 and alignment 4, but would be freed with size 24 and \
+             alignment 8"
+        ),
+        "{}",
+        message
+    );
+    // Nothing was freed, so the parts can still be reassembled with the right type.
+    let value = unsafe { DynStruct::<u32, u32>::from_raw_parts(ptr, len) };
+    assert_eq!(&value.tail, &[2, 3, 4]);
+
+    let (ptr, len) = DynStruct::into_raw_parts(DynStruct::new(1u64, [2u64]));
+    let message = panic_message(|| unsafe { Packet::from_raw_parts(ptr, len) });
+    assert!(
+        message.ends_with(
+            "was allocated with size 16 and alignment 8, but would be freed with size 12 and \
+             alignment 4"
+        ),
+        "{}",
+        message
+    );
+    drop(unsafe { DynStruct::<u64, u64>::from_raw_parts(ptr, len) });
+}
@@ -0,0 +1,59 @@
+//! The generated code is compiled with strict lints, as downstream crates may use them. Public
+//! items without documentation, and any warning from clippy, fail the build (clippy runs on the
+//! tests as well).
+
+#![deny(missing_docs, warnings, clippy::all, clippy::pedantic)]
+
+use dyn_struct::DynStruct;
+
+/// A message with more fields than clippy allows function arguments.
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(
+    try_new,
+    rc,
+    arc,
+    zeroable,
+    uninit,
+    clone,
+    default,
+    builder,
+    getters,
+    header = "MessageHeader",
+    eq,
+    hash,
+    owned(Debug),
+    view
+)]
+pub struct Message<T> {
+    /// The kind of message.
+    pub kind: u8,
+    /// The sender.
+    pub from: u8,
+    /// The receiver.
+    pub to: u8,
+    /// The priority.
+    pub priority: u8,
+    /// The number of times the message was sent.
+    pub attempts: u8,
+    /// The time to live.
+    pub ttl: u8,
+    /// Some other data.
+    pub extra: T,
+    /// The payload.
+    pub payload: [u8],
+}
+
+/// A tuple struct with a `str` tail.
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(try_new, clone, default, eq, hash)]
+pub struct Label(pub u32, pub str);
+
+#[test]
+fn strict_lints() {
+    let message = Message::new(1, 2, 3, 4, 5, 6, 7u8, vec![8, 9]);
+    assert_eq!(message.payload, [8, 9]);
+    assert!(Message::new_from_slice(1, 2, 3, 4, 5, 6, 7u8, &[8, 9]) == message);
+    assert_eq!(&Label::new(1, "label").1, "label");
+}
@@ -0,0 +1,73 @@
+//! The generated code must not depend on anything from the prelude of `std`.
+
+#![no_std]
+
+extern crate alloc;
+extern crate std;
+
+use alloc::vec;
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(
+    try_new, rc, arc, zeroable, uninit, pod, clone, default, builder, getters
+)]
+struct Message {
+    id: u32,
+    values: [u32],
+}
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(clone, default, builder)]
+struct Symbol {
+    #[dyn_struct(into)]
+    id: u64,
+    #[dyn_struct(skip)]
+    marker: core::marker::PhantomData<()>,
+    name: str,
+}
+
+#[repr(C)]
+#[derive(DynStruct)]
+struct Task {
+    priority: u8,
+    job: dyn Fn() -> u8,
+}
+
+#[repr(C)]
+#[derive(DynStruct)]
+struct Nested {
+    id: u8,
+    symbol: Symbol,
+}
+
+#[test]
+fn no_std() {
+    let message = Message::new(1, vec![2, 3]);
+    assert_eq!(message.id(), 1);
+    assert_eq!(message.values(), [2, 3]);
+    assert_eq!(message.as_bytes().len(), 12);
+    assert_eq!(message.clone().values.len(), 2);
+    assert!(Message::try_new(1, []).is_ok());
+    assert_eq!(Message::new_rc(1, [2]).values.len(), 1);
+    assert_eq!(Message::new_arc(1, [2]).values.len(), 1);
+    assert_eq!(&Message::new_zeroed(1, 2).values, [0, 0]);
+    assert_eq!(Message::new_uninit(1, 1).values.len(), 1);
+    assert!(alloc::boxed::Box::<Message>::default().values.is_empty());
+    assert!(Message::builder().build().is_err());
+    assert_eq!(message.into_parts(), (1, vec![2, 3]));
+
+    let symbol = Symbol::new(1u8, "name");
+    assert_eq!(&symbol.clone().name, "name");
+    assert_eq!(symbol.into_string(), "name");
+    assert_eq!(Symbol::builder().id(2).name("a").build().unwrap().id, 2);
+    assert_eq!(&alloc::boxed::Box::<Symbol>::default().name, "");
+
+    let task = Task::new(1, || 2);
+    assert_eq!((task.job)(), 2);
+
+    let nested = Nested::new(1, Symbol::new(2u8, "nested"));
+    assert_eq!(&nested.symbol.name, "nested");
+}
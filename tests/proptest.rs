@@ -0,0 +1,167 @@
+//! Builds values with random header and tail types from a fixed menu, and random tails, and checks
+//! them against the layout the compiler gives them. Failures shrink towards the first types of
+//! each menu and towards short tails.
+
+use std::alloc::Layout;
+use std::fmt::Debug;
+
+use dyn_struct::DynStruct;
+use proptest::prelude::*;
+
+/// Deterministically builds a value from a byte, so that every element of a tail can be compared
+/// after it has been written.
+trait Sample: Clone + PartialEq + Debug {
+    fn sample(seed: u8) -> Self;
+}
+
+macro_rules! sample_int {
+    ($($ty:ty),*) => {
+        $(impl Sample for $ty {
+            fn sample(seed: u8) -> Self {
+                <$ty>::from(seed).wrapping_mul(0x5f)
+            }
+        })*
+    };
+}
+
+sample_int!(u8, u16, u32, u64, u128);
+
+impl Sample for () {
+    fn sample(_: u8) -> Self {}
+}
+
+impl Sample for [u8; 3] {
+    fn sample(seed: u8) -> Self {
+        [seed, !seed, seed.wrapping_add(1)]
+    }
+}
+
+impl Sample for [u32; 0] {
+    fn sample(_: u8) -> Self {
+        []
+    }
+}
+
+impl Sample for (u16, u8) {
+    fn sample(seed: u8) -> Self {
+        (u16::sample(seed), !seed)
+    }
+}
+
+impl Sample for (u8, u64) {
+    fn sample(seed: u8) -> Self {
+        (seed, u64::sample(!seed))
+    }
+}
+
+impl Sample for String {
+    fn sample(seed: u8) -> Self {
+        seed.to_string()
+    }
+}
+
+#[repr(C, align(16))]
+#[derive(Clone, PartialEq, Debug)]
+struct Aligned(u8);
+
+impl Sample for Aligned {
+    fn sample(seed: u8) -> Self {
+        Aligned(seed)
+    }
+}
+
+#[repr(C)]
+#[derive(DynStruct)]
+struct Shaped<Header, Tail> {
+    pub header: Header,
+    pub tail: [Tail],
+}
+
+fn check<Header: Sample, Tail: Sample>(header_seed: u8, seeds: &[u8]) {
+    let header = Header::sample(header_seed);
+    let tail: Vec<Tail> = seeds.iter().copied().map(Tail::sample).collect();
+
+    let value = DynStruct::new(header.clone(), tail.iter().cloned());
+    assert_eq!(value.header, header);
+    assert_eq!(&value.tail, &tail[..]);
+
+    // This is how the compiler lays out a `#[repr(C)]` struct with the same fields.
+    let (layout, offset) = Layout::new::<Header>()
+        .extend(Layout::array::<Tail>(tail.len()).unwrap())
+        .unwrap();
+    assert_eq!(Layout::for_value(&*value), layout.pad_to_align());
+    let base = &*value as *const DynStruct<Header, Tail> as *const u8;
+    assert_eq!(value.tail.as_ptr() as usize - base as usize, offset);
+    assert_eq!(DynStruct::<Header, Tail>::TAIL_OFFSET, offset);
+    assert_eq!(
+        DynStruct::<Header, Tail>::size_for_len(tail.len()),
+        layout.pad_to_align().size()
+    );
+
+    let shaped = Shaped::new(header.clone(), tail.clone());
+    assert_eq!(shaped.header, header);
+    assert_eq!(&shaped.tail, &tail[..]);
+    assert_eq!(
+        Layout::for_value(&*shaped),
+        Shaped::<Header, Tail>::layout_for_len(tail.len())
+    );
+    assert_eq!(Layout::for_value(&*shaped), Layout::for_value(&*value));
+    let base = &*shaped as *const Shaped<Header, Tail> as *const u8;
+    assert_eq!(
+        shaped.tail.as_ptr() as usize - base as usize,
+        Shaped::<Header, Tail>::TAIL_OFFSET
+    );
+    assert_eq!(Shaped::<Header, Tail>::TAIL_OFFSET, offset);
+}
+
+/// Calls `check` with the header type at index `$header` and the tail type at index `$tail`.
+macro_rules! dispatch {
+    ($header:expr, $tail:expr, $args:tt; [$($h:ty),*]; $tails:tt) => {
+        dispatch!(@headers $header, $tail, $args; 0usize; [$($h),*]; $tails)
+    };
+    (@headers $header:expr, $tail:expr, $args:tt; $index:expr; [$h:ty $(, $rest:ty)*]; $tails:tt) => {
+        if $header == $index {
+            dispatch!(@tails $tail, $args; $h; 0usize; $tails)
+        } else {
+            dispatch!(@headers $header, $tail, $args; $index + 1; [$($rest),*]; $tails)
+        }
+    };
+    (@headers $header:expr, $tail:expr, $args:tt; $index:expr; []; $tails:tt) => {
+        unreachable!()
+    };
+    (@tails $tail:expr, ($($arg:expr),*); $h:ty; $index:expr; [$t:ty $(, $rest:ty)*]) => {
+        if $tail == $index {
+            check::<$h, $t>($($arg),*)
+        } else {
+            dispatch!(@tails $tail, ($($arg),*); $h; $index + 1; [$($rest),*])
+        }
+    };
+    (@tails $tail:expr, $args:tt; $h:ty; $index:expr; []) => {
+        unreachable!()
+    };
+}
+
+const HEADERS: usize = 10;
+const TAILS: usize = 11;
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: if cfg!(miri) { 8 } else { 512 },
+        failure_persistence: None,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn layout_matches_compiler(
+        header in 0..HEADERS,
+        tail in 0..TAILS,
+        header_seed in any::<u8>(),
+        seeds in proptest::collection::vec(any::<u8>(), 0..40),
+    ) {
+        dispatch!(
+            header, tail, (header_seed, &seeds);
+            [(), u8, u16, u64, u128, [u8; 3], [u32; 0], (u16, u8), (u8, u64), Aligned];
+            [(), u8, u16, u32, u64, u128, [u8; 3], [u32; 0], (u16, u8), String, Aligned]
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct, Debug, PartialEq)]
+#[dyn_struct(serde)]
+struct Message {
+    #[serde(rename = "message_id")]
+    pub id: u32,
+    pub r#type: u8,
+    pub values: [u16],
+}
+
+#[test]
+fn round_trip() {
+    let message = Message::new(7, 2, [1, 2, 3]);
+    let json = serde_json::to_string(&message).unwrap();
+    assert_eq!(json, r#"{"message_id":7,"type":2,"values":[1,2,3]}"#);
+
+    let back: Box<Message> = serde_json::from_str(&json).unwrap();
+    assert_eq!(back, message);
+}
+
+#[test]
+fn str_tail() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(serde)]
+    #[serde(rename_all = "UPPERCASE")]
+    struct Named<T> {
+        pub owner: T,
+        pub name: str,
+    }
+
+    let named = Named::new(vec![String::from("a")], "name");
+    let json = serde_json::to_string(&named).unwrap();
+    assert_eq!(json, r#"{"OWNER":["a"],"NAME":"name"}"#);
+
+    let back: Box<Named<Vec<String>>> = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.owner, ["a"]);
+    assert_eq!(&back.name, "name");
+}
+
+#[test]
+fn tuple_struct() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(serde)]
+    struct Pair(pub bool, pub [String]);
+
+    let pair = Pair::new(true, vec![String::from("x")]);
+    let json = serde_json::to_string(&pair).unwrap();
+    assert_eq!(json, r#"[true,["x"]]"#);
+
+    let back: Box<Pair> = serde_json::from_str(&json).unwrap();
+    assert!(back.0);
+    assert_eq!(&back.1, ["x"]);
+    assert!(serde_json::from_str::<Box<Pair>>(r#"[true]"#).is_err());
+}
+
+#[test]
+fn skipped_field() {
+    use std::marker::PhantomData;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(serde)]
+    struct Tagged<T> {
+        #[dyn_struct(skip)]
+        _marker: PhantomData<T>,
+        pub values: [u8],
+    }
+
+    let tagged = Tagged::<String>::new([1, 2]);
+    let json = serde_json::to_string(&tagged).unwrap();
+    assert_eq!(json, r#"{"values":[1,2]}"#);
+
+    let back: Box<Tagged<String>> = serde_json::from_str(&json).unwrap();
+    assert_eq!(&back.values, [1, 2]);
+}
+
+mod facade {
+    pub use dyn_struct as dst;
+}
+
+#[test]
+fn renamed_crate() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(serde, crate = "crate::facade::dst")]
+    struct Name(pub u8, pub str);
+
+    let json = serde_json::to_string(&Name::new(1, "a")).unwrap();
+    assert_eq!(json, r#"[1,"a"]"#);
+    let back: Box<Name> = serde_json::from_str(&json).unwrap();
+    assert_eq!(&back.1, "a");
+}
+
+#[test]
+fn associated_types() {
+    trait Codec {
+        type Meta;
+        type Item;
+    }
+
+    struct Text;
+    impl Codec for Text {
+        type Meta = String;
+        type Item = char;
+    }
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(serde)]
+    struct Batch<C: Codec> {
+        meta: <C as Codec>::Meta,
+        items: [C::Item],
+    }
+
+    let batch = Batch::<Text>::new("meta".to_string(), vec!['a', 'b']);
+    let json = serde_json::to_string(&batch).unwrap();
+    assert_eq!(json, r#"{"meta":"meta","items":["a","b"]}"#);
+
+    let back: Box<Batch<Text>> = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.meta, "meta");
+    assert_eq!(&back.items, &['a', 'b']);
+}
+
+#[test]
+fn owned() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(owned(serde::Deserialize))]
+    #[serde(rename_all = "UPPERCASE")]
+    struct Config {
+        #[serde(default)]
+        pub verbose: bool,
+        pub paths: [String],
+    }
+
+    let config: ConfigOwned = serde_json::from_str(r#"{"PATHS":["a","b"]}"#).unwrap();
+    let config = config.into_dyn();
+    assert!(!config.verbose);
+    assert_eq!(&config.paths, ["a", "b"]);
+}
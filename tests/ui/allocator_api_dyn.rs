@@ -0,0 +1,11 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(allocator_api)]
+struct Foo {
+    id: u32,
+    tail: dyn std::fmt::Debug,
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(builder)]
+struct Foo(u32, [u8]);
+
+fn main() {}
@@ -0,0 +1,23 @@
+use bumpalo::Bump;
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(bumpalo)]
+struct Node {
+    pub kind: u8,
+    pub children: [u32],
+}
+
+fn main() {
+    // The references borrow the arena, so they may not outlive it.
+    let value = {
+        let bump = Bump::new();
+        DynStruct::new_in_bump(1u8, [2u32, 3], &bump)
+    };
+    let node = {
+        let bump = Bump::new();
+        Node::new_in_bump(1, [2, 3], &bump)
+    };
+    println!("{} {}", value.header, node.kind);
+}
@@ -0,0 +1,17 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+struct Inner {
+    pub values: [u8],
+}
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(bumpalo)]
+struct Outer {
+    pub id: u32,
+    pub inner: Inner,
+}
+
+fn main() {}
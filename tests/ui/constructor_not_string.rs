@@ -0,0 +1,11 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(constructor = 1)]
+struct Foo {
+    pub inner: u32,
+    pub values: [u32],
+}
+
+fn main() {}
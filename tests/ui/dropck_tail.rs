@@ -0,0 +1,19 @@
+use std::cell::Cell;
+
+use dyn_struct::DynStruct;
+
+struct Counter<'a>(&'a Cell<u32>);
+
+impl Drop for Counter<'_> {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}
+
+fn main() {
+    // The value drops its tail, which still uses `count`, after `count` itself is gone.
+    let value;
+    let count = Cell::new(0);
+    value = DynStruct::new((), [Counter(&count)]);
+    let _ = &value;
+}
@@ -0,0 +1,14 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+struct Foo {
+    pub inner: u32,
+    pub values: [u32],
+}
+
+impl Foo {
+    pub fn new() {}
+}
+
+fn main() {}
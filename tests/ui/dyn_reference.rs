@@ -0,0 +1,18 @@
+use std::fmt::Debug;
+
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+struct Message {
+    pub id: u32,
+    pub value: dyn Debug,
+}
+
+fn main() {
+    // Without a lifetime in the trait object the tail is `'static`, so a borrowed value cannot be
+    // stored behind it.
+    let local = String::from("borrowed");
+    let message = Message::new(1, &local);
+    println!("{:?}", &message.value);
+}
@@ -0,0 +1,7 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+struct Foo {}
+
+fn main() {}
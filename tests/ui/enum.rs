@@ -0,0 +1,10 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+enum Foo {
+    Empty,
+    Values([u8; 4]),
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use dyn_struct::DynStruct;
+
+struct Handle;
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(eq)]
+struct Foo {
+    handle: Handle,
+    tail: [u8],
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use dyn_struct::DynStruct;
+
+struct Unique;
+
+#[repr(C)]
+#[derive(DynStruct)]
+struct Foo {
+    pub inner: u32,
+    pub values: [Unique],
+}
+
+fn main() {
+    let _ = Foo::new_from_slice(1, &[Unique]);
+}
@@ -0,0 +1,11 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(getters)]
+struct Foo {
+    len: usize,
+    tail: [u8],
+}
+
+fn main() {}
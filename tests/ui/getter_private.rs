@@ -0,0 +1,18 @@
+mod inner {
+    use dyn_struct::DynStruct;
+
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(getters)]
+    pub struct Foo {
+        #[dyn_struct(vis = "pub(self)")]
+        id: u32,
+        tail: [u8],
+    }
+}
+
+fn main() {
+    let foo = inner::Foo::new(1, vec![2]);
+    let _ = foo.tail();
+    let _ = foo.id();
+}
@@ -0,0 +1,15 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(header = "FooHeader")]
+struct Foo {
+    pub wide: u32,
+    pub narrow: u8,
+    pub values: [u8],
+}
+
+fn main() {
+    let foo = Foo::new(1, 2, vec![3]);
+    let _ = foo.as_dyn_struct();
+}
@@ -0,0 +1,11 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(header = "FooHeader")]
+struct Foo {
+    pub id: u32,
+    pub name: str,
+}
+
+fn main() {}
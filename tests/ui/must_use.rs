@@ -0,0 +1,18 @@
+#![deny(unused_must_use)]
+
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(rc, builder)]
+struct Foo {
+    inner: u32,
+    values: [u32],
+}
+
+fn main() {
+    Foo::new(1, vec![2, 3]);
+    Foo::new_rc(1, vec![2, 3]);
+    Foo::new_from_slice(1, &[2, 3]);
+    Foo::builder();
+}
@@ -0,0 +1,11 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(pod)]
+struct Foo {
+    pub flag: bool,
+    pub values: [u8],
+}
+
+fn main() {}
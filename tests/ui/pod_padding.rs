@@ -0,0 +1,14 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(pod)]
+struct Foo {
+    pub inner: u8,
+    pub values: [u32],
+}
+
+fn main() {
+    let foo = Foo::new(1, vec![2]);
+    let _ = foo.as_bytes();
+}
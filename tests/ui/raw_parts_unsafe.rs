@@ -0,0 +1,14 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+struct Foo {
+    inner: u32,
+    values: [u32],
+}
+
+fn main() {
+    let (ptr, len) = Foo::into_raw_parts(Foo::new(1, vec![2, 3]));
+    let _ = DynStruct::<u64, u64>::from_raw_parts(ptr, len);
+    let _ = Foo::from_raw_parts(ptr, len);
+}
@@ -0,0 +1,9 @@
+use dyn_struct::DynStruct;
+
+#[derive(DynStruct)]
+struct Foo {
+    pub inner: u32,
+    pub values: [u32],
+}
+
+fn main() {}
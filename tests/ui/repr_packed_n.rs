@@ -0,0 +1,10 @@
+use dyn_struct::DynStruct;
+
+#[repr(C, packed(2))]
+#[derive(DynStruct)]
+struct Foo {
+    pub inner: u32,
+    pub values: [u32],
+}
+
+fn main() {}
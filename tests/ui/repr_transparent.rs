@@ -0,0 +1,9 @@
+use dyn_struct::DynStruct;
+
+#[repr(transparent)]
+#[derive(DynStruct)]
+struct Foo {
+    pub values: [u32],
+}
+
+fn main() {}
@@ -0,0 +1,22 @@
+mod outer {
+    pub mod inner {
+        use dyn_struct::DynStruct;
+
+        #[repr(C)]
+        #[derive(DynStruct)]
+        #[dyn_struct(vis = "pub(super)")]
+        pub struct Foo {
+            pub inner: u32,
+            pub values: [u32],
+        }
+    }
+
+    pub fn make() -> Box<inner::Foo> {
+        inner::Foo::new(1, vec![2, 3])
+    }
+}
+
+fn main() {
+    let _ = outer::make();
+    let _ = outer::inner::Foo::new(1, vec![2, 3]);
+}
@@ -0,0 +1,14 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+struct Packet {
+    pub id: u32,
+    pub kind: u32,
+    pub payload: [u16],
+}
+
+fn main() {
+    let value = DynStruct::new([1u32, 2], [3u16, 4]);
+    let _ = DynStruct::<[u32; 2], u16>::into_repr::<Packet>(value);
+}
@@ -0,0 +1,10 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+struct Foo<T> {
+    pub inner: u32,
+    pub values: T,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+struct Foo {
+    id: u32,
+    #[dyn_struct(skip)]
+    tail: [u8],
+}
+
+fn main() {}
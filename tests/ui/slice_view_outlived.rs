@@ -0,0 +1,10 @@
+use dyn_struct::DynStruct;
+
+fn view() -> &'static DynStruct<[u32; 1], u32> {
+    let values = vec![1, 2, 3];
+    DynStruct::<[u32; 1], u32>::slice_view(&values)
+}
+
+fn main() {
+    assert_eq!(view().header, [1]);
+}
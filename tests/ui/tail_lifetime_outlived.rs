@@ -0,0 +1,16 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+struct Spans<'a> {
+    count: u32,
+    spans: [&'a str],
+}
+
+fn main() {
+    let spans = {
+        let arena = String::from("one two");
+        Spans::new(2, vec![&arena[..3], &arena[4..]])
+    };
+    assert_eq!(spans.count, 2);
+}
@@ -0,0 +1,10 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+union Foo {
+    number: u32,
+    bytes: [u8; 4],
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+struct Foo {
+    #[dyn_struct(intoo)]
+    id: u32,
+    tail: [u8],
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use dyn_struct::DynStruct;
+
+fn main() {
+    let value = DynStruct::new(1u32, [2u32, 3]);
+    let (header, tail) = DynStruct::into_parts(value);
+    assert_eq!(value.header, header);
+    assert_eq!(value.tail, tail[..]);
+}
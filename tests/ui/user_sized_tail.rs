@@ -0,0 +1,14 @@
+use dyn_struct::DynStruct;
+
+struct Point {
+    pub value: u32,
+}
+
+#[repr(C)]
+#[derive(DynStruct)]
+struct Foo {
+    pub inner: u32,
+    pub values: Point,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use dyn_struct::DynStruct;
+
+// Shared values are covariant, but a mutable reference cannot shorten the lifetime of the header,
+// since a borrowed `&str` could then be written to a value that outlives it.
+fn shorten<'a>(value: &'a mut DynStruct<&'static str, u8>, local: &'a str) {
+    let value: &mut DynStruct<&'a str, u8> = value;
+    value.header = local;
+}
+
+fn main() {}
@@ -0,0 +1,17 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(view)]
+struct Foo {
+    inner: u32,
+    values: [u32],
+}
+
+fn main() {
+    let view = {
+        let foo = Foo::new(1, vec![2, 3]);
+        foo.as_view()
+    };
+    assert_eq!(*view.inner, 1);
+}
@@ -0,0 +1,11 @@
+use dyn_struct::DynStruct;
+
+#[repr(C)]
+#[derive(DynStruct)]
+#[dyn_struct(zeroable)]
+struct Foo {
+    pub inner: u32,
+    pub values: [String],
+}
+
+fn main() {}
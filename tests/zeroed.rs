@@ -0,0 +1,95 @@
+//! Zero-filled values should get their memory from `alloc_zeroed`, so that the allocator can hand
+//! out memory which is already zero instead of the process writing every byte.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use dyn_struct::{DynStruct, DynStructPool};
+
+/// Records, for the current thread, the sizes of the blocks allocated with `alloc_zeroed`. Only
+/// allocations inside `watch` are counted. Plain allocations are not, since the `layout-tracking`
+/// feature allocates for its own bookkeeping.
+struct Counting;
+
+thread_local! {
+    static WATCHING: Cell<bool> = const { Cell::new(false) };
+    static ZEROED: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        if WATCHING.with(Cell::get) {
+            ZEROED.with(|zeroed| {
+                let (count, bytes) = zeroed.get();
+                zeroed.set((count + 1, bytes + layout.size()));
+            });
+        }
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Runs `f` and returns its result, together with the number of blocks it allocated with
+/// `alloc_zeroed` and their total size.
+fn watch<R>(f: impl FnOnce() -> R) -> (R, (usize, usize)) {
+    ZEROED.with(|zeroed| zeroed.set((0, 0)));
+    WATCHING.with(|watching| watching.set(true));
+    let result = f();
+    WATCHING.with(|watching| watching.set(false));
+    (result, ZEROED.with(Cell::get))
+}
+
+#[test]
+fn new_zeroed() {
+    let (value, zeroed) = watch(|| DynStruct::<(u8, u64), u32>::new_zeroed((1, 2), 4096));
+    assert_eq!(zeroed, (1, size_of_val(&*value)));
+    assert_eq!(value.header, (1, 2));
+    assert!(value.tail.iter().all(|&value| value == 0));
+
+    // The layout is the same as the one of a value built element by element, so both are freed the
+    // same way.
+    let written = DynStruct::new((1u8, 2u64), vec![0u32; 4096]);
+    assert_eq!(Layout::for_value(&*value), Layout::for_value(&*written));
+    assert_eq!(*value, *written);
+}
+
+#[test]
+fn derived() {
+    #[repr(C)]
+    #[derive(DynStruct)]
+    #[dyn_struct(zeroable)]
+    struct Buffer {
+        pub id: u64,
+        pub flags: u8,
+        pub bytes: [u8],
+    }
+
+    let (buffer, zeroed) = watch(|| Buffer::new_zeroed(1, 2, 4096));
+    assert_eq!(zeroed, (1, size_of_val(&*buffer)));
+    assert_eq!((buffer.id, buffer.flags), (1, 2));
+    assert!(buffer.bytes.iter().all(|&byte| byte == 0));
+}
+
+#[test]
+fn pool() {
+    let pool = DynStructPool::<u32, u64>::new();
+    // New allocations of the pool start out as zeros, whether or not that was asked for.
+    let (value, zeroed) = watch(|| pool.acquire(1, 1000));
+    assert_eq!(zeroed, (1, size_of_val(&*value)));
+    drop(value);
+
+    // Reused ones are zeroed by hand, without allocating.
+    let (value, zeroed) = watch(|| pool.acquire_zeroed(2, 1000));
+    assert_eq!(zeroed, (0, 0));
+    assert!(value.tail.iter().all(|&value| value == 0));
+}